@@ -0,0 +1,9 @@
+-- Track partial downloads so they can be resumed with HTTP Range requests
+
+-- Number of bytes already written to the ".part" file
+ALTER TABLE episodes ADD COLUMN resume_offset INTEGER NOT NULL DEFAULT 0;
+
+-- Validators returned by the server for the partial file
+-- A resume is only attempted when they still match (sent via If-Range)
+ALTER TABLE episodes ADD COLUMN resume_etag TEXT;
+ALTER TABLE episodes ADD COLUMN resume_last_modified TEXT;
@@ -0,0 +1,15 @@
+-- Automatic retry with exponential backoff
+
+-- Per-subscription overrides (NULL means use the global settings)
+ALTER TABLE subscriptions ADD COLUMN retry_max_attempts INTEGER DEFAULT NULL;
+ALTER TABLE subscriptions ADD COLUMN retry_base_delay_seconds INTEGER DEFAULT NULL;
+
+-- When a queued retry becomes eligible (NULL means as soon as possible)
+ALTER TABLE download_queue ADD COLUMN next_attempt_at DATETIME DEFAULT NULL;
+
+-- Global retry settings
+INSERT OR IGNORE INTO settings (key, value) VALUES
+  ('retry_max_attempts', '3'),
+  ('retry_base_delay_seconds', '30'),
+  ('retry_max_delay_seconds', '3600'),
+  ('retry_jitter_percent', '20');
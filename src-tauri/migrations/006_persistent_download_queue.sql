@@ -0,0 +1,4 @@
+-- Make download_queue the source of truth for the download manager
+
+-- Where the file should be written (NULL means derive it from the subscription)
+ALTER TABLE download_queue ADD COLUMN output_path TEXT DEFAULT NULL;
@@ -0,0 +1,6 @@
+-- Pause and resume individual downloads or the whole queue
+
+-- Paused entries keep their place in the queue but are not started
+ALTER TABLE download_queue ADD COLUMN paused BOOLEAN NOT NULL DEFAULT 0;
+
+INSERT OR IGNORE INTO settings (key, value) VALUES ('downloads_paused', 'false');
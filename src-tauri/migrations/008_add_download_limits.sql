@@ -0,0 +1,3 @@
+-- Global bandwidth cap and per-host connection limit, 0 means unlimited
+INSERT OR IGNORE INTO settings (key, value) VALUES ('bandwidth_limit_bytes_per_sec', '0');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('max_connections_per_host', '0');
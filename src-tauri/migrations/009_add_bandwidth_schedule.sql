@@ -0,0 +1,9 @@
+-- Weekly bandwidth schedule, a JSON list of windows such as
+-- [{"start": "01:00", "end": "06:00", "mode": "unlimited"},
+--  {"days": [0, 1, 2, 3, 4], "start": "07:00", "end": "09:00", "mode": "paused"},
+--  {"start": "18:00", "end": "20:00", "mode": "limited", "limit_bytes_per_sec": 204800}]
+-- Outside of every window bandwidth_limit_bytes_per_sec applies
+INSERT OR IGNORE INTO settings (key, value) VALUES ('bandwidth_schedule', '[]');
+
+-- Urgent subscriptions download at the regular limit whatever the schedule says
+ALTER TABLE subscriptions ADD COLUMN ignore_bandwidth_schedule BOOLEAN NOT NULL DEFAULT 0;
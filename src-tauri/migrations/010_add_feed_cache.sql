@@ -0,0 +1,12 @@
+-- Last fetched feed per subscription, for conditional GET and offline lookups
+CREATE TABLE IF NOT EXISTS feed_cache (
+  subscription_id INTEGER PRIMARY KEY,
+  -- URL the body was fetched from, validators are only reused for the same URL
+  url TEXT NOT NULL,
+  etag TEXT,
+  last_modified TEXT,
+  body TEXT NOT NULL,
+  fetched_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+  FOREIGN KEY (subscription_id) REFERENCES subscriptions(id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+-- Options of the shared HTTP client
+INSERT OR IGNORE INTO settings (key, value) VALUES ('http_connect_timeout_seconds', '15');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('http_read_timeout_seconds', '60');
+-- Empty means PodcastSync/<version>
+INSERT OR IGNORE INTO settings (key, value) VALUES ('http_user_agent', '');
+-- http://, https://, socks5:// or socks5h:// URL, empty for a direct connection
+INSERT OR IGNORE INTO settings (key, value) VALUES ('http_proxy_url', '');
+-- PEM files with extra CA certificates, separated by new lines or semicolons
+INSERT OR IGNORE INTO settings (key, value) VALUES ('http_ca_certificates', '');
+INSERT OR IGNORE INTO settings (key, value) VALUES ('http_max_redirects', '10');
@@ -0,0 +1,4 @@
+-- How episodes of a subscription are recognised across feed checks:
+-- 'guid', 'enclosure_url', 'normalized_url' or 'content_hash'
+-- The computed key is stored in episodes.guid
+ALTER TABLE subscriptions ADD COLUMN identity_strategy TEXT NOT NULL DEFAULT 'guid';
@@ -0,0 +1,7 @@
+-- Set when the feed replaced the audio of an already known episode
+ALTER TABLE episodes ADD COLUMN content_updated_at DATETIME;
+
+-- What to do with updated episodes that were already downloaded:
+-- 'flag' only marks them, 'replace' downloads the new file over the previous one,
+-- 'keep_previous' renames the previous file to "<name>.v1.<ext>" before downloading
+INSERT OR IGNORE INTO settings (key, value) VALUES ('updated_episode_action', 'flag');
@@ -0,0 +1,4 @@
+-- Defaults for subscriptions created without a form, e.g. by an OPML import
+INSERT OR IGNORE INTO settings (key, value) VALUES
+  ('default_preferred_quality', 'enclosure'),
+  ('default_filename_format', '{show} - {episode}');
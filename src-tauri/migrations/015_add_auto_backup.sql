@@ -0,0 +1,7 @@
+-- Automatic database backups, every auto_backup_interval_hours (0 = never),
+-- keeping the auto_backup_keep newest ones
+-- An empty directory means "backups" in the app data directory
+INSERT OR IGNORE INTO settings (key, value) VALUES
+  ('auto_backup_interval_hours', '24'),
+  ('auto_backup_keep', '7'),
+  ('auto_backup_directory', '');
@@ -0,0 +1,8 @@
+-- Optional tag writing after a download (ID3v2.4, Vorbis comments, MP4 atoms)
+-- The formats use the same {placeholders} as filename_format, an empty format
+-- keeps the tag embedded by the publisher
+ALTER TABLE subscriptions ADD COLUMN write_tags BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE subscriptions ADD COLUMN tag_title_format TEXT NOT NULL DEFAULT '{episode}';
+ALTER TABLE subscriptions ADD COLUMN tag_artist_format TEXT NOT NULL DEFAULT '{program}';
+ALTER TABLE subscriptions ADD COLUMN tag_album_format TEXT NOT NULL DEFAULT '{show}';
+ALTER TABLE subscriptions ADD COLUMN tag_comment_format TEXT NOT NULL DEFAULT '{description}';
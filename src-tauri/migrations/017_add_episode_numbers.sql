@@ -0,0 +1,8 @@
+-- Season and episode numbers from <itunes:season> and <itunes:episode>
+ALTER TABLE episodes ADD COLUMN season INTEGER;
+ALTER TABLE episodes ADD COLUMN episode_number INTEGER;
+
+-- Filename formats may now contain folders: the show folder that used to be
+-- added implicitly becomes part of existing formats
+UPDATE subscriptions SET filename_format = '{show}/' || filename_format;
+UPDATE settings SET value = '{show}/' || value WHERE key = 'default_filename_format';
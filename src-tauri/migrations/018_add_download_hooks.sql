@@ -0,0 +1,17 @@
+-- Commands run after a download completed or failed for good
+-- A subscription's empty command falls back to the global setting
+ALTER TABLE subscriptions ADD COLUMN on_completed_command TEXT NOT NULL DEFAULT '';
+ALTER TABLE subscriptions ADD COLUMN on_failed_command TEXT NOT NULL DEFAULT '';
+
+INSERT OR IGNORE INTO settings (key, value) VALUES
+  ('hook_on_completed_command', ''),
+  ('hook_on_failed_command', ''),
+  ('hook_timeout_seconds', '120'),
+  ('hook_max_attempts', '3'),
+  ('hook_retry_delay_seconds', '30');
+
+-- Outcome of the last hook run for an episode
+ALTER TABLE episodes ADD COLUMN hook_status TEXT;
+ALTER TABLE episodes ADD COLUMN hook_exit_code INTEGER;
+ALTER TABLE episodes ADD COLUMN hook_output TEXT;
+ALTER TABLE episodes ADD COLUMN hook_ran_at DATETIME;
@@ -0,0 +1,22 @@
+-- Rules deciding which new feed items of a subscription get downloaded
+-- Patterns are case-insensitive regular expressions, NULL disables a rule
+CREATE TABLE IF NOT EXISTS episode_filters (
+  subscription_id INTEGER PRIMARY KEY,
+  title_include TEXT,
+  title_exclude TEXT,
+  description_include TEXT,
+  description_exclude TEXT,
+  author_include TEXT,
+  min_duration_seconds INTEGER,
+  max_duration_seconds INTEGER,
+  min_size_bytes INTEGER,
+  max_size_bytes INTEGER,
+  -- Items published before this date are skipped
+  published_after DATETIME,
+  updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+  FOREIGN KEY (subscription_id) REFERENCES subscriptions(id) ON DELETE CASCADE
+);
+
+-- Why a filtered item was recorded as skipped
+ALTER TABLE episodes ADD COLUMN skip_reason TEXT;
@@ -0,0 +1,7 @@
+-- What the first check of a new subscription downloads from the existing archive
+-- NULL once applied, and for subscriptions created before this option
+-- 'none' skips every existing item, 'latest' keeps initial_download_count of them,
+-- 'since' keeps those published from initial_download_since
+ALTER TABLE subscriptions ADD COLUMN initial_download TEXT;
+ALTER TABLE subscriptions ADD COLUMN initial_download_count INTEGER;
+ALTER TABLE subscriptions ADD COLUMN initial_download_since DATETIME;
@@ -0,0 +1,3 @@
+-- Default priority of a subscription's downloads, higher ones start first
+-- Queue entries keep the priority they were queued with
+ALTER TABLE subscriptions ADD COLUMN download_priority INTEGER NOT NULL DEFAULT 0;
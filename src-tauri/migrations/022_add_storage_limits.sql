@@ -0,0 +1,8 @@
+-- Free space to keep on the download disk, the queue pauses below it, 0 disables the check
+INSERT OR IGNORE INTO settings (key, value) VALUES ('min_free_space_bytes', '1073741824');
+
+-- Bytes a subscription's downloaded files may use, NULL means unlimited
+ALTER TABLE subscriptions ADD COLUMN max_storage_bytes INTEGER;
+
+-- Size of the downloaded file, the enclosure length is used for older downloads
+ALTER TABLE episodes ADD COLUMN download_size_bytes INTEGER;
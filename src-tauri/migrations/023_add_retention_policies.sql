@@ -0,0 +1,30 @@
+-- Retention rules of a subscription, NULL disables a rule
+-- Files downloaded more than retention_days ago are removed, and only the latest
+-- retention_keep_completed completed episodes keep their file
+ALTER TABLE subscriptions ADD COLUMN retention_days INTEGER;
+ALTER TABLE subscriptions ADD COLUMN retention_keep_completed INTEGER;
+-- Removed files are moved there instead of being deleted
+ALTER TABLE subscriptions ADD COLUMN archive_directory TEXT;
+
+-- Protected episodes are never removed by retention rules
+ALTER TABLE episodes ADD COLUMN protected BOOLEAN NOT NULL DEFAULT 0;
+
+-- Every file or episode removed by a retention rule
+CREATE TABLE IF NOT EXISTS retention_history (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  subscription_id INTEGER NOT NULL,
+  -- The episode may be gone, its title is kept
+  episode_id INTEGER NOT NULL,
+  episode_title TEXT NOT NULL,
+  file_path TEXT,
+  -- Where the file was moved when archived
+  archive_path TEXT,
+  episode_removed BOOLEAN NOT NULL DEFAULT 0,
+  reason TEXT NOT NULL,
+  created_at DATETIME NOT NULL,
+
+  FOREIGN KEY (subscription_id) REFERENCES subscriptions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_retention_history_subscription
+  ON retention_history(subscription_id, created_at DESC);
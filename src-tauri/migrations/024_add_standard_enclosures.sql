@@ -0,0 +1,5 @@
+-- Enclosure the feed lists first, whatever quality is preferred, so a changed
+-- preference or a new CDN host isn't taken for a replaced file
+-- NULL for episodes discovered earlier, recorded on the next check
+ALTER TABLE episodes ADD COLUMN enclosure_url TEXT;
+ALTER TABLE episodes ADD COLUMN enclosure_length INTEGER;
@@ -0,0 +1,3 @@
+-- File of an updated episode's previous audio under another extension, removed once
+-- the new download is in place so a failed re-download doesn't lose the episode
+ALTER TABLE episodes ADD COLUMN replaced_path TEXT;
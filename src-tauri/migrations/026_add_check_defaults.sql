@@ -0,0 +1,4 @@
+-- Check defaults for new subscriptions, shared by the subscription form and OPML imports
+INSERT OR IGNORE INTO settings (key, value) VALUES
+  ('default_check_frequency_minutes', '15'),
+  ('default_max_items_to_check', '100');
@@ -0,0 +1,5 @@
+-- Episodes whose file a retention rule removed were marked skipped, which backfill re-queues
+UPDATE episodes
+SET download_status = 'removed'
+WHERE download_status = 'skipped'
+  AND id IN (SELECT episode_id FROM retention_history WHERE episode_removed = 0);
@@ -0,0 +1,353 @@
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use std::path::{Path, PathBuf};
+
+use crate::db::settings::get_setting;
+use crate::db::MIGRATOR;
+use crate::utils::{AppError, AppResult};
+
+const BACKUP_PREFIX: &str = "podcastsync-";
+const BACKUP_SUFFIX: &str = ".db";
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// A backup found in the backup directory
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub path: String,
+    /// Local time the backup was made, from its file name
+    pub created_at: NaiveDateTime,
+    pub size_bytes: u64,
+}
+
+/// Directory of timestamped backups, "backups" in the app data directory by default
+pub async fn backup_directory(pool: &SqlitePool, app_data_dir: &Path) -> AppResult<PathBuf> {
+    let directory = get_setting(pool, "auto_backup_directory")
+        .await?
+        .filter(|directory| !directory.trim().is_empty());
+
+    Ok(match directory {
+        Some(directory) => PathBuf::from(directory.trim()),
+        None => app_data_dir.join("backups"),
+    })
+}
+
+/// Write a consistent snapshot of the whole database, safe while the pool is in use
+/// An existing file at `path` is only replaced once the snapshot is complete
+pub async fn create_backup(pool: &SqlitePool, path: &Path) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    // VACUUM INTO refuses to write over an existing file
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+    let _ = tokio::fs::remove_file(&temp_path).await;
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(temp_path.to_string_lossy().as_ref())
+        .execute(pool)
+        .await?;
+    tokio::fs::rename(&temp_path, path).await?;
+
+    tracing::info!("Database backed up to {}", path.display());
+
+    Ok(())
+}
+
+/// Back up into `directory` under a timestamped name
+pub async fn create_backup_in(pool: &SqlitePool, directory: &Path) -> AppResult<PathBuf> {
+    let file_name = format!(
+        "{}{}{}",
+        BACKUP_PREFIX,
+        Local::now().format(BACKUP_TIME_FORMAT),
+        BACKUP_SUFFIX
+    );
+    let path = directory.join(file_name);
+    create_backup(pool, &path).await?;
+
+    Ok(path)
+}
+
+/// Timestamped backups of `directory`, newest first
+pub async fn list_backups(directory: &Path) -> AppResult<Vec<BackupInfo>> {
+    let mut entries = match tokio::fs::read_dir(directory).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut backups = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let Some(created_at) = backup_time(&file_name.to_string_lossy()) else {
+            continue;
+        };
+
+        backups.push(BackupInfo {
+            path: entry.path().display().to_string(),
+            created_at,
+            size_bytes: entry.metadata().await?.len(),
+        });
+    }
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+
+    Ok(backups)
+}
+
+/// Delete the oldest timestamped backups, keeping the `keep` newest
+/// Returns the number of deleted files
+pub async fn rotate_backups(directory: &Path, keep: usize) -> AppResult<usize> {
+    let backups = list_backups(directory).await?;
+
+    let mut deleted = 0;
+    for backup in backups.iter().skip(keep) {
+        match tokio::fs::remove_file(&backup.path).await {
+            Ok(()) => deleted += 1,
+            Err(e) => tracing::warn!("Failed to delete old backup {}: {}", backup.path, e),
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Replace the content of the database with a backup
+/// The backup is validated and migrated to the current schema on a copy first,
+/// so a bad file leaves the database untouched
+pub async fn restore_backup(
+    pool: &SqlitePool,
+    backup: &Path,
+    work_directory: &Path,
+) -> AppResult<()> {
+    tokio::fs::create_dir_all(work_directory).await?;
+    let copy = work_directory.join("restore.db.tmp");
+    tokio::fs::copy(backup, &copy).await?;
+
+    let result = restore_from_copy(pool, &copy).await;
+    let _ = tokio::fs::remove_file(&copy).await;
+    result?;
+
+    tracing::info!("Database restored from {}", backup.display());
+
+    Ok(())
+}
+
+async fn restore_from_copy(pool: &SqlitePool, copy: &Path) -> AppResult<()> {
+    prepare_backup(copy).await?;
+
+    // Foreign keys and attached databases are per connection: use a dedicated one,
+    // closed afterwards so it never goes back to the pool with foreign keys off
+    let mut connection = pool.acquire().await?.detach();
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut connection)
+        .await?;
+    sqlx::query("ATTACH DATABASE ? AS backup")
+        .bind(copy.to_string_lossy().as_ref())
+        .execute(&mut connection)
+        .await?;
+
+    let result = copy_tables(&mut connection).await;
+
+    let _ = sqlx::query("DETACH DATABASE backup")
+        .execute(&mut connection)
+        .await;
+    let _ = connection.close().await;
+
+    result
+}
+
+/// Check a backup file and bring it to the current schema
+async fn prepare_backup(path: &Path) -> AppResult<()> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(false);
+    let backup_pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await?;
+
+    let result = migrate_backup(&backup_pool).await;
+    backup_pool.close().await;
+
+    result
+}
+
+async fn migrate_backup(backup_pool: &SqlitePool) -> AppResult<()> {
+    let check = sqlx::query_scalar::<_, String>("PRAGMA quick_check")
+        .fetch_one(backup_pool)
+        .await?;
+    if check != "ok" {
+        return Err(AppError::InvalidInput(format!(
+            "Backup file is corrupted: {}",
+            check
+        )));
+    }
+
+    let has_migrations = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')
+        "#,
+    )
+    .fetch_one(backup_pool)
+    .await?;
+    let version = if has_migrations {
+        sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1",
+        )
+        .fetch_one(backup_pool)
+        .await?
+    } else {
+        None
+    };
+
+    let Some(version) = version else {
+        return Err(AppError::InvalidInput(
+            "Not a PodcastSync backup".to_string(),
+        ));
+    };
+
+    let latest = MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0);
+    if version > latest {
+        return Err(AppError::InvalidInput(format!(
+            "Backup was made by a newer version of PodcastSync (schema {}, this version supports {})",
+            version, latest
+        )));
+    }
+
+    // Older backups get the migrations they are missing
+    MIGRATOR.run(backup_pool).await?;
+
+    Ok(())
+}
+
+/// Replace every table of the main database with its copy from the attached backup
+async fn copy_tables(connection: &mut SqliteConnection) -> AppResult<()> {
+    let mut tx = connection.begin().await?;
+
+    let tables = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT name FROM main.sqlite_master
+        WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != '_sqlx_migrations'
+        "#,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for table in tables {
+        let columns = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT m.name FROM pragma_table_info(?1, 'main') AS m
+            JOIN pragma_table_info(?1, 'backup') AS b ON b.name = m.name
+            "#,
+        )
+        .bind(&table)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        sqlx::query(&format!(r#"DELETE FROM main."{}""#, table))
+            .execute(&mut *tx)
+            .await?;
+
+        if columns.is_empty() {
+            continue;
+        }
+
+        let columns = columns
+            .iter()
+            .map(|column| format!(r#""{}""#, column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        sqlx::query(&format!(
+            r#"INSERT INTO main."{table}" ({columns}) SELECT {columns} FROM backup."{table}""#
+        ))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Creation time of a timestamped backup from its file name
+fn backup_time(file_name: &str) -> Option<NaiveDateTime> {
+    let timestamp = file_name
+        .strip_prefix(BACKUP_PREFIX)?
+        .strip_suffix(BACKUP_SUFFIX)?;
+    NaiveDateTime::parse_from_str(timestamp, BACKUP_TIME_FORMAT).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::TestDb;
+
+    #[test]
+    fn test_backup_time() {
+        assert_eq!(
+            backup_time("podcastsync-20240102-030405.db"),
+            NaiveDateTime::parse_from_str("2024-01-02 03:04:05", "%Y-%m-%d %H:%M:%S").ok()
+        );
+        assert_eq!(backup_time("podcastsync-20240102-030405.db.tmp"), None);
+        assert_eq!(backup_time("app.db"), None);
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let db = TestDb::new().await;
+        let (pool, directory) = (db.pool.clone(), db.directory.clone());
+
+        let set_language = |value: &'static str| {
+            let pool = pool.clone();
+            async move {
+                crate::db::settings::set_setting(&pool, "language", value)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        set_language("en").await;
+        let backup = create_backup_in(&pool, &directory.join("backups"))
+            .await
+            .unwrap();
+        set_language("de").await;
+
+        restore_backup(&pool, &backup, &directory).await.unwrap();
+        assert_eq!(
+            crate::db::settings::get_setting(&pool, "language")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("en")
+        );
+
+        // Anything that isn't a backup leaves the database alone
+        let not_a_backup = directory.join("notes.db");
+        tokio::fs::write(&not_a_backup, b"not a database")
+            .await
+            .unwrap();
+        assert!(restore_backup(&pool, &not_a_backup, &directory)
+            .await
+            .is_err());
+        assert_eq!(
+            list_backups(&directory.join("backups"))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            rotate_backups(&directory.join("backups"), 0).await.unwrap(),
+            1
+        );
+
+        db.close().await;
+    }
+}
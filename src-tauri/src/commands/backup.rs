@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::backup::{self, BackupInfo};
+use crate::state::AppState;
+
+fn app_data_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| "Failed to get app data directory".to_string())
+}
+
+/// Back the database up to `path`, or to the backup directory when not given
+/// Returns the path of the backup
+#[tauri::command]
+pub async fn create_backup(
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+    path: Option<String>,
+) -> Result<String, String> {
+    let path = match path {
+        Some(path) => {
+            backup::create_backup(&state.db_pool, Path::new(&path))
+                .await
+                .map_err(|e| e.to_string())?;
+            PathBuf::from(path)
+        }
+        None => {
+            let directory = backup::backup_directory(&state.db_pool, &app_data_dir(&app_handle)?)
+                .await
+                .map_err(|e| e.to_string())?;
+            backup::create_backup_in(&state.db_pool, &directory)
+                .await
+                .map_err(|e| e.to_string())?
+        }
+    };
+
+    Ok(path.display().to_string())
+}
+
+#[tauri::command]
+pub async fn list_backups(
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Vec<BackupInfo>, String> {
+    let directory = backup::backup_directory(&state.db_pool, &app_data_dir(&app_handle)?)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    backup::list_backups(&directory)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Replace all settings, subscriptions and episodes with a backup
+/// Feed checks and automatic backups are held off and downloads are stopped during the
+/// restore, downloads restart from the restored queue
+#[tauri::command]
+pub async fn restore_backup(
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+    path: String,
+) -> Result<(), String> {
+    let work_directory = app_data_dir(&app_handle)?;
+
+    // Waits for running feed checks and backups, new ones start on the restored database
+    let _restoring = state.restore_lock.write().await;
+    state.download_control.suspend().await;
+    let result = backup::restore_backup(&state.db_pool, Path::new(&path), &work_directory).await;
+
+    // Restart even after a failed restore, the database is then unchanged
+    if let Err(e) = state.http_client.reload(&state.db_pool).await {
+        tracing::error!("Invalid HTTP settings after restore: {}", e);
+    }
+    if let Err(e) = state.download_control.restart().await {
+        tracing::error!("Failed to restart downloads after restore: {}", e);
+    }
+
+    result.map_err(|e| e.to_string())?;
+
+    let _ = app_handle.emit_all("database-restored", ());
+
+    Ok(())
+}
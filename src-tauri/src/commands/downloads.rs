@@ -1,5 +1,6 @@
 use tauri::State;
 
+use crate::db::models::DownloadQueueItem;
 use crate::db::queue;
 use crate::state::AppState;
 
@@ -10,6 +11,15 @@ pub async fn get_queue_size(state: State<'_, AppState>) -> Result<i32, String> {
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn list_download_queue(
+    state: State<'_, AppState>,
+) -> Result<Vec<DownloadQueueItem>, String> {
+    queue::list_queue(&state.db_pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn clear_queue(state: State<'_, AppState>) -> Result<(), String> {
     queue::clear_queue(&state.db_pool)
@@ -17,6 +27,69 @@ pub async fn clear_queue(state: State<'_, AppState>) -> Result<(), String> {
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn cancel_download(state: State<'_, AppState>, episode_id: i64) -> Result<(), String> {
+    state
+        .download_control
+        .cancel(episode_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn pause_download(state: State<'_, AppState>, episode_id: i64) -> Result<(), String> {
+    state
+        .download_control
+        .pause(episode_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn resume_download(state: State<'_, AppState>, episode_id: i64) -> Result<(), String> {
+    state
+        .download_control
+        .resume(episode_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn bump_download(state: State<'_, AppState>, episode_id: i64) -> Result<(), String> {
+    state
+        .download_control
+        .bump(episode_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn pause_all_downloads(state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .download_control
+        .pause_all()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn resume_all_downloads(state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .download_control
+        .resume_all()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_downloads_paused(state: State<'_, AppState>) -> Result<bool, String> {
+    state
+        .download_control
+        .is_paused()
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn select_directory() -> Result<Option<String>, String> {
     use tauri::api::dialog::blocking::FileDialogBuilder;
@@ -1,14 +1,19 @@
 use tauri::State;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use serde::Serialize;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use tokio::sync::mpsc;
 
 use crate::db::episodes::{self, EpisodeStats};
+use crate::db::feed_cache;
 use crate::db::models::Episode;
 use crate::db::subscriptions;
-use crate::download::DownloadRequest;
+use crate::download::{download_request, queue_download, DownloadRequest};
 use crate::state::AppState;
-use crate::utils::{build_output_path, extension_from_mime, extract_extension};
-use crate::rss::fetch_rss;
+use crate::rss::{fetch_rss, IdentityStrategy};
+use crate::rss::parser::find_media_urls;
+use crate::utils::{AppError, AppResult};
 
 #[derive(Debug, Serialize)]
 pub struct AvailableMedia {
@@ -54,104 +59,122 @@ pub async fn get_episode(state: State<'_, AppState>, id: i64) -> Result<Episode,
 
 #[tauri::command]
 pub async fn retry_episode(state: State<'_, AppState>, id: i64) -> Result<(), String> {
-    // Get episode details
-    let episode = episodes::get_episode(&state.db_pool, id)
+    retry(&state.db_pool, &state.download_tx, id)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| e.to_string())
+}
+
+/// Reset an episode and queue it again, under the name it was first queued with
+async fn retry(
+    db_pool: &SqlitePool,
+    download_tx: &mpsc::Sender<DownloadRequest>,
+    id: i64,
+) -> AppResult<()> {
+    // Get episode details
+    let episode = episodes::get_episode(db_pool, id).await?;
 
     // Get subscription details for output path
-    let subscription = subscriptions::get_subscription(&state.db_pool, episode.subscription_id)
-        .await
-        .map_err(|e| e.to_string())?;
+    let subscription = subscriptions::get_subscription(db_pool, episode.subscription_id).await?;
 
     // Reset episode for retry
-    episodes::reset_episode_for_retry(&state.db_pool, id)
+    episodes::reset_episode_for_retry(db_pool, id).await?;
+
+    // Add back to queue and send download request
+    let request = queue_download(db_pool, &subscription, &episode).await?;
+    download_tx
+        .send(request)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::Other(format!("Failed to send download request: {}", e)))?;
 
-    // Add back to queue
-    crate::db::queue::add_to_queue(&state.db_pool, id, 0)
+    Ok(())
+}
+
+/// Queue a subscription's skipped episodes published within a range, whatever skipped
+/// them, returns how many were queued
+#[tauri::command]
+pub async fn backfill_subscription(
+    state: State<'_, AppState>,
+    subscription_id: i64,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<u32, String> {
+    let subscription = subscriptions::get_subscription(&state.db_pool, subscription_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let skipped = episodes::list_skipped_episodes(&state.db_pool, subscription_id, from, to)
         .await
         .map_err(|e| e.to_string())?;
 
-    // Build output path
-    let extension = episode
-        .audio_type
-        .as_ref()
-        .map(|mime| extension_from_mime(mime))
-        .or_else(|| extract_extension(&episode.audio_url))
-        .unwrap_or_else(|| "mp3".to_string());
-
-    let output_path = build_output_path(
-        &subscription.output_directory,
-        &subscription.name,
-        &episode.title,
-        episode.pub_date,
-        &format!("{}.{}", episode.title, extension),
-    );
+    let mut count = 0;
 
-    // Send download request
-    state
-        .download_tx
-        .send(DownloadRequest {
-            episode_id: episode.id,
-            subscription_id: episode.subscription_id,
-            url: episode.audio_url.clone(),
-            output_path: PathBuf::from(output_path),
-        })
-        .await
-        .map_err(|e| format!("Failed to send download request: {}", e))?;
+    for episode in skipped {
+        episodes::reset_episode_for_retry(&state.db_pool, episode.id)
+            .await
+            .map_err(|e| e.to_string())?;
 
-    Ok(())
+        let request = queue_download(&state.db_pool, &subscription, &episode)
+            .await
+            .map_err(|e| e.to_string())?;
+        state
+            .download_tx
+            .send(request)
+            .await
+            .map_err(|e| format!("Failed to send download request: {}", e))?;
+
+        count += 1;
+    }
+
+    tracing::info!(
+        "Queued {} skipped episode(s) of {} for backfill",
+        count,
+        subscription.name
+    );
+
+    Ok(count)
 }
 
 #[tauri::command]
 pub async fn process_pending_episodes(state: State<'_, AppState>) -> Result<u32, String> {
-    // Get all pending episodes
-    let pending_episodes = episodes::list_episodes_by_status(&state.db_pool, "pending")
+    send_pending_episodes(&state.db_pool, &state.download_tx)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| e.to_string())
+}
+
+/// Send a download request for every pending episode, returns how many were sent
+async fn send_pending_episodes(
+    db_pool: &SqlitePool,
+    download_tx: &mpsc::Sender<DownloadRequest>,
+) -> AppResult<u32> {
+    // Get all pending episodes
+    let pending_episodes = episodes::list_episodes_by_status(db_pool, "pending").await?;
 
     let mut count = 0;
 
     for episode in pending_episodes {
         // Get subscription details
-        let subscription = match subscriptions::get_subscription(&state.db_pool, episode.subscription_id).await {
-            Ok(sub) => sub,
-            Err(e) => {
-                tracing::error!("Failed to get subscription for episode {}: {}", episode.id, e);
-                continue;
-            }
-        };
-
-        // Build output path
-        let extension = episode
-            .audio_type
-            .as_ref()
-            .map(|mime| extension_from_mime(mime))
-            .or_else(|| extract_extension(&episode.audio_url))
-            .unwrap_or_else(|| "mp3".to_string());
-
-        let output_path = build_output_path(
-            &subscription.output_directory,
-            &subscription.name,
-            &episode.title,
-            episode.pub_date,
-            &format!("{}.{}", episode.title, extension),
-        );
+        let subscription =
+            match subscriptions::get_subscription(db_pool, episode.subscription_id).await {
+                Ok(sub) => sub,
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to get subscription for episode {}: {}",
+                        episode.id,
+                        e
+                    );
+                    continue;
+                }
+            };
 
         // Send download request
-        if let Err(e) = state
-            .download_tx
-            .send(DownloadRequest {
-                episode_id: episode.id,
-                subscription_id: episode.subscription_id,
-                url: episode.audio_url.clone(),
-                output_path: PathBuf::from(output_path),
-            })
+        if let Err(e) = download_tx
+            .send(download_request(&subscription, &episode))
             .await
         {
-            tracing::error!("Failed to send download request for episode {}: {}", episode.id, e);
+            tracing::error!(
+                "Failed to send download request for episode {}: {}",
+                episode.id,
+                e
+            );
             continue;
         }
 
@@ -168,6 +191,17 @@ pub async fn delete_episode(state: State<'_, AppState>, id: i64) -> Result<(), S
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn set_episode_protected(
+    state: State<'_, AppState>,
+    id: i64,
+    protected: bool,
+) -> Result<(), String> {
+    episodes::set_episode_protected(&state.db_pool, id, protected)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_episode_stats(state: State<'_, AppState>) -> Result<EpisodeStats, String> {
     episodes::get_episode_stats(&state.db_pool)
@@ -256,27 +290,33 @@ pub async fn get_episode_available_media(
         .await
         .map_err(|e| format!("Failed to get subscription: {}", e))?;
 
-    // Fetch RSS feed
-    let xml = fetch_rss(&subscription.rss_url)
+    // Use the feed cached by the last check, the episode is usually in it
+    let cache = feed_cache::get_feed_cache(&state.db_pool, subscription_id, &subscription.rss_url)
         .await
-        .map_err(|e| format!("Failed to fetch RSS: {}", e))?;
-
-    // Parse RSS to get the raw channel
-    let channel = rss::Channel::read_from(xml.as_bytes())
-        .map_err(|e| format!("Failed to parse RSS: {}", e))?;
-
-    // Find the item with matching GUID
-    let rss_item = channel.items().iter()
-        .find(|item| {
-            item.guid()
-                .map(|g| g.value() == guid)
-                .unwrap_or(false)
-        })
-        .ok_or_else(|| format!("Episode with GUID '{}' not found in RSS", guid))?;
+        .map_err(|e| e.to_string())?;
+    let strategy =
+        IdentityStrategy::parse(&subscription.identity_strategy).map_err(|e| e.to_string())?;
+    let quality = &subscription.preferred_quality;
+    let cached_urls = cache.and_then(|cache| {
+        find_media_urls(&cache.body, &guid, strategy, quality)
+            .ok()
+            .flatten()
+    });
 
     // Extract all available media URLs
-    let (standard_url, original_url, flac_url, mp3_url) =
-        crate::rss::parser::extract_all_media_urls(rss_item);
+    let (standard_url, original_url, flac_url, mp3_url) = match cached_urls {
+        Some(urls) => urls,
+        None => {
+            // Fetch RSS feed
+            let xml = fetch_rss(&state.http_client, &subscription.rss_url)
+                .await
+                .map_err(|e| format!("Failed to fetch RSS: {}", e))?;
+
+            find_media_urls(&xml, &guid, strategy, quality)
+                .map_err(|e| format!("Failed to parse RSS: {}", e))?
+                .ok_or_else(|| format!("Episode with GUID '{}' not found in RSS", guid))?
+        }
+    };
 
     Ok(AvailableMedia {
         standard_url,
@@ -285,3 +325,65 @@ pub async fn get_episode_available_media(
         mp3_url,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::CreateSubscriptionData;
+    use crate::db::queue::{get_queued_output_path, remove_from_queue};
+    use crate::db::test_support::{self, add_episode, TestDb};
+    use chrono::TimeZone;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn test_same_path_for_new_retried_and_pending_episodes() {
+        let db = TestDb::new().await;
+        let pool = &db.pool;
+        let (download_tx, mut download_rx) = mpsc::channel(10);
+
+        let data = CreateSubscriptionData {
+            filename_format: "{show}/{date}_{episode}".to_string(),
+            ..test_support::subscription_data()
+        };
+        let subscription = subscriptions::create_subscription(pool, data)
+            .await
+            .unwrap();
+        let episode = Episode {
+            subscription_id: subscription.id,
+            title: "Interview".to_string(),
+            pub_date: Some(Utc.with_ymd_and_hms(2024, 3, 5, 8, 0, 0).unwrap()),
+            audio_url: "https://cdn.example.com/interview.m4a?token=1".to_string(),
+            ..test_support::episode(1)
+        };
+        let episode = add_episode(pool, &episode).await;
+        let queued_path = || async {
+            get_queued_output_path(pool, episode.id)
+                .await
+                .unwrap()
+                .map(PathBuf::from)
+        };
+
+        // Feed checker: queued right after the episode is discovered
+        let discovered = queue_download(pool, &subscription, &episode)
+            .await
+            .unwrap()
+            .output_path;
+        assert_eq!(
+            discovered,
+            PathBuf::from("/nonexistent/Morning Show/2024-03-05_Interview.m4a")
+        );
+        assert_eq!(queued_path().await, Some(discovered.clone()));
+
+        // Pending episodes processed on startup
+        assert_eq!(send_pending_episodes(pool, &download_tx).await.unwrap(), 1);
+        assert_eq!(download_rx.recv().await.unwrap().output_path, discovered);
+
+        // Retried episodes, queued again after leaving the queue
+        remove_from_queue(pool, episode.id).await.unwrap();
+        retry(pool, &download_tx, episode.id).await.unwrap();
+        assert_eq!(download_rx.recv().await.unwrap().output_path, discovered);
+        assert_eq!(queued_path().await, Some(discovered));
+
+        db.close().await;
+    }
+}
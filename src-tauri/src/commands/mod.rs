@@ -1,11 +1,15 @@
+pub mod backup;
 pub mod downloads;
 pub mod episodes;
+pub mod opml;
 pub mod settings;
 pub mod subscriptions;
 pub mod updater;
 
+pub use backup::*;
 pub use downloads::*;
 pub use episodes::*;
+pub use opml::*;
 pub use settings::*;
 pub use subscriptions::*;
 pub use updater::*;
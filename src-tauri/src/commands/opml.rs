@@ -0,0 +1,25 @@
+use tauri::State;
+
+use crate::opml::{export_subscriptions, import_subscriptions, OpmlImportReport};
+use crate::state::AppState;
+
+/// Create subscriptions from the content of an OPML file
+/// Without an output directory, the default one from settings is used
+#[tauri::command]
+pub async fn import_opml(
+    state: State<'_, AppState>,
+    content: String,
+    output_directory: Option<String>,
+) -> Result<OpmlImportReport, String> {
+    import_subscriptions(&state.db_pool, &content, output_directory)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// All subscriptions as an OPML document
+#[tauri::command]
+pub async fn export_opml(state: State<'_, AppState>) -> Result<String, String> {
+    export_subscriptions(&state.db_pool)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -2,7 +2,12 @@ use tauri::State;
 
 use crate::db::models::Setting;
 use crate::db::settings;
+use crate::download::hooks::{validate_hook_command, HOOK_COMMAND_SETTING_KEYS};
+use crate::download::limits::LIMIT_SETTING_KEYS;
+use crate::download::schedule::BandwidthSchedule;
+use crate::download::storage::MIN_FREE_SPACE_SETTING;
 use crate::state::AppState;
+use crate::utils::http::HTTP_SETTING_KEYS;
 
 #[tauri::command]
 pub async fn get_all_settings(state: State<'_, AppState>) -> Result<Vec<Setting>, String> {
@@ -20,7 +25,44 @@ pub async fn get_setting(state: State<'_, AppState>, key: String) -> Result<Opti
 
 #[tauri::command]
 pub async fn set_setting(state: State<'_, AppState>, key: String, value: String) -> Result<(), String> {
+    if key == "bandwidth_schedule" {
+        BandwidthSchedule::parse(&value).map_err(|e| e.to_string())?;
+    }
+    if HOOK_COMMAND_SETTING_KEYS.contains(&key.as_str()) {
+        validate_hook_command(&value).map_err(|e| e.to_string())?;
+    }
+    if key == MIN_FREE_SPACE_SETTING && value.trim().parse::<u64>().is_err() {
+        return Err(format!(
+            "Invalid free space '{}', expected a number of bytes",
+            value
+        ));
+    }
+
+    let previous = settings::get_setting(&state.db_pool, &key)
+        .await
+        .map_err(|e| e.to_string())?;
+
     settings::set_setting(&state.db_pool, &key, &value)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    // Rebuild the HTTP client, restoring the previous value if it can't be built
+    if HTTP_SETTING_KEYS.contains(&key.as_str()) {
+        if let Err(e) = state.http_client.reload(&state.db_pool).await {
+            let _ = settings::set_setting(&state.db_pool, &key, previous.as_deref().unwrap_or(""))
+                .await;
+            return Err(e.to_string());
+        }
+    }
+
+    // Download limits take effect without a restart
+    if LIMIT_SETTING_KEYS.contains(&key.as_str()) {
+        state
+            .download_control
+            .reload_limits()
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
 }
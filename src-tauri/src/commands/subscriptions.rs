@@ -1,21 +1,95 @@
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use tauri::{AppHandle, State};
 
-use crate::db::models::{CreateSubscriptionData, Subscription};
-use crate::db::subscriptions;
-use crate::rss::{fetch_rss_with_limit, parse_rss_with_quality};
+use crate::db::models::{
+    CreateSubscriptionData, EpisodeFilter, RetentionAction, RetentionHistoryEntry, Subscription,
+    SubscriptionDefaults,
+};
+use crate::db::{episodes, feed_cache, filters, retention, settings, subscriptions};
+use crate::download::episode_extension;
+use crate::download::hooks::validate_hook_command;
+use crate::download::retention::{enforce_retention, preview_retention};
+use crate::rss::filter::ItemFilter;
+use crate::rss::identity::{plan_rekey, RekeySummary};
+use crate::rss::{
+    fetch_rss, fetch_rss_with_limit, parse_rss_with_quality, IdentityStrategy, InitialDownload,
+};
 use crate::scheduler::feed_checker;
 use crate::state::AppState;
+use crate::utils::template::{Template, TemplateValues};
+use crate::utils::{relative_output_path, AppResult};
+
+/// Number of episodes rendered by a filename format preview
+const PREVIEW_EPISODES: usize = 5;
+
+/// Number of retention history entries listed at most
+const RETENTION_HISTORY_LIMIT: i64 = 200;
+
+/// An episode's path with a filename format, relative to the output directory
+#[derive(Debug, Clone, Serialize)]
+pub struct FilenamePreview {
+    pub episode_id: i64,
+    pub title: String,
+    pub path: String,
+}
+
+/// Check the filename and tag formats and the hook commands before saving them
+fn validate_formats(data: &CreateSubscriptionData) -> AppResult<()> {
+    Template::parse_path(&data.filename_format)?;
+
+    let tag_formats = [
+        &data.tag_title_format,
+        &data.tag_artist_format,
+        &data.tag_album_format,
+        &data.tag_comment_format,
+    ];
+    for format in tag_formats.into_iter().flatten() {
+        Template::parse(format)?;
+    }
+
+    let hook_commands = [&data.on_completed_command, &data.on_failed_command];
+    for command in hook_commands.into_iter().flatten() {
+        validate_hook_command(command)?;
+    }
+
+    Ok(())
+}
 
 #[tauri::command]
 pub async fn create_subscription(
     state: State<'_, AppState>,
     data: CreateSubscriptionData,
 ) -> Result<Subscription, String> {
+    if let Some(strategy) = &data.identity_strategy {
+        IdentityStrategy::parse(strategy).map_err(|e| e.to_string())?;
+    }
+    if let Some(mode) = &data.initial_download {
+        InitialDownload::parse(
+            mode,
+            data.initial_download_count,
+            data.initial_download_since,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    validate_formats(&data).map_err(|e| e.to_string())?;
+
     subscriptions::create_subscription(&state.db_pool, data)
         .await
         .map_err(|e| e.to_string())
 }
 
+/// Values the subscription form starts with, also used by OPML imports
+#[tauri::command]
+pub async fn get_subscription_defaults(
+    state: State<'_, AppState>,
+) -> Result<SubscriptionDefaults, String> {
+    settings::get_subscription_defaults(&state.db_pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn list_subscriptions(state: State<'_, AppState>) -> Result<Vec<Subscription>, String> {
     subscriptions::list_subscriptions(&state.db_pool)
@@ -39,6 +113,8 @@ pub async fn update_subscription(
     id: i64,
     data: CreateSubscriptionData,
 ) -> Result<Subscription, String> {
+    validate_formats(&data).map_err(|e| e.to_string())?;
+
     subscriptions::update_subscription(&state.db_pool, id, data)
         .await
         .map_err(|e| e.to_string())
@@ -62,6 +138,83 @@ pub async fn toggle_subscription(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_episode_filter(
+    state: State<'_, AppState>,
+    subscription_id: i64,
+) -> Result<EpisodeFilter, String> {
+    filters::get_episode_filter(&state.db_pool, subscription_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Replace a subscription's filter, it applies to items found by the next checks
+#[tauri::command]
+pub async fn set_episode_filter(
+    state: State<'_, AppState>,
+    subscription_id: i64,
+    mut filter: EpisodeFilter,
+) -> Result<EpisodeFilter, String> {
+    subscriptions::get_subscription(&state.db_pool, subscription_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Blank patterns disable their rule
+    let patterns = [
+        &mut filter.title_include,
+        &mut filter.title_exclude,
+        &mut filter.description_include,
+        &mut filter.description_exclude,
+        &mut filter.author_include,
+    ];
+    for pattern in patterns {
+        *pattern = pattern.take().filter(|p| !p.trim().is_empty());
+    }
+    filter.subscription_id = subscription_id;
+    ItemFilter::new(&filter).map_err(|e| e.to_string())?;
+
+    filters::save_episode_filter(&state.db_pool, &filter)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(filter)
+}
+
+/// What the subscription's retention rules would remove now, without removing anything
+#[tauri::command]
+pub async fn preview_retention_policy(
+    state: State<'_, AppState>,
+    subscription_id: i64,
+) -> Result<Vec<RetentionAction>, String> {
+    let subscription = subscriptions::get_subscription(&state.db_pool, subscription_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    preview_retention(&state.db_pool, &subscription, 0)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn apply_retention_policy(
+    state: State<'_, AppState>,
+    subscription_id: i64,
+) -> Result<Vec<RetentionAction>, String> {
+    enforce_retention(&state.db_pool, subscription_id, 0)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_retention_history(
+    state: State<'_, AppState>,
+    subscription_id: Option<i64>,
+) -> Result<Vec<RetentionHistoryEntry>, String> {
+    retention::list_retention_history(&state.db_pool, subscription_id, RETENTION_HISTORY_LIMIT)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn check_subscription_now(
     state: State<'_, AppState>,
@@ -72,16 +225,18 @@ pub async fn check_subscription_now(
     feed_checker::check_single_subscription_now(
         id,
         state.db_pool.clone(),
+        state.http_client.clone(),
         state.download_tx.clone(),
+        state.restore_lock.clone(),
         app_handle,
     )
     .await
 }
 
 #[tauri::command]
-pub async fn fetch_rss_title(url: String) -> Result<String, String> {
+pub async fn fetch_rss_title(state: State<'_, AppState>, url: String) -> Result<String, String> {
     // Fetch RSS feed with limit=1 for speed
-    let xml = fetch_rss_with_limit(&url, Some(1))
+    let xml = fetch_rss_with_limit(&state.http_client, &url, Some(1))
         .await
         .map_err(|e| e.to_string())?;
 
@@ -91,3 +246,97 @@ pub async fn fetch_rss_title(url: String) -> Result<String, String> {
 
     Ok(feed.title)
 }
+
+/// Switch a subscription to another identity strategy, re-keying its existing
+/// episodes so none of them is seen as new and downloaded again
+#[tauri::command]
+pub async fn rekey_subscription(
+    state: State<'_, AppState>,
+    id: i64,
+    identity_strategy: String,
+) -> Result<RekeySummary, String> {
+    let to = IdentityStrategy::parse(&identity_strategy).map_err(|e| e.to_string())?;
+    let subscription = subscriptions::get_subscription(&state.db_pool, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let from =
+        IdentityStrategy::parse(&subscription.identity_strategy).map_err(|e| e.to_string())?;
+
+    // Match against the current feed, or the last fetched one when offline
+    let xml = match fetch_rss(&state.http_client, &subscription.rss_url).await {
+        Ok(xml) => xml,
+        Err(e) => {
+            let cache = feed_cache::get_feed_cache(&state.db_pool, id, &subscription.rss_url)
+                .await
+                .map_err(|e| e.to_string())?;
+            cache
+                .map(|cache| cache.body)
+                .ok_or_else(|| format!("Failed to fetch RSS: {}", e))?
+        }
+    };
+    let feed = parse_rss_with_quality(&xml, &subscription.preferred_quality)
+        .map_err(|e| format!("Failed to parse RSS: {}", e))?;
+
+    let existing = episodes::list_episodes_by_subscription(&state.db_pool, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (keys, summary) = plan_rekey(from, to, &existing, &feed.items);
+
+    subscriptions::rekey_subscription(&state.db_pool, id, to.as_str(), &keys)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    tracing::info!(
+        "Re-keyed subscription {} from {} to {}: {:?}",
+        subscription.name,
+        from.as_str(),
+        to.as_str(),
+        summary
+    );
+
+    Ok(summary)
+}
+
+/// Render a filename format against the latest episodes of a subscription,
+/// or of all subscriptions when it isn't created yet
+#[tauri::command]
+pub async fn preview_filename_format(
+    state: State<'_, AppState>,
+    filename_format: String,
+    subscription_id: Option<i64>,
+) -> Result<Vec<FilenamePreview>, String> {
+    Template::parse_path(&filename_format).map_err(|e| e.to_string())?;
+
+    let episodes = match subscription_id {
+        Some(id) => episodes::list_episodes_by_subscription(&state.db_pool, id).await,
+        None => episodes::list_episodes(&state.db_pool).await,
+    }
+    .map_err(|e| e.to_string())?;
+
+    let mut subscriptions_by_id: HashMap<i64, Subscription> = HashMap::new();
+    let mut previews = Vec::new();
+    for episode in episodes.into_iter().take(PREVIEW_EPISODES) {
+        let subscription = match subscriptions_by_id.entry(episode.subscription_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(
+                subscriptions::get_subscription(&state.db_pool, episode.subscription_id)
+                    .await
+                    .map_err(|e| e.to_string())?,
+            ),
+        };
+
+        let path = relative_output_path(
+            &filename_format,
+            &TemplateValues::new(subscription, &episode),
+            &episode_extension(&episode),
+        );
+
+        previews.push(FilenamePreview {
+            episode_id: episode.id,
+            title: episode.title.clone(),
+            path: path.display().to_string(),
+        });
+    }
+
+    Ok(previews)
+}
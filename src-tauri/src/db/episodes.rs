@@ -1,22 +1,27 @@
 use chrono::{DateTime, Utc};
 use sqlx::SqlitePool;
 
+use crate::db::fetch_returning;
 use crate::db::models::Episode;
 use crate::utils::{AppError, AppResult};
 
-/// Check if episode exists by GUID
-pub async fn episode_exists(pool: &SqlitePool, subscription_id: i64, guid: &str) -> AppResult<bool> {
-    let exists = sqlx::query_scalar::<_, bool>(
+/// Find an episode of a subscription by GUID
+pub async fn get_episode_by_guid(
+    pool: &SqlitePool,
+    subscription_id: i64,
+    guid: &str,
+) -> AppResult<Option<Episode>> {
+    let episode = sqlx::query_as::<_, Episode>(
         r#"
-        SELECT EXISTS(SELECT 1 FROM episodes WHERE subscription_id = ? AND guid = ?)
+        SELECT * FROM episodes WHERE subscription_id = ? AND guid = ?
         "#,
     )
     .bind(subscription_id)
     .bind(guid)
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?;
 
-    Ok(exists)
+    Ok(episode)
 }
 
 /// Insert new episode
@@ -33,16 +38,21 @@ pub async fn insert_episode(
     duration_seconds: Option<i32>,
     image_url: Option<String>,
     program_name: Option<String>,
+    season: Option<i32>,
+    episode_number: Option<i32>,
+    enclosure_url: Option<String>,
+    enclosure_length: Option<i64>,
 ) -> AppResult<Episode> {
     let now = Utc::now();
 
-    let episode = sqlx::query_as::<_, Episode>(
+    let query = sqlx::query_as::<_, Episode>(
         r#"
         INSERT INTO episodes (
             subscription_id, guid, title, description, pub_date,
             audio_url, audio_type, audio_size_bytes, duration_seconds,
-            image_url, program_name, download_status, discovered_at
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'pending', ?)
+            image_url, program_name, season, episode_number, enclosure_url, enclosure_length,
+            download_status, discovered_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'pending', ?)
         RETURNING *
         "#,
     )
@@ -57,9 +67,12 @@ pub async fn insert_episode(
     .bind(duration_seconds)
     .bind(image_url)
     .bind(program_name)
-    .bind(now)
-    .fetch_one(pool)
-    .await?;
+    .bind(season)
+    .bind(episode_number)
+    .bind(enclosure_url)
+    .bind(enclosure_length)
+    .bind(now);
+    let episode = fetch_returning(query, pool).await?;
 
     Ok(episode)
 }
@@ -113,6 +126,35 @@ pub async fn list_episodes_by_status(pool: &SqlitePool, status: &str) -> AppResu
     Ok(episodes)
 }
 
+/// List a subscription's skipped episodes published within a range, oldest first
+/// Undated episodes are only listed without bounds
+pub async fn list_skipped_episodes(
+    pool: &SqlitePool,
+    subscription_id: i64,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> AppResult<Vec<Episode>> {
+    let episodes = sqlx::query_as::<_, Episode>(
+        r#"
+        SELECT * FROM episodes
+        WHERE subscription_id = ?
+          AND download_status = 'skipped'
+          AND (? IS NULL OR pub_date >= ?)
+          AND (? IS NULL OR pub_date <= ?)
+        ORDER BY pub_date ASC NULLS LAST, discovered_at ASC
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(from)
+    .bind(from)
+    .bind(to)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(episodes)
+}
+
 /// Get episode by ID
 pub async fn get_episode(pool: &SqlitePool, id: i64) -> AppResult<Episode> {
     let episode = sqlx::query_as::<_, Episode>(
@@ -173,8 +215,7 @@ pub async fn mark_episode_downloading(pool: &SqlitePool, id: i64) -> AppResult<(
         r#"
         UPDATE episodes
         SET download_status = 'downloading',
-            download_started_at = ?,
-            download_attempts = download_attempts + 1
+            download_started_at = ?
         WHERE id = ?
         "#,
     )
@@ -203,20 +244,74 @@ pub async fn update_episode_progress(pool: &SqlitePool, id: i64, progress: i32)
     Ok(())
 }
 
+/// Save the resume state of a partial download
+pub async fn update_resume_state(
+    pool: &SqlitePool,
+    id: i64,
+    offset: i64,
+    etag: Option<String>,
+    last_modified: Option<String>,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes
+        SET resume_offset = ?,
+            resume_etag = ?,
+            resume_last_modified = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(offset)
+    .bind(etag)
+    .bind(last_modified)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Update only the resume offset of a partial download
+pub async fn update_resume_offset(pool: &SqlitePool, id: i64, offset: i64) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes
+        SET resume_offset = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(offset)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 /// Mark episode as completed
-pub async fn mark_episode_completed(pool: &SqlitePool, id: i64, file_path: String) -> AppResult<()> {
+pub async fn mark_episode_completed(
+    pool: &SqlitePool,
+    id: i64,
+    file_path: String,
+    file_size: Option<i64>,
+) -> AppResult<()> {
     sqlx::query(
         r#"
         UPDATE episodes
         SET download_status = 'completed',
             download_path = ?,
+            download_size_bytes = ?,
             download_progress = 100,
             download_completed_at = ?,
-            download_error = NULL
+            download_error = NULL,
+            resume_offset = 0,
+            resume_etag = NULL,
+            resume_last_modified = NULL
         WHERE id = ?
         "#,
     )
     .bind(file_path)
+    .bind(file_size)
     .bind(Utc::now())
     .bind(id)
     .execute(pool)
@@ -231,7 +326,8 @@ pub async fn mark_episode_failed(pool: &SqlitePool, id: i64, error: String) -> A
         r#"
         UPDATE episodes
         SET download_status = 'failed',
-            download_error = ?
+            download_error = ?,
+            download_attempts = download_attempts + 1
         WHERE id = ?
         "#,
     )
@@ -243,6 +339,203 @@ pub async fn mark_episode_failed(pool: &SqlitePool, id: i64, error: String) -> A
     Ok(())
 }
 
+/// Record the outcome of a hook run for an episode
+pub async fn update_episode_hook_result(
+    pool: &SqlitePool,
+    id: i64,
+    status: &str,
+    exit_code: Option<i32>,
+    output: &str,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes
+        SET hook_status = ?,
+            hook_exit_code = ?,
+            hook_output = ?,
+            hook_ran_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(status)
+    .bind(exit_code)
+    .bind(output)
+    .bind(Utc::now())
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Mark an episode left out by its subscription's filter as skipped
+pub async fn mark_episode_skipped(pool: &SqlitePool, id: i64, reason: &str) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes
+        SET download_status = 'skipped',
+            skip_reason = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(reason)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Mark an episode whose file was removed to make room as removed, backfill leaves it alone
+pub async fn mark_episode_evicted(pool: &SqlitePool, id: i64, reason: &str) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes
+        SET download_status = 'removed',
+            skip_reason = ?,
+            download_path = NULL
+        WHERE id = ?
+        "#,
+    )
+    .bind(reason)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Mark episode as waiting for an automatic retry
+pub async fn mark_episode_retry_scheduled(pool: &SqlitePool, id: i64, error: String) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes
+        SET download_status = 'pending',
+            download_error = ?,
+            download_attempts = download_attempts + 1
+        WHERE id = ?
+        "#,
+    )
+    .bind(error)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record the new audio of an episode updated in the feed
+/// Any partial download belongs to the previous file and can't be resumed
+pub async fn mark_episode_content_updated(
+    pool: &SqlitePool,
+    id: i64,
+    audio_url: &str,
+    audio_type: Option<&str>,
+    audio_size_bytes: Option<i64>,
+    duration_seconds: Option<i32>,
+) -> AppResult<Episode> {
+    let query = sqlx::query_as::<_, Episode>(
+        r#"
+        UPDATE episodes
+        SET audio_url = ?,
+            audio_type = ?,
+            audio_size_bytes = ?,
+            duration_seconds = COALESCE(?, duration_seconds),
+            content_updated_at = ?,
+            resume_offset = 0,
+            resume_etag = NULL,
+            resume_last_modified = NULL
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(audio_url)
+    .bind(audio_type)
+    .bind(audio_size_bytes)
+    .bind(duration_seconds)
+    .bind(Utc::now())
+    .bind(id);
+    let episode = fetch_returning(query, pool).await?;
+
+    Ok(episode)
+}
+
+/// Follow the audio of an episode to another URL or quality without marking it updated
+/// A partial download of another URL can't be resumed
+pub async fn rebase_episode_audio(
+    pool: &SqlitePool,
+    id: i64,
+    audio_url: &str,
+    audio_type: Option<&str>,
+    audio_size_bytes: Option<i64>,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes
+        SET resume_offset = CASE WHEN audio_url = ? THEN resume_offset ELSE 0 END,
+            resume_etag = CASE WHEN audio_url = ? THEN resume_etag END,
+            resume_last_modified = CASE WHEN audio_url = ? THEN resume_last_modified END,
+            audio_url = ?,
+            audio_type = ?,
+            audio_size_bytes = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(audio_url)
+    .bind(audio_url)
+    .bind(audio_url)
+    .bind(audio_url)
+    .bind(audio_type)
+    .bind(audio_size_bytes)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record the standard enclosure of an episode, replacements are told from it
+pub async fn set_episode_enclosure(
+    pool: &SqlitePool,
+    id: i64,
+    enclosure_url: &str,
+    enclosure_length: Option<i64>,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes
+        SET enclosure_url = ?, enclosure_length = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(enclosure_url)
+    .bind(enclosure_length)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Set or clear the previous file an episode's next download replaces
+pub async fn set_episode_replaced_path(
+    pool: &SqlitePool,
+    id: i64,
+    replaced_path: Option<&str>,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE episodes SET replaced_path = ? WHERE id = ?
+        "#,
+    )
+    .bind(replaced_path)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 /// Reset episode for retry
 pub async fn reset_episode_for_retry(pool: &SqlitePool, id: i64) -> AppResult<()> {
     sqlx::query(
@@ -250,7 +543,9 @@ pub async fn reset_episode_for_retry(pool: &SqlitePool, id: i64) -> AppResult<()
         UPDATE episodes
         SET download_status = 'pending',
             download_progress = 0,
-            download_error = NULL
+            download_error = NULL,
+            download_attempts = 0,
+            skip_reason = NULL
         WHERE id = ?
         "#,
     )
@@ -275,6 +570,21 @@ pub async fn delete_episode(pool: &SqlitePool, id: i64) -> AppResult<()> {
     Ok(())
 }
 
+/// Protect an episode's file from the retention rules, or lift the protection
+pub async fn set_episode_protected(pool: &SqlitePool, id: i64, protected: bool) -> AppResult<()> {
+    let result = sqlx::query("UPDATE episodes SET protected = ? WHERE id = ?")
+        .bind(protected)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Episode with id {} not found", id)));
+    }
+
+    Ok(())
+}
+
 /// Update episode status (simple version)
 pub async fn update_episode_status_simple(pool: &SqlitePool, id: i64, status: &str) -> AppResult<()> {
     sqlx::query(
@@ -301,7 +611,10 @@ pub async fn clear_episode_download_info(pool: &SqlitePool, id: i64) -> AppResul
             download_progress = 0,
             download_started_at = NULL,
             download_completed_at = NULL,
-            download_error = NULL
+            download_error = NULL,
+            resume_offset = 0,
+            resume_etag = NULL,
+            resume_last_modified = NULL
         WHERE id = ?
         "#,
     )
@@ -346,13 +659,13 @@ pub async fn count_completed_episodes(pool: &SqlitePool, subscription_id: i64) -
     Ok(count)
 }
 
-/// Count all episodes for a subscription (all statuses)
+/// Count the episodes of a subscription (all statuses except skipped and removed)
 pub async fn count_all_episodes(pool: &SqlitePool, subscription_id: i64) -> AppResult<i64> {
     let count = sqlx::query_scalar::<_, i64>(
         r#"
         SELECT COUNT(*)
         FROM episodes
-        WHERE subscription_id = ?
+        WHERE subscription_id = ? AND download_status NOT IN ('skipped', 'removed')
         "#,
     )
     .bind(subscription_id)
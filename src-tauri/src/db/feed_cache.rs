@@ -0,0 +1,57 @@
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::db::models::FeedCache;
+use crate::utils::AppResult;
+
+/// Get the cached feed of a subscription, if it was fetched from `url`
+pub async fn get_feed_cache(
+    pool: &SqlitePool,
+    subscription_id: i64,
+    url: &str,
+) -> AppResult<Option<FeedCache>> {
+    let cache = sqlx::query_as::<_, FeedCache>(
+        r#"
+        SELECT * FROM feed_cache WHERE subscription_id = ? AND url = ?
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(url)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(cache)
+}
+
+/// Store the last fetched feed of a subscription with its validators
+pub async fn save_feed_cache(
+    pool: &SqlitePool,
+    subscription_id: i64,
+    url: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+    body: &str,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO feed_cache (subscription_id, url, etag, last_modified, body, fetched_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(subscription_id) DO UPDATE SET
+            url = excluded.url,
+            etag = excluded.etag,
+            last_modified = excluded.last_modified,
+            body = excluded.body,
+            fetched_at = excluded.fetched_at
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(url)
+    .bind(etag)
+    .bind(last_modified)
+    .bind(body)
+    .bind(Utc::now())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::db::models::EpisodeFilter;
+use crate::utils::AppResult;
+
+/// Filter rules of a subscription, an empty filter when it has none
+pub async fn get_episode_filter(
+    pool: &SqlitePool,
+    subscription_id: i64,
+) -> AppResult<EpisodeFilter> {
+    let filter = sqlx::query_as::<_, EpisodeFilter>(
+        r#"
+        SELECT subscription_id, title_include, title_exclude, description_include,
+            description_exclude, author_include, min_duration_seconds, max_duration_seconds,
+            min_size_bytes, max_size_bytes, published_after
+        FROM episode_filters WHERE subscription_id = ?
+        "#,
+    )
+    .bind(subscription_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(filter.unwrap_or(EpisodeFilter {
+        subscription_id,
+        ..EpisodeFilter::default()
+    }))
+}
+
+/// Replace the filter rules of a subscription
+pub async fn save_episode_filter(pool: &SqlitePool, filter: &EpisodeFilter) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO episode_filters (
+            subscription_id, title_include, title_exclude, description_include,
+            description_exclude, author_include, min_duration_seconds, max_duration_seconds,
+            min_size_bytes, max_size_bytes, published_after, updated_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(subscription_id) DO UPDATE SET
+            title_include = excluded.title_include,
+            title_exclude = excluded.title_exclude,
+            description_include = excluded.description_include,
+            description_exclude = excluded.description_exclude,
+            author_include = excluded.author_include,
+            min_duration_seconds = excluded.min_duration_seconds,
+            max_duration_seconds = excluded.max_duration_seconds,
+            min_size_bytes = excluded.min_size_bytes,
+            max_size_bytes = excluded.max_size_bytes,
+            published_after = excluded.published_after,
+            updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(filter.subscription_id)
+    .bind(&filter.title_include)
+    .bind(&filter.title_exclude)
+    .bind(&filter.description_include)
+    .bind(&filter.description_exclude)
+    .bind(&filter.author_include)
+    .bind(filter.min_duration_seconds)
+    .bind(filter.max_duration_seconds)
+    .bind(filter.min_size_bytes)
+    .bind(filter.max_size_bytes)
+    .bind(filter.published_after)
+    .bind(Utc::now())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,17 +1,29 @@
 pub mod episodes;
+pub mod feed_cache;
+pub mod filters;
 pub mod models;
 pub mod queue;
+pub mod retention;
 pub mod settings;
 pub mod subscriptions;
+#[cfg(test)]
+pub mod test_support;
 
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
-use sqlx::ConnectOptions;
+use sqlx::migrate::Migrator;
+use sqlx::query::QueryAs;
+use sqlx::sqlite::{
+    SqliteArguments, SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow,
+};
+use sqlx::{ConnectOptions, FromRow, Sqlite};
 use std::path::PathBuf;
 use std::str::FromStr;
 use tracing::log::LevelFilter;
 
 use crate::utils::AppResult;
 
+/// Schema migrations embedded in the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
 /// Initialize database connection pool
 pub async fn init_database(db_path: PathBuf) -> AppResult<SqlitePool> {
     // Ensure parent directory exists
@@ -32,9 +44,24 @@ pub async fn init_database(db_path: PathBuf) -> AppResult<SqlitePool> {
         .await?;
 
     // Run migrations
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    MIGRATOR.run(&pool).await?;
 
     tracing::info!("Database initialized at: {}", db_path.display());
 
     Ok(pool)
 }
+
+/// Row returned by an `INSERT`/`UPDATE ... RETURNING` statement, once it is committed
+/// `fetch_one` hands the row back before the statement finished, so another connection
+/// of the pool could still miss the change
+pub async fn fetch_returning<'q, T>(
+    query: QueryAs<'q, Sqlite, T, SqliteArguments<'q>>,
+    pool: &SqlitePool,
+) -> AppResult<T>
+where
+    T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+{
+    let row = query.fetch_all(pool).await?.pop();
+
+    Ok(row.ok_or(sqlx::Error::RowNotFound)?)
+}
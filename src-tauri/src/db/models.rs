@@ -2,7 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
 pub struct Subscription {
     pub id: i64,
     pub name: String,
@@ -15,7 +15,26 @@ pub struct Subscription {
     pub enabled: bool,
     pub preferred_quality: String,
     pub max_episodes: Option<i32>,
+    pub max_storage_bytes: Option<i64>,
+    pub retention_days: Option<i32>,
+    pub retention_keep_completed: Option<i32>,
+    pub archive_directory: Option<String>,
     pub filename_format: String,
+    pub retry_max_attempts: Option<i32>,
+    pub retry_base_delay_seconds: Option<i32>,
+    pub ignore_bandwidth_schedule: bool,
+    pub download_priority: i32,
+    pub identity_strategy: String,
+    pub write_tags: bool,
+    pub tag_title_format: String,
+    pub tag_artist_format: String,
+    pub tag_album_format: String,
+    pub tag_comment_format: String,
+    pub on_completed_command: String,
+    pub on_failed_command: String,
+    pub initial_download: Option<String>,
+    pub initial_download_count: Option<i32>,
+    pub initial_download_since: Option<DateTime<Utc>>,
     pub last_checked_at: Option<DateTime<Utc>>,
     pub last_success_at: Option<DateTime<Utc>>,
     pub last_error: Option<String>,
@@ -25,7 +44,7 @@ pub struct Subscription {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreateSubscriptionData {
     pub name: String,
     pub rss_url: String,
@@ -36,10 +55,54 @@ pub struct CreateSubscriptionData {
     pub max_items_to_check: i32,
     pub preferred_quality: String,
     pub max_episodes: Option<i32>,
+    /// Storage quota in bytes, 0 removes it and the current one is kept when missing
+    #[serde(default)]
+    pub max_storage_bytes: Option<i64>,
+    /// Retention rules, 0 (or an empty directory) removes one and the current one is
+    /// kept when missing
+    #[serde(default)]
+    pub retention_days: Option<i32>,
+    #[serde(default)]
+    pub retention_keep_completed: Option<i32>,
+    #[serde(default)]
+    pub archive_directory: Option<String>,
     pub filename_format: String,
+    pub retry_max_attempts: Option<i32>,
+    pub retry_base_delay_seconds: Option<i32>,
+    #[serde(default)]
+    pub ignore_bandwidth_schedule: bool,
+    /// Priority given to the subscription's downloads, the current (or 0) is kept when missing
+    #[serde(default)]
+    pub download_priority: Option<i32>,
+    /// Only used on creation, existing episodes must be re-keyed to change it
+    #[serde(default)]
+    pub identity_strategy: Option<String>,
+    /// Tag writing settings, the current (or default) value is kept when missing
+    #[serde(default)]
+    pub write_tags: Option<bool>,
+    #[serde(default)]
+    pub tag_title_format: Option<String>,
+    #[serde(default)]
+    pub tag_artist_format: Option<String>,
+    #[serde(default)]
+    pub tag_album_format: Option<String>,
+    #[serde(default)]
+    pub tag_comment_format: Option<String>,
+    /// Hook commands, empty uses the global one
+    #[serde(default)]
+    pub on_completed_command: Option<String>,
+    #[serde(default)]
+    pub on_failed_command: Option<String>,
+    /// Only used on creation: all, none, latest or since, applied by the first check
+    #[serde(default)]
+    pub initial_download: Option<String>,
+    #[serde(default)]
+    pub initial_download_count: Option<i32>,
+    #[serde(default)]
+    pub initial_download_since: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
 pub struct Episode {
     pub id: i64,
     pub subscription_id: i64,
@@ -59,17 +122,102 @@ pub struct Episode {
     pub download_started_at: Option<DateTime<Utc>>,
     pub download_completed_at: Option<DateTime<Utc>>,
     pub download_error: Option<String>,
+    /// Failed download attempts, pauses and suspends don't count
     pub download_attempts: i32,
     pub discovered_at: DateTime<Utc>,
+    pub resume_offset: i64,
+    pub resume_etag: Option<String>,
+    pub resume_last_modified: Option<String>,
+    pub content_updated_at: Option<DateTime<Utc>>,
+    pub season: Option<i32>,
+    pub episode_number: Option<i32>,
+    /// "succeeded" or "failed" once a hook ran for the episode
+    pub hook_status: Option<String>,
+    pub hook_exit_code: Option<i32>,
+    pub hook_output: Option<String>,
+    pub hook_ran_at: Option<DateTime<Utc>>,
+    pub skip_reason: Option<String>,
+    pub download_size_bytes: Option<i64>,
+    pub protected: bool,
+    /// Enclosure the feed lists first, whatever quality was downloaded
+    pub enclosure_url: Option<String>,
+    pub enclosure_length: Option<i64>,
+    /// Previous file of an updated episode, removed once the new one is downloaded
+    pub replaced_path: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[allow(dead_code)]
+/// Something a retention rule removes, as shown by a dry run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionAction {
+    pub episode_id: i64,
+    pub title: String,
+    /// The episode's file, if it has one
+    pub file_path: Option<String>,
+    /// Whether the file is moved to the archive directory instead of deleted
+    pub archive: bool,
+    /// Whether the episode itself is removed, otherwise it stays known as removed
+    pub remove_episode: bool,
+    pub reason: String,
+}
+
+/// A file or episode removed by a retention rule
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RetentionHistoryEntry {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub episode_id: i64,
+    pub episode_title: String,
+    pub file_path: Option<String>,
+    pub archive_path: Option<String>,
+    pub episode_removed: bool,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct DownloadQueueItem {
     pub id: i64,
     pub episode_id: i64,
     pub priority: i32,
     pub added_at: DateTime<Utc>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub paused: bool,
+}
+
+/// Rules new items of a subscription must pass to be downloaded, None disables a rule
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct EpisodeFilter {
+    #[serde(default)]
+    pub subscription_id: i64,
+    pub title_include: Option<String>,
+    pub title_exclude: Option<String>,
+    pub description_include: Option<String>,
+    pub description_exclude: Option<String>,
+    pub author_include: Option<String>,
+    pub min_duration_seconds: Option<i32>,
+    pub max_duration_seconds: Option<i32>,
+    pub min_size_bytes: Option<i64>,
+    pub max_size_bytes: Option<i64>,
+    pub published_after: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FeedCache {
+    pub subscription_id: i64,
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Fields of a new subscription that the user didn't choose, from the default settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubscriptionDefaults {
+    pub check_frequency_minutes: i32,
+    pub max_items_to_check: i32,
+    pub preferred_quality: String,
+    pub filename_format: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -108,12 +256,57 @@ pub struct DownloadFailedPayload {
     pub error: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadStateChangedPayload {
+    pub episode_id: i64,
+    pub subscription_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadQueueStatePayload {
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskSpaceLowPayload {
+    pub episode_id: i64,
+    pub directory: String,
+    pub required_bytes: u64,
+    pub available_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadRetryScheduledPayload {
+    pub episode_id: i64,
+    pub attempt: i32,
+    pub max_attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookFinishedPayload {
+    pub episode_id: i64,
+    pub subscription_id: i64,
+    /// "completed" or "failed", the download outcome the hook ran for
+    pub event: String,
+    pub succeeded: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EpisodeDiscoveredPayload {
     pub subscription_id: i64,
     pub episode: Episode,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodeUpdatedPayload {
+    pub subscription_id: i64,
+    pub episode: Episode,
+    /// Whether the new file was queued for download
+    pub redownload: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubscriptionCheckedPayload {
     pub subscription_id: i64,
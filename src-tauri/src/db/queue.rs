@@ -1,25 +1,86 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sqlx::SqlitePool;
 
+use crate::db::models::DownloadQueueItem;
 use crate::utils::AppResult;
 
-/// Add episode to download queue
-pub async fn add_to_queue(pool: &SqlitePool, episode_id: i64, priority: i32) -> AppResult<()> {
+/// A queue entry joined with what is needed to start its download
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct QueuedDownload {
+    pub episode_id: i64,
+    pub subscription_id: i64,
+    pub audio_url: String,
+    pub output_path: Option<String>,
+    pub ignore_bandwidth_schedule: bool,
+}
+
+/// Add episode to download queue, with its subscription's priority
+/// An existing entry keeps its priority and position, but gains an output path if it had none
+pub async fn add_to_queue(
+    pool: &SqlitePool,
+    episode_id: i64,
+    output_path: Option<&str>,
+) -> AppResult<()> {
     sqlx::query(
         r#"
-        INSERT OR IGNORE INTO download_queue (episode_id, priority, added_at)
-        VALUES (?, ?, ?)
+        INSERT INTO download_queue (episode_id, priority, added_at, output_path)
+        SELECT e.id, s.download_priority, ?, ?
+        FROM episodes e
+        JOIN subscriptions s ON s.id = e.subscription_id
+        WHERE e.id = ?
+        ON CONFLICT(episode_id) DO UPDATE SET
+            output_path = COALESCE(download_queue.output_path, excluded.output_path)
         "#,
     )
+    .bind(Utc::now())
+    .bind(output_path)
     .bind(episode_id)
-    .bind(priority)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Put an episode back in the queue for a retry at the given time
+pub async fn schedule_retry(
+    pool: &SqlitePool,
+    episode_id: i64,
+    next_attempt_at: DateTime<Utc>,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO download_queue (episode_id, priority, added_at, next_attempt_at)
+        SELECT e.id, s.download_priority, ?, ?
+        FROM episodes e
+        JOIN subscriptions s ON s.id = e.subscription_id
+        WHERE e.id = ?
+        ON CONFLICT(episode_id) DO UPDATE SET
+            next_attempt_at = excluded.next_attempt_at
+        "#,
+    )
     .bind(Utc::now())
+    .bind(next_attempt_at)
+    .bind(episode_id)
     .execute(pool)
     .await?;
 
     Ok(())
 }
 
+/// List queued items in download order
+pub async fn list_queue(pool: &SqlitePool) -> AppResult<Vec<DownloadQueueItem>> {
+    let items = sqlx::query_as::<_, DownloadQueueItem>(
+        r#"
+        SELECT * FROM download_queue
+        ORDER BY priority DESC, added_at ASC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(items)
+}
+
 /// Remove episode from queue
 pub async fn remove_from_queue(pool: &SqlitePool, episode_id: i64) -> AppResult<()> {
     sqlx::query("DELETE FROM download_queue WHERE episode_id = ?")
@@ -30,27 +91,134 @@ pub async fn remove_from_queue(pool: &SqlitePool, episode_id: i64) -> AppResult<
     Ok(())
 }
 
-/// Get next episode from queue
-#[allow(dead_code)]
-pub async fn get_next_from_queue(pool: &SqlitePool) -> AppResult<Option<i64>> {
-    let episode_id = sqlx::query_scalar::<_, i64>(
+/// List queue entries that can start now, in download order
+pub async fn list_ready_queue(pool: &SqlitePool) -> AppResult<Vec<QueuedDownload>> {
+    let items = sqlx::query_as::<_, QueuedDownload>(
         r#"
-        SELECT episode_id FROM download_queue
-        ORDER BY priority DESC, added_at ASC
+        SELECT q.episode_id, e.subscription_id, e.audio_url, q.output_path,
+               s.ignore_bandwidth_schedule
+        FROM download_queue q
+        JOIN episodes e ON e.id = q.episode_id
+        JOIN subscriptions s ON s.id = e.subscription_id
+        WHERE q.paused = 0
+          AND (q.next_attempt_at IS NULL OR q.next_attempt_at <= ?)
+        ORDER BY q.priority DESC, q.added_at ASC
+        "#,
+    )
+    .bind(Utc::now())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(items)
+}
+
+/// Move a queue entry ahead of every other one, starting it now if it was waiting
+/// for a retry; returns false if the episode isn't queued
+pub async fn bump_queue_entry(pool: &SqlitePool, episode_id: i64) -> AppResult<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE download_queue
+        SET priority = (SELECT MAX(priority) FROM download_queue) + 1,
+            next_attempt_at = NULL
+        WHERE episode_id = ?
+        "#,
+    )
+    .bind(episode_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Pause or unpause a queue entry, returns false if the episode isn't queued
+pub async fn set_queue_paused(pool: &SqlitePool, episode_id: i64, paused: bool) -> AppResult<bool> {
+    let result = sqlx::query("UPDATE download_queue SET paused = ? WHERE episode_id = ?")
+        .bind(paused)
+        .bind(episode_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get the output path recorded for a queued episode
+pub async fn get_queued_output_path(
+    pool: &SqlitePool,
+    episode_id: i64,
+) -> AppResult<Option<String>> {
+    let output_path = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT output_path FROM download_queue WHERE episode_id = ?",
+    )
+    .bind(episode_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(output_path.flatten())
+}
+
+/// Get the earliest scheduled retry that is still in the future
+pub async fn get_next_retry_at(pool: &SqlitePool) -> AppResult<Option<DateTime<Utc>>> {
+    let next = sqlx::query_scalar::<_, DateTime<Utc>>(
+        r#"
+        SELECT next_attempt_at FROM download_queue
+        WHERE paused = 0 AND next_attempt_at > ?
+        ORDER BY next_attempt_at ASC
         LIMIT 1
         "#,
     )
+    .bind(Utc::now())
     .fetch_optional(pool)
     .await?;
 
-    Ok(episode_id)
+    Ok(next)
+}
+
+/// Put episodes left in 'downloading' by a previous run back into the queue
+/// Returns the number of recovered episodes
+pub async fn recover_interrupted_downloads(pool: &SqlitePool) -> AppResult<u64> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO download_queue (episode_id, priority, added_at)
+        SELECT e.id, s.download_priority, ?
+        FROM episodes e
+        JOIN subscriptions s ON s.id = e.subscription_id
+        WHERE e.download_status = 'downloading'
+        "#,
+    )
+    .bind(Utc::now())
+    .execute(&mut *tx)
+    .await?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE episodes
+        SET download_status = 'pending'
+        WHERE download_status = 'downloading'
+        "#,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
 }
 
 /// Get queue size
 pub async fn get_queue_size(pool: &SqlitePool) -> AppResult<i32> {
-    let size = sqlx::query_scalar::<_, i32>("SELECT COUNT(*) FROM download_queue")
-        .fetch_one(pool)
-        .await?;
+    // Entries stay queued while downloading, only count the waiting ones
+    let size = sqlx::query_scalar::<_, i32>(
+        r#"
+        SELECT COUNT(*)
+        FROM download_queue q
+        JOIN episodes e ON e.id = q.episode_id
+        WHERE e.download_status != 'downloading'
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
 
     Ok(size)
 }
@@ -80,3 +248,65 @@ pub async fn remove_subscription_from_queue(pool: &SqlitePool, subscription_id:
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::{CreateSubscriptionData, Episode};
+    use crate::db::subscriptions::create_subscription;
+    use crate::db::test_support::{self, add_episode, TestDb};
+
+    async fn queue_episode(pool: &SqlitePool, subscription_id: i64, number: i64) -> i64 {
+        let episode = Episode {
+            subscription_id,
+            ..test_support::episode(number)
+        };
+        let episode = add_episode(pool, &episode).await;
+        add_to_queue(pool, episode.id, None).await.unwrap();
+
+        episode.id
+    }
+
+    async fn ready_order(pool: &SqlitePool) -> Vec<i64> {
+        list_ready_queue(pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|item| item.episode_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_priority_order() {
+        let db = TestDb::new().await;
+        let pool = &db.pool;
+
+        let mut subscription_ids = Vec::new();
+        for (name, priority) in [("Archive", 0), ("News", 10)] {
+            let data = CreateSubscriptionData {
+                name: name.to_string(),
+                rss_url: format!("https://example.com/{}.xml", name),
+                download_priority: Some(priority),
+                ..test_support::subscription_data()
+            };
+            subscription_ids.push(create_subscription(pool, data).await.unwrap().id);
+        }
+
+        let archive_1 = queue_episode(pool, subscription_ids[0], 1).await;
+        let archive_2 = queue_episode(pool, subscription_ids[0], 2).await;
+        let news = queue_episode(pool, subscription_ids[1], 3).await;
+
+        // The urgent subscription goes first, the others in the order they were queued
+        assert_eq!(ready_order(pool).await, vec![news, archive_1, archive_2]);
+
+        // Queuing again keeps the entry's place
+        add_to_queue(pool, archive_1, None).await.unwrap();
+        assert_eq!(ready_order(pool).await, vec![news, archive_1, archive_2]);
+
+        assert!(bump_queue_entry(pool, archive_2).await.unwrap());
+        assert_eq!(ready_order(pool).await, vec![archive_2, news, archive_1]);
+        assert!(!bump_queue_entry(pool, 9999).await.unwrap());
+
+        db.close().await;
+    }
+}
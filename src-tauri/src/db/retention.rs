@@ -0,0 +1,57 @@
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::db::models::{RetentionAction, RetentionHistoryEntry};
+use crate::utils::AppResult;
+
+/// Record a file or episode removed by a retention rule
+pub async fn insert_retention_history(
+    pool: &SqlitePool,
+    subscription_id: i64,
+    action: &RetentionAction,
+    archive_path: Option<&str>,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO retention_history (
+            subscription_id, episode_id, episode_title, file_path, archive_path,
+            episode_removed, reason, created_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(action.episode_id)
+    .bind(&action.title)
+    .bind(&action.file_path)
+    .bind(archive_path)
+    .bind(action.remove_episode)
+    .bind(&action.reason)
+    .bind(Utc::now())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// List the latest retention removals, of one subscription or all of them
+pub async fn list_retention_history(
+    pool: &SqlitePool,
+    subscription_id: Option<i64>,
+    limit: i64,
+) -> AppResult<Vec<RetentionHistoryEntry>> {
+    let entries = sqlx::query_as::<_, RetentionHistoryEntry>(
+        r#"
+        SELECT * FROM retention_history
+        WHERE ? IS NULL OR subscription_id = ?
+        ORDER BY created_at DESC, id DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(subscription_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(entries)
+}
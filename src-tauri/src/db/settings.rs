@@ -1,7 +1,7 @@
 use chrono::Utc;
 use sqlx::SqlitePool;
 
-use crate::db::models::Setting;
+use crate::db::models::{Setting, SubscriptionDefaults};
 use crate::utils::AppResult;
 
 /// Get setting by key
@@ -61,10 +61,32 @@ pub async fn get_setting_int(pool: &SqlitePool, key: &str, default: i32) -> AppR
 }
 
 /// Get setting as boolean
-#[allow(dead_code)]
 pub async fn get_setting_bool(pool: &SqlitePool, key: &str, default: bool) -> AppResult<bool> {
     match get_setting(pool, key).await? {
         Some(value) => Ok(value == "true"),
         None => Ok(default),
     }
 }
+
+/// Defaults of new subscriptions, the same for the subscription form and OPML imports
+pub async fn get_subscription_defaults(pool: &SqlitePool) -> AppResult<SubscriptionDefaults> {
+    let check_frequency_minutes = get_setting_int(pool, "default_check_frequency_minutes", 15)
+        .await?
+        .max(1);
+    let max_items_to_check = get_setting_int(pool, "default_max_items_to_check", 100)
+        .await?
+        .max(1);
+    let preferred_quality = get_setting(pool, "default_preferred_quality")
+        .await?
+        .unwrap_or_else(|| "enclosure".to_string());
+    let filename_format = get_setting(pool, "default_filename_format")
+        .await?
+        .unwrap_or_else(|| "{show}/{show} - {episode}".to_string());
+
+    Ok(SubscriptionDefaults {
+        check_frequency_minutes,
+        max_items_to_check,
+        preferred_quality,
+        filename_format,
+    })
+}
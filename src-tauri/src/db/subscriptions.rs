@@ -1,6 +1,7 @@
 use chrono::Utc;
 use sqlx::SqlitePool;
 
+use crate::db::fetch_returning;
 use crate::db::models::{CreateSubscriptionData, Subscription};
 use crate::utils::{AppError, AppResult};
 
@@ -11,13 +12,22 @@ pub async fn create_subscription(
 ) -> AppResult<Subscription> {
     let now = Utc::now();
 
-    let result = sqlx::query_as::<_, Subscription>(
+    let query = sqlx::query_as::<_, Subscription>(
         r#"
         INSERT INTO subscriptions (
             name, rss_url, radio_slug, automation_name,
             check_frequency_minutes, output_directory, max_items_to_check,
-            preferred_quality, max_episodes, filename_format, enabled, created_at, updated_at
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, ?, ?)
+            preferred_quality, max_episodes, filename_format,
+            retry_max_attempts, retry_base_delay_seconds, ignore_bandwidth_schedule,
+            identity_strategy, write_tags, tag_title_format, tag_artist_format,
+            tag_album_format, tag_comment_format, on_completed_command, on_failed_command,
+            initial_download, initial_download_count, initial_download_since, download_priority,
+            max_storage_bytes, retention_days, retention_keep_completed, archive_directory,
+            enabled, created_at, updated_at
+        ) VALUES (
+            ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+            1, ?, ?
+        )
         RETURNING *
         "#,
     )
@@ -31,10 +41,41 @@ pub async fn create_subscription(
     .bind(&data.preferred_quality)
     .bind(data.max_episodes)
     .bind(&data.filename_format)
+    .bind(data.retry_max_attempts)
+    .bind(data.retry_base_delay_seconds)
+    .bind(data.ignore_bandwidth_schedule)
+    .bind(data.identity_strategy.as_deref().unwrap_or("guid"))
+    .bind(data.write_tags.unwrap_or(false))
+    .bind(data.tag_title_format.as_deref().unwrap_or("{episode}"))
+    .bind(data.tag_artist_format.as_deref().unwrap_or("{program}"))
+    .bind(data.tag_album_format.as_deref().unwrap_or("{show}"))
+    .bind(
+        data.tag_comment_format
+            .as_deref()
+            .unwrap_or("{description}"),
+    )
+    .bind(data.on_completed_command.as_deref().unwrap_or(""))
+    .bind(data.on_failed_command.as_deref().unwrap_or(""))
+    // Downloading everything is the behaviour without a mode
+    .bind(
+        data.initial_download
+            .as_deref()
+            .filter(|mode| *mode != "all"),
+    )
+    .bind(data.initial_download_count)
+    .bind(data.initial_download_since)
+    .bind(data.download_priority.unwrap_or(0))
+    .bind(data.max_storage_bytes.filter(|bytes| *bytes > 0))
+    .bind(data.retention_days.filter(|days| *days > 0))
+    .bind(data.retention_keep_completed.filter(|count| *count > 0))
+    .bind(
+        data.archive_directory
+            .as_deref()
+            .filter(|directory| !directory.trim().is_empty()),
+    )
     .bind(now)
-    .bind(now)
-    .fetch_one(pool)
-    .await?;
+    .bind(now);
+    let result = fetch_returning(query, pool).await?;
 
     Ok(result)
 }
@@ -81,7 +122,21 @@ pub async fn update_subscription(
         UPDATE subscriptions
         SET name = ?, rss_url = ?, radio_slug = ?, automation_name = ?,
             check_frequency_minutes = ?, output_directory = ?, max_items_to_check = ?,
-            preferred_quality = ?, max_episodes = ?, filename_format = ?, updated_at = ?
+            preferred_quality = ?, max_episodes = ?, filename_format = ?,
+            retry_max_attempts = ?, retry_base_delay_seconds = ?,
+            ignore_bandwidth_schedule = ?, write_tags = COALESCE(?, write_tags),
+            tag_title_format = COALESCE(?, tag_title_format),
+            tag_artist_format = COALESCE(?, tag_artist_format),
+            tag_album_format = COALESCE(?, tag_album_format),
+            tag_comment_format = COALESCE(?, tag_comment_format),
+            on_completed_command = COALESCE(?, on_completed_command),
+            on_failed_command = COALESCE(?, on_failed_command),
+            download_priority = COALESCE(?, download_priority),
+            max_storage_bytes = NULLIF(COALESCE(?, max_storage_bytes), 0),
+            retention_days = NULLIF(COALESCE(?, retention_days), 0),
+            retention_keep_completed = NULLIF(COALESCE(?, retention_keep_completed), 0),
+            archive_directory = NULLIF(TRIM(COALESCE(?, archive_directory)), ''),
+            updated_at = ?
         WHERE id = ?
         "#,
     )
@@ -95,6 +150,21 @@ pub async fn update_subscription(
     .bind(&data.preferred_quality)
     .bind(data.max_episodes)
     .bind(&data.filename_format)
+    .bind(data.retry_max_attempts)
+    .bind(data.retry_base_delay_seconds)
+    .bind(data.ignore_bandwidth_schedule)
+    .bind(data.write_tags)
+    .bind(&data.tag_title_format)
+    .bind(&data.tag_artist_format)
+    .bind(&data.tag_album_format)
+    .bind(&data.tag_comment_format)
+    .bind(&data.on_completed_command)
+    .bind(&data.on_failed_command)
+    .bind(data.download_priority)
+    .bind(data.max_storage_bytes)
+    .bind(data.retention_days)
+    .bind(data.retention_keep_completed)
+    .bind(&data.archive_directory)
     .bind(now)
     .bind(id)
     .execute(pool)
@@ -219,17 +289,17 @@ pub async fn update_subscription_checked(
     Ok(())
 }
 
-/// Increment download count for subscription
-pub async fn increment_download_count(pool: &SqlitePool, id: i64) -> AppResult<()> {
+/// Forget a subscription's initial download mode once its first check applied it
+pub async fn clear_initial_download(pool: &SqlitePool, id: i64) -> AppResult<()> {
     sqlx::query(
         r#"
         UPDATE subscriptions
-        SET total_downloads = total_downloads + 1,
-            updated_at = ?
+        SET initial_download = NULL,
+            initial_download_count = NULL,
+            initial_download_since = NULL
         WHERE id = ?
         "#,
     )
-    .bind(Utc::now())
     .bind(id)
     .execute(pool)
     .await?;
@@ -237,79 +307,68 @@ pub async fn increment_download_count(pool: &SqlitePool, id: i64) -> AppResult<(
     Ok(())
 }
 
-/// Cleanup old episodes for a subscription if max_episodes is set
-/// Deletes the oldest episodes (both files and DB records) to stay within the limit
-pub async fn cleanup_old_episodes(pool: &SqlitePool, subscription_id: i64) -> AppResult<()> {
-    // Get subscription to check max_episodes
-    let subscription = get_subscription(pool, subscription_id).await?;
-
-    if let Some(max_episodes) = subscription.max_episodes {
-        if max_episodes <= 0 {
-            return Ok(());
-        }
+/// Switch a subscription to another identity strategy,
+/// replacing the keys of its existing episodes in one transaction
+pub async fn rekey_subscription(
+    pool: &SqlitePool,
+    id: i64,
+    identity_strategy: &str,
+    keys: &[(i64, String)],
+) -> AppResult<()> {
+    let mut tx = pool.begin().await?;
 
-        // Get count of ALL episodes
-        let count_result = sqlx::query_scalar::<_, i64>(
-            r#"
-            SELECT COUNT(*) FROM episodes
-            WHERE subscription_id = ?
-            "#,
+    // Move keys out of the way first, so swapped keys don't hit the unique constraint
+    for (episode_id, _) in keys {
+        sqlx::query(
+            "UPDATE episodes SET guid = 'rekey:' || id WHERE id = ? AND subscription_id = ?",
         )
-        .bind(subscription_id)
-        .fetch_one(pool)
+        .bind(episode_id)
+        .bind(id)
+        .execute(&mut *tx)
         .await?;
+    }
 
-        let episodes_to_delete = count_result - max_episodes as i64;
-
-        if episodes_to_delete > 0 {
-            // Get the oldest episodes to delete (exclude 'downloading' to avoid deleting active downloads)
-            // Priority: failed first, then completed, then pending (by oldest date)
-            let episodes_to_remove = sqlx::query_as::<_, (i64, Option<String>)>(
-                r#"
-                SELECT id, download_path FROM episodes
-                WHERE subscription_id = ? AND download_status != 'downloading'
-                ORDER BY
-                    CASE download_status
-                        WHEN 'failed' THEN 1
-                        WHEN 'completed' THEN 2
-                        WHEN 'pending' THEN 3
-                        ELSE 4
-                    END,
-                    pub_date ASC,
-                    discovered_at ASC
-                LIMIT ?
-                "#,
-            )
-            .bind(subscription_id)
-            .bind(episodes_to_delete)
-            .fetch_all(pool)
+    for (episode_id, key) in keys {
+        sqlx::query("UPDATE episodes SET guid = ? WHERE id = ? AND subscription_id = ?")
+            .bind(key)
+            .bind(episode_id)
+            .bind(id)
+            .execute(&mut *tx)
             .await?;
-
-            // Delete files and database records
-            for (episode_id, download_path) in episodes_to_remove {
-                // Delete file if it exists
-                if let Some(path) = download_path {
-                    if let Err(e) = tokio::fs::remove_file(&path).await {
-                        tracing::warn!("Failed to delete old episode file {}: {}", path, e);
-                    } else {
-                        tracing::info!("Deleted old episode file: {}", path);
-                    }
-                }
-
-                // Delete from database
-                sqlx::query("DELETE FROM episodes WHERE id = ?")
-                    .bind(episode_id)
-                    .execute(pool)
-                    .await?;
-            }
-
-            tracing::info!(
-                "Cleaned up {} old episodes for subscription {}",
-                episodes_to_delete,
-                subscription_id
-            );
-        }
     }
 
+    sqlx::query(
+        r#"
+        UPDATE subscriptions
+        SET identity_strategy = ?, updated_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(identity_strategy)
+    .bind(Utc::now())
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Increment download count for subscription
+pub async fn increment_download_count(pool: &SqlitePool, id: i64) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE subscriptions
+        SET total_downloads = total_downloads + 1,
+            updated_at = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(Utc::now())
+    .bind(id)
+    .execute(pool)
+    .await?;
+
     Ok(())
 }
@@ -0,0 +1,102 @@
+use sqlx::SqlitePool;
+use std::path::PathBuf;
+
+use crate::db::episodes::insert_episode;
+use crate::db::init_database;
+use crate::db::models::{CreateSubscriptionData, Episode, Subscription};
+
+/// A migrated database in its own temporary directory
+pub struct TestDb {
+    pub pool: SqlitePool,
+    pub directory: PathBuf,
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        let directory =
+            std::env::temp_dir().join(format!("podcastsync-test-{}", uuid::Uuid::new_v4()));
+        let pool = init_database(directory.join("app.db")).await.unwrap();
+
+        Self { pool, directory }
+    }
+
+    /// Close the database and remove its directory
+    pub async fn close(self) {
+        self.pool.close().await;
+        let _ = tokio::fs::remove_dir_all(&self.directory).await;
+    }
+}
+
+/// Data for a subscription with the required fields set, override the others as needed
+pub fn subscription_data() -> CreateSubscriptionData {
+    CreateSubscriptionData {
+        name: "Morning Show".to_string(),
+        rss_url: "https://example.com/feed.xml".to_string(),
+        check_frequency_minutes: 15,
+        output_directory: "/nonexistent".to_string(),
+        max_items_to_check: 100,
+        preferred_quality: "enclosure".to_string(),
+        filename_format: "{episode}".to_string(),
+        ..Default::default()
+    }
+}
+
+/// A stored subscription with the database defaults, without touching the database
+pub fn subscription() -> Subscription {
+    let data = subscription_data();
+
+    Subscription {
+        id: 1,
+        name: data.name,
+        rss_url: data.rss_url,
+        check_frequency_minutes: data.check_frequency_minutes,
+        output_directory: data.output_directory,
+        max_items_to_check: data.max_items_to_check,
+        enabled: true,
+        preferred_quality: data.preferred_quality,
+        filename_format: data.filename_format,
+        identity_strategy: "guid".to_string(),
+        tag_title_format: "{episode}".to_string(),
+        tag_artist_format: "{program}".to_string(),
+        tag_album_format: "{show}".to_string(),
+        tag_comment_format: "{description}".to_string(),
+        ..Default::default()
+    }
+}
+
+/// A pending episode of subscription 1, named after its id
+pub fn episode(id: i64) -> Episode {
+    Episode {
+        id,
+        subscription_id: 1,
+        guid: format!("guid-{}", id),
+        title: format!("Episode {}", id),
+        audio_url: format!("https://example.com/{}.mp3", id),
+        download_status: "pending".to_string(),
+        ..Default::default()
+    }
+}
+
+/// Insert an episode built with `episode`, returns it as stored
+pub async fn add_episode(pool: &SqlitePool, episode: &Episode) -> Episode {
+    insert_episode(
+        pool,
+        episode.subscription_id,
+        episode.guid.clone(),
+        episode.title.clone(),
+        episode.description.clone(),
+        episode.pub_date,
+        episode.audio_url.clone(),
+        episode.audio_type.clone(),
+        episode.audio_size_bytes,
+        episode.duration_seconds,
+        episode.image_url.clone(),
+        episode.program_name.clone(),
+        episode.season,
+        episode.episode_number,
+        episode.enclosure_url.clone(),
+        episode.enclosure_length,
+    )
+    .await
+    .unwrap()
+}
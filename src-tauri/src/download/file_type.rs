@@ -0,0 +1,172 @@
+use crate::utils::extension_from_mime;
+
+/// Bytes read from the start of a download to recognize its format
+pub const SNIFF_LEN: usize = 64;
+
+/// Extensions of formats whose magic bytes don't tell audio from video or the exact
+/// flavour, the server's name for them is kept when it agrees with the content
+const AMBIGUOUS_FAMILIES: &[&[&str]] = &[
+    &["mp4", "m4a", "m4b", "m4v", "mov"],
+    &["webm", "mka", "mkv"],
+];
+
+/// Extension the finished file should have
+/// The content wins over the headers, which win over the extension guessed from the feed;
+/// None when nothing is known and the guess is kept
+pub fn detect_extension(
+    head: &[u8],
+    content_type: Option<&str>,
+    content_disposition: Option<&str>,
+) -> Option<String> {
+    let declared = content_disposition
+        .and_then(extension_from_disposition)
+        .or_else(|| content_type.and_then(extension_from_mime));
+
+    match (sniff_extension(head), declared) {
+        (Some(sniffed), Some(declared)) if same_family(sniffed, &declared) => Some(declared),
+        (Some(sniffed), _) => Some(sniffed.to_string()),
+        (None, declared) => declared,
+    }
+}
+
+/// Extension matching the magic bytes at the start of a file
+pub fn sniff_extension(head: &[u8]) -> Option<&'static str> {
+    let contains = |needle: &[u8]| head.windows(needle.len()).any(|window| window == needle);
+
+    if head.starts_with(b"ID3") {
+        Some("mp3")
+    } else if head.starts_with(b"fLaC") {
+        Some("flac")
+    } else if head.starts_with(b"OggS") {
+        Some(if contains(b"OpusHead") { "opus" } else { "ogg" })
+    } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WAVE") {
+        Some("wav")
+    } else if head.starts_with(b"FORM") && matches!(head.get(8..12), Some(b"AIFF") | Some(b"AIFC"))
+    {
+        Some("aiff")
+    } else if head.get(4..8) == Some(b"ftyp") {
+        match head.get(8..12) {
+            Some(b"M4A ") | Some(b"M4B ") | Some(b"M4P ") => Some("m4a"),
+            Some(b"qt  ") => Some("mov"),
+            _ => Some("mp4"),
+        }
+    } else if head.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some(if contains(b"webm") { "webm" } else { "mka" })
+    } else if head.len() >= 2 && head[0] == 0xFF && head[1] & 0xE0 == 0xE0 {
+        // MPEG frame sync, layer bits set to 0 mean an ADTS AAC stream
+        Some(if head[1] & 0x06 == 0 { "aac" } else { "mp3" })
+    } else {
+        None
+    }
+}
+
+/// Extension of the file name in a Content-Disposition header
+/// `filename*` (RFC 5987) is preferred over `filename`
+pub fn extension_from_disposition(content_disposition: &str) -> Option<String> {
+    let mut filename = None;
+    for parameter in content_disposition.split(';') {
+        let Some((name, value)) = parameter.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"');
+        match name.trim().to_ascii_lowercase().as_str() {
+            // charset'language'percent-encoded name, the extension is plain ASCII anyway
+            "filename*" => {
+                filename = Some(value.rsplit('\'').next().unwrap_or(value));
+                break;
+            }
+            "filename" => filename = Some(value),
+            _ => {}
+        }
+    }
+
+    let (_, extension) = filename?.rsplit_once('.')?;
+    let valid = !extension.is_empty()
+        && extension.len() <= 5
+        && extension.chars().all(|c| c.is_ascii_alphanumeric());
+
+    valid.then(|| extension.to_ascii_lowercase())
+}
+
+fn same_family(sniffed: &str, declared: &str) -> bool {
+    sniffed == declared
+        || AMBIGUOUS_FAMILIES
+            .iter()
+            .any(|family| family.contains(&sniffed) && family.contains(&declared))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_extension() {
+        let ogg_opus = b"OggS\0\x02\0\0\0\0\0\0\0\0\x01\0\0\0\0\0\0\0\0\0\0\0\x01\x13OpusHead";
+        let mp4 = b"\0\0\0\x20ftypisom\0\0\x02\0";
+        let m4a = b"\0\0\0\x20ftypM4A \0\0\x02\0";
+        let webm = b"\x1A\x45\xDF\xA3\x9F\x42\x86\x81\x01\x42\x82\x84webm";
+
+        assert_eq!(sniff_extension(b"ID3\x04\0\0"), Some("mp3"));
+        assert_eq!(sniff_extension(&[0xFF, 0xFB, 0x90, 0x64]), Some("mp3"));
+        assert_eq!(sniff_extension(&[0xFF, 0xF1, 0x50, 0x80]), Some("aac"));
+        assert_eq!(sniff_extension(b"fLaC\0\0\0\x22"), Some("flac"));
+        assert_eq!(sniff_extension(ogg_opus), Some("opus"));
+        assert_eq!(sniff_extension(b"OggS\0\x02"), Some("ogg"));
+        assert_eq!(sniff_extension(b"RIFF\x24\0\0\0WAVEfmt "), Some("wav"));
+        assert_eq!(sniff_extension(mp4), Some("mp4"));
+        assert_eq!(sniff_extension(m4a), Some("m4a"));
+        assert_eq!(sniff_extension(webm), Some("webm"));
+        assert_eq!(sniff_extension(b"<!DOCTYPE html>"), None);
+        assert_eq!(sniff_extension(b""), None);
+    }
+
+    #[test]
+    fn test_extension_from_disposition() {
+        assert_eq!(
+            extension_from_disposition(r#"attachment; filename="Episode 12.opus""#).as_deref(),
+            Some("opus")
+        );
+        assert_eq!(
+            extension_from_disposition(
+                "attachment; filename=episode.mp3; filename*=UTF-8''%C3%A9pisode.M4A"
+            )
+            .as_deref(),
+            Some("m4a")
+        );
+        assert_eq!(extension_from_disposition("inline"), None);
+        assert_eq!(
+            extension_from_disposition("attachment; filename=episode"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_detect_extension() {
+        let mp4 = b"\0\0\0\x20ftypisom\0\0\x02\0";
+
+        // Content overrides a wrong or generic header
+        assert_eq!(
+            detect_extension(b"OggS\0\x02", Some("audio/mpeg"), None).as_deref(),
+            Some("ogg")
+        );
+        assert_eq!(
+            detect_extension(mp4, Some("application/octet-stream"), None).as_deref(),
+            Some("mp4")
+        );
+        // Headers name the flavour of an ambiguous container
+        assert_eq!(
+            detect_extension(mp4, Some("audio/x-m4a"), None).as_deref(),
+            Some("m4a")
+        );
+        assert_eq!(
+            detect_extension(mp4, Some("video/mp4"), Some("attachment; filename=a.m4b")).as_deref(),
+            Some("m4b")
+        );
+        // Unrecognized content
+        assert_eq!(
+            detect_extension(b"????", Some("audio/webm"), None).as_deref(),
+            Some("webm")
+        );
+        assert_eq!(detect_extension(b"????", None, None), None);
+    }
+}
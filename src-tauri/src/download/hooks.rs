@@ -0,0 +1,381 @@
+use sqlx::SqlitePool;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::db::episodes::{get_episode, update_episode_hook_result};
+use crate::db::models::{Episode, Subscription};
+use crate::db::settings::{get_setting, get_setting_int};
+use crate::db::subscriptions::get_subscription;
+use crate::utils::template::{Template, TemplateValues, HOOK_VARIABLES};
+use crate::utils::{AppError, AppResult};
+
+/// Settings holding the global hook commands
+pub const HOOK_COMMAND_SETTING_KEYS: &[&str] =
+    &["hook_on_completed_command", "hook_on_failed_command"];
+
+/// Hook output kept on the episode, the end is kept when it is longer
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+
+/// Download outcome a hook runs for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    Completed,
+    Failed,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::Completed => "completed",
+            HookEvent::Failed => "failed",
+        }
+    }
+}
+
+/// A hook command line rendered for an episode
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+/// Result of one hook run
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookOutcome {
+    /// None when the program couldn't start, timed out or was killed by a signal
+    pub exit_code: Option<i32>,
+    pub output: String,
+}
+
+impl HookOutcome {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+impl HookCommand {
+    /// Render a command line, None when it is empty
+    /// Every argument is a template, rendered values are never split into more arguments
+    pub fn build(
+        command: &str,
+        subscription: &Subscription,
+        episode: &Episode,
+        event: HookEvent,
+        path: Option<&str>,
+        error: Option<&str>,
+    ) -> AppResult<Option<Self>> {
+        let words = split_command(command)?;
+        if words.is_empty() {
+            return Ok(None);
+        }
+
+        let values = TemplateValues {
+            path,
+            status: Some(event.as_str()),
+            error,
+            ..TemplateValues::new(subscription, episode)
+        };
+        let mut args = words
+            .iter()
+            .map(|word| Ok(Template::parse_with(word, HOOK_VARIABLES)?.render_text(&values)))
+            .collect::<AppResult<Vec<_>>>()?;
+        let program = args.remove(0);
+
+        let env = [
+            ("PODCASTSYNC_EVENT", event.as_str().to_string()),
+            ("PODCASTSYNC_EPISODE_ID", episode.id.to_string()),
+            ("PODCASTSYNC_EPISODE_TITLE", episode.title.clone()),
+            ("PODCASTSYNC_EPISODE_GUID", episode.guid.clone()),
+            (
+                "PODCASTSYNC_FILE_PATH",
+                path.unwrap_or_default().to_string(),
+            ),
+            ("PODCASTSYNC_ERROR", error.unwrap_or_default().to_string()),
+            ("PODCASTSYNC_SUBSCRIPTION_ID", subscription.id.to_string()),
+            ("PODCASTSYNC_SUBSCRIPTION_NAME", subscription.name.clone()),
+            (
+                "PODCASTSYNC_AUTOMATION_NAME",
+                subscription.automation_name.clone().unwrap_or_default(),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+
+        Ok(Some(Self { program, args, env }))
+    }
+}
+
+/// Check a hook command line before saving it, an empty one disables the hook
+pub fn validate_hook_command(command: &str) -> AppResult<()> {
+    for word in split_command(command)? {
+        Template::parse_with(&word, HOOK_VARIABLES)?;
+    }
+
+    Ok(())
+}
+
+/// Run the hook configured for an episode's download outcome
+/// Non-zero exits are retried, the last run is recorded on the episode
+/// Returns None when no hook is configured, whether it succeeded otherwise
+pub async fn run_download_hook(
+    db_pool: &SqlitePool,
+    episode_id: i64,
+    event: HookEvent,
+    path: Option<&Path>,
+    error: Option<&str>,
+) -> AppResult<Option<bool>> {
+    let episode = get_episode(db_pool, episode_id).await?;
+    let subscription = get_subscription(db_pool, episode.subscription_id).await?;
+
+    let (own_command, setting) = match event {
+        HookEvent::Completed => (
+            &subscription.on_completed_command,
+            "hook_on_completed_command",
+        ),
+        HookEvent::Failed => (&subscription.on_failed_command, "hook_on_failed_command"),
+    };
+    let command = if own_command.trim().is_empty() {
+        get_setting(db_pool, setting).await?.unwrap_or_default()
+    } else {
+        own_command.clone()
+    };
+
+    let path = path.map(|path| path.display().to_string());
+    let Some(hook) = HookCommand::build(
+        &command,
+        &subscription,
+        &episode,
+        event,
+        path.as_deref(),
+        error,
+    )?
+    else {
+        return Ok(None);
+    };
+
+    let timeout = get_setting_int(db_pool, "hook_timeout_seconds", 120)
+        .await?
+        .max(1);
+    let max_attempts = get_setting_int(db_pool, "hook_max_attempts", 3)
+        .await?
+        .max(1);
+    let retry_delay = get_setting_int(db_pool, "hook_retry_delay_seconds", 30)
+        .await?
+        .max(0);
+
+    for attempt in 1..=max_attempts {
+        let outcome = run_hook(&hook, Duration::from_secs(timeout as u64)).await;
+        let status = if outcome.succeeded() {
+            "succeeded"
+        } else {
+            "failed"
+        };
+        update_episode_hook_result(
+            db_pool,
+            episode_id,
+            status,
+            outcome.exit_code,
+            &outcome.output,
+        )
+        .await?;
+
+        if outcome.succeeded() {
+            tracing::info!(
+                "{} hook succeeded for episode {}",
+                event.as_str(),
+                episode_id
+            );
+            return Ok(Some(true));
+        }
+
+        tracing::warn!(
+            "{} hook failed for episode {} (attempt {}/{}, exit code {:?})",
+            event.as_str(),
+            episode_id,
+            attempt,
+            max_attempts,
+            outcome.exit_code
+        );
+        if attempt < max_attempts {
+            tokio::time::sleep(Duration::from_secs((retry_delay * attempt) as u64)).await;
+        }
+    }
+
+    Ok(Some(false))
+}
+
+/// Run a hook once, killing it when it takes longer than `timeout`
+pub async fn run_hook(hook: &HookCommand, timeout: Duration) -> HookOutcome {
+    let child = Command::new(&hook.program)
+        .args(&hook.args)
+        .envs(hook.env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            return HookOutcome {
+                exit_code: None,
+                output: format!("Failed to start {}: {}", hook.program, e),
+            }
+        }
+    };
+
+    // Dropping the child on timeout kills it
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => HookOutcome {
+            exit_code: output.status.code(),
+            output: combine_output(&output.stdout, &output.stderr),
+        },
+        Ok(Err(e)) => HookOutcome {
+            exit_code: None,
+            output: format!("Failed to wait for {}: {}", hook.program, e),
+        },
+        Err(_) => HookOutcome {
+            exit_code: None,
+            output: format!("Timed out after {}s", timeout.as_secs()),
+        },
+    }
+}
+
+/// Split a command line into words
+/// Single quotes keep everything, double quotes allow \" inside; backslashes are
+/// literal otherwise so Windows paths need no escaping
+fn split_command(command: &str) -> AppResult<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(quoted) if quoted == c => break,
+                        Some('\\') if c == '"' && chars.peek() == Some(&'"') => {
+                            word.push(chars.next().unwrap_or('"'));
+                        }
+                        Some(quoted) => word.push(quoted),
+                        None => {
+                            return Err(AppError::InvalidInput(format!(
+                                "Invalid command \"{}\": missing closing {}",
+                                command, c
+                            )))
+                        }
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(word) = word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// Standard output followed by standard error, keeping the end when too long
+fn combine_output(stdout: &[u8], stderr: &[u8]) -> String {
+    let mut output = String::from_utf8_lossy(stdout).into_owned();
+    if !stderr.is_empty() {
+        if !output.is_empty() && !output.ends_with('\n') {
+            output.push('\n');
+        }
+        output.push_str(&String::from_utf8_lossy(stderr));
+    }
+
+    if output.len() > MAX_OUTPUT_BYTES {
+        let mut start = output.len() - MAX_OUTPUT_BYTES;
+        while !output.is_char_boundary(start) {
+            start += 1;
+        }
+        output = format!("[...]{}", &output[start..]);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(r#"/opt/import.sh --file "{path}" 'a b' x"y"z"#).unwrap(),
+            vec!["/opt/import.sh", "--file", "{path}", "a b", "xyz"]
+        );
+        assert_eq!(
+            split_command(r#"C:\Scripts\import.bat "say \"hi\"" """#).unwrap(),
+            vec![r"C:\Scripts\import.bat", r#"say "hi""#, ""]
+        );
+        assert!(split_command("   ").unwrap().is_empty());
+        assert!(split_command("echo 'unterminated").is_err());
+
+        assert!(validate_hook_command("import {path} {status} {error}").is_ok());
+        assert!(validate_hook_command("import {file}").is_err());
+    }
+
+    #[test]
+    fn test_combine_output() {
+        assert_eq!(combine_output(b"done", b""), "done");
+        assert_eq!(combine_output(b"done", b"warning\n"), "done\nwarning\n");
+
+        let long = "é".repeat(MAX_OUTPUT_BYTES);
+        let output = combine_output(long.as_bytes(), b"");
+        assert!(output.starts_with("[...]é"));
+        assert!(output.len() <= MAX_OUTPUT_BYTES + "[...]".len());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_hook() {
+        let hook = HookCommand {
+            program: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                r#"echo "$PODCASTSYNC_EPISODE_TITLE $1"; echo oops >&2; exit 3"#.to_string(),
+                "sh".to_string(),
+                "/music/News.mp3".to_string(),
+            ],
+            env: vec![("PODCASTSYNC_EPISODE_TITLE".to_string(), "News".to_string())],
+        };
+        let outcome = run_hook(&hook, Duration::from_secs(10)).await;
+        assert_eq!(outcome.exit_code, Some(3));
+        assert_eq!(outcome.output, "News /music/News.mp3\noops\n");
+        assert!(!outcome.succeeded());
+
+        let hook = HookCommand {
+            program: "sleep".to_string(),
+            args: vec!["5".to_string()],
+            env: Vec::new(),
+        };
+        let outcome = run_hook(&hook, Duration::from_millis(100)).await;
+        assert_eq!(outcome.exit_code, None);
+        assert!(outcome.output.starts_with("Timed out"));
+
+        let hook = HookCommand {
+            program: "/nonexistent/hook".to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+        };
+        assert!(run_hook(&hook, Duration::from_secs(1))
+            .await
+            .output
+            .starts_with("Failed to start"));
+    }
+}
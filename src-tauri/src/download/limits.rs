@@ -0,0 +1,322 @@
+use chrono::{Local, NaiveDateTime};
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::db::settings::{get_setting, get_setting_int};
+use crate::download::schedule::{BandwidthSchedule, ScheduleMode};
+use crate::utils::AppResult;
+
+/// Settings that affect download limits, reloaded live when changed
+pub const LIMIT_SETTING_KEYS: [&str; 4] = [
+    "max_concurrent_downloads",
+    "bandwidth_limit_bytes_per_sec",
+    "max_connections_per_host",
+    "bandwidth_schedule",
+];
+
+/// Download limits shared by the manager and all running tasks
+/// Every limit can be changed while downloads are running
+pub struct DownloadLimits {
+    concurrency: ConcurrencyLimiter,
+    /// Shared by downloads following the bandwidth schedule
+    bandwidth: BandwidthLimiter,
+    /// Shared by downloads of subscriptions that ignore the schedule,
+    /// always capped at the regular bandwidth limit
+    unscheduled_bandwidth: BandwidthLimiter,
+    /// Maximum simultaneous downloads from the same host (0 = unlimited)
+    max_per_host: AtomicUsize,
+    schedule: Mutex<ScheduleState>,
+}
+
+#[derive(Default)]
+struct ScheduleState {
+    schedule: BandwidthSchedule,
+    /// Regular bandwidth limit, used outside of schedule windows
+    base_rate: Option<u64>,
+    /// Mode applied last, None outside of every window
+    current: Option<ScheduleMode>,
+}
+
+impl DownloadLimits {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            concurrency: ConcurrencyLimiter::new(max_concurrent),
+            bandwidth: BandwidthLimiter::new(None),
+            unscheduled_bandwidth: BandwidthLimiter::new(None),
+            max_per_host: AtomicUsize::new(0),
+            schedule: Mutex::new(ScheduleState::default()),
+        }
+    }
+
+    /// Re-read all limits from settings
+    pub async fn reload(&self, pool: &SqlitePool) -> AppResult<()> {
+        let max_concurrent = get_setting_int(pool, "max_concurrent_downloads", 3).await?;
+        let bandwidth = get_setting_int(pool, "bandwidth_limit_bytes_per_sec", 0).await?;
+        let max_per_host = get_setting_int(pool, "max_connections_per_host", 0).await?;
+        let schedule = match get_setting(pool, "bandwidth_schedule").await? {
+            Some(json) => BandwidthSchedule::parse(&json).unwrap_or_else(|e| {
+                tracing::error!("Ignoring bandwidth schedule: {}", e);
+                BandwidthSchedule::default()
+            }),
+            None => BandwidthSchedule::default(),
+        };
+
+        let base_rate = (bandwidth > 0).then_some(bandwidth as u64);
+        self.concurrency.set_limit(max_concurrent.max(1) as usize);
+        self.unscheduled_bandwidth.set_rate(base_rate);
+        if let Ok(mut state) = self.schedule.lock() {
+            *state = ScheduleState {
+                schedule,
+                base_rate,
+                current: None,
+            };
+            self.bandwidth.set_rate(base_rate);
+        }
+        self.apply_schedule(Local::now().naive_local());
+        self.max_per_host
+            .store(max_per_host.max(0) as usize, Ordering::Relaxed);
+
+        tracing::info!(
+            "Download limits: max_concurrent={}, bandwidth={} B/s, per_host={}",
+            max_concurrent,
+            bandwidth,
+            max_per_host
+        );
+
+        Ok(())
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.concurrency.limit()
+    }
+
+    pub fn max_per_host(&self) -> Option<usize> {
+        match self.max_per_host.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Apply the schedule window active at `now` to the scheduled bandwidth
+    pub fn apply_schedule(&self, now: NaiveDateTime) {
+        let Ok(mut state) = self.schedule.lock() else {
+            return;
+        };
+
+        let mode = state.schedule.mode_at(now);
+        if mode == state.current {
+            return;
+        }
+
+        let rate = match mode {
+            None | Some(ScheduleMode::Paused) => state.base_rate,
+            Some(ScheduleMode::Unlimited) => None,
+            Some(ScheduleMode::Limited {
+                limit_bytes_per_sec,
+            }) => Some(limit_bytes_per_sec),
+        };
+        self.bandwidth.set_rate(rate);
+
+        tracing::info!(
+            "Bandwidth schedule changed: {:?} -> {:?}",
+            state.current,
+            mode
+        );
+        state.current = mode;
+    }
+
+    /// Whether a schedule window currently pauses downloads
+    pub fn is_schedule_paused(&self) -> bool {
+        self.schedule
+            .lock()
+            .is_ok_and(|state| state.current == Some(ScheduleMode::Paused))
+    }
+
+    /// Bandwidth limiter for a download, depending on whether it follows the schedule
+    pub fn bandwidth_for(&self, ignore_schedule: bool) -> &BandwidthLimiter {
+        if ignore_schedule {
+            &self.unscheduled_bandwidth
+        } else {
+            &self.bandwidth
+        }
+    }
+
+    /// Take a download slot if one is free
+    pub fn try_acquire_slot(&self) -> Option<OwnedSemaphorePermit> {
+        self.concurrency.try_acquire()
+    }
+
+    /// Give a download slot back when a task ends
+    pub fn release_slot(&self, permit: OwnedSemaphorePermit) {
+        self.concurrency.release(permit);
+    }
+}
+
+/// Resizable semaphore limiting the number of simultaneous downloads
+struct ConcurrencyLimiter {
+    semaphore: Arc<Semaphore>,
+    state: Mutex<ConcurrencyState>,
+}
+
+struct ConcurrencyState {
+    limit: usize,
+    /// Permits still held by running tasks that must be dropped when returned,
+    /// after the limit was lowered below the number of running downloads
+    pending_forget: usize,
+}
+
+impl ConcurrencyLimiter {
+    fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            state: Mutex::new(ConcurrencyState {
+                limit,
+                pending_forget: 0,
+            }),
+        }
+    }
+
+    fn limit(&self) -> usize {
+        self.state.lock().map(|state| state.limit).unwrap_or(0)
+    }
+
+    fn set_limit(&self, limit: usize) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        if limit > state.limit {
+            // Cancel pending removals first, then hand out new permits
+            let mut added = limit - state.limit;
+            let cancelled = added.min(state.pending_forget);
+            state.pending_forget -= cancelled;
+            added -= cancelled;
+            self.semaphore.add_permits(added);
+        } else if limit < state.limit {
+            // Remove idle permits now, the rest as running downloads finish
+            let removed = state.limit - limit;
+            let forgotten = self.semaphore.forget_permits(removed);
+            state.pending_forget += removed - forgotten;
+        }
+
+        state.limit = limit;
+    }
+
+    fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        self.semaphore.clone().try_acquire_owned().ok()
+    }
+
+    fn release(&self, permit: OwnedSemaphorePermit) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.pending_forget > 0 {
+                state.pending_forget -= 1;
+                permit.forget();
+            }
+        }
+    }
+}
+
+/// Token bucket shared by all downloads to cap the total bandwidth
+pub struct BandwidthLimiter {
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    /// Bytes per second, None means unlimited
+    rate: Option<u64>,
+    /// Bytes that can be sent right away, negative when in debt
+    available: f64,
+    last_refill: Instant,
+}
+
+impl BandwidthLimiter {
+    pub fn new(rate: Option<u64>) -> Self {
+        Self {
+            state: Mutex::new(BucketState {
+                rate,
+                available: rate.unwrap_or(0) as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn set_rate(&self, rate: Option<u64>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.rate = rate;
+            state.available = state.available.min(rate.unwrap_or(0) as f64);
+            state.last_refill = Instant::now();
+        }
+    }
+
+    /// Account for `bytes` received, waiting as long as needed to stay under the rate
+    pub async fn acquire(&self, bytes: usize) {
+        if let Some(wait) = self.reserve(bytes, Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take `bytes` from the bucket and return how long the caller must wait
+    fn reserve(&self, bytes: usize, now: Instant) -> Option<Duration> {
+        let mut state = self.state.lock().ok()?;
+        let rate = state.rate? as f64;
+
+        // Refill, allowing bursts of up to one second worth of data
+        let elapsed = now
+            .saturating_duration_since(state.last_refill)
+            .as_secs_f64();
+        state.available = (state.available + elapsed * rate).min(rate);
+        state.last_refill = now;
+
+        state.available -= bytes as f64;
+        if state.available >= 0.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(-state.available / rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrency_limit_changes() {
+        let limiter = ConcurrencyLimiter::new(2);
+        let first = limiter.try_acquire().unwrap();
+        let second = limiter.try_acquire().unwrap();
+        assert!(limiter.try_acquire().is_none());
+
+        // Raising the limit frees a slot right away
+        limiter.set_limit(3);
+        let third = limiter.try_acquire().unwrap();
+        assert!(limiter.try_acquire().is_none());
+
+        // Lowering it waits for running downloads to finish
+        limiter.set_limit(1);
+        limiter.release(first);
+        limiter.release(second);
+        assert!(limiter.try_acquire().is_none());
+        limiter.release(third);
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn test_bandwidth_limiter() {
+        let limiter = BandwidthLimiter::new(Some(1000));
+        let now = Instant::now();
+
+        // A full bucket lets one second worth of data through
+        assert_eq!(limiter.reserve(1000, now), None);
+
+        // Then each byte costs 1ms
+        assert_eq!(limiter.reserve(500, now), Some(Duration::from_millis(500)));
+
+        // Unlimited never waits
+        limiter.set_rate(None);
+        assert_eq!(limiter.reserve(1_000_000, now), None);
+    }
+}
@@ -1,22 +1,44 @@
+use chrono::{Local, Timelike, Utc};
 use futures::StreamExt;
+use reqwest::header::{
+    HeaderName, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE,
+    LAST_MODIFIED, RANGE,
+};
+use reqwest::StatusCode;
 use sqlx::SqlitePool;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tauri::{AppHandle, Manager};
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-use tokio::sync::{mpsc, Mutex};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, Mutex, Notify, OwnedSemaphorePermit};
 use tokio_util::sync::CancellationToken;
 
 use crate::db::episodes::{
-    mark_episode_completed, mark_episode_downloading, mark_episode_failed, update_episode_progress,
+    get_episode, mark_episode_completed, mark_episode_downloading, mark_episode_failed,
+    mark_episode_retry_scheduled, set_episode_replaced_path, update_episode_progress,
+    update_episode_status_simple, update_resume_offset, update_resume_state,
+};
+use crate::db::models::{
+    DiskSpaceLowPayload, DownloadCompletedPayload, DownloadFailedPayload, DownloadProgressPayload,
+    DownloadQueueStatePayload, DownloadRetryScheduledPayload, DownloadStartedPayload,
+    DownloadStateChangedPayload, HookFinishedPayload,
 };
-use crate::db::models::{DownloadCompletedPayload, DownloadFailedPayload, DownloadProgressPayload, DownloadStartedPayload};
-use crate::db::queue::remove_from_queue;
-use crate::db::subscriptions::increment_download_count;
-use crate::utils::AppResult;
+use crate::db::queue::{self, remove_from_queue, QueuedDownload};
+use crate::db::settings::{get_setting_bool, set_setting};
+use crate::db::subscriptions::{get_subscription, increment_download_count};
+use crate::download::file_type::{detect_extension, SNIFF_LEN};
+use crate::download::hooks::{run_download_hook, HookEvent};
+use crate::download::limits::{BandwidthLimiter, DownloadLimits};
+use crate::download::output_path::episode_output_path;
+use crate::download::retention::enforce_retention;
+use crate::download::retry::RetryPolicy;
+use crate::download::storage::{check_free_space, min_free_space};
+use crate::download::tags::tag_downloaded_file;
+use crate::utils::{available_path, AppError, AppResult, HttpClient};
 
 #[derive(Debug, Clone)]
 pub struct DownloadRequest {
@@ -27,8 +49,7 @@ pub struct DownloadRequest {
 }
 
 pub struct DownloadManager {
-    max_concurrent: usize,
-    active_downloads: Arc<Mutex<HashMap<i64, DownloadTask>>>,
+    control: DownloadControl,
     request_rx: mpsc::Receiver<DownloadRequest>,
     db_pool: SqlitePool,
     app_handle: AppHandle,
@@ -39,206 +60,972 @@ impl DownloadManager {
         max_concurrent: usize,
         request_rx: mpsc::Receiver<DownloadRequest>,
         db_pool: SqlitePool,
+        http_client: HttpClient,
         app_handle: AppHandle,
     ) -> Self {
-        Self {
-            max_concurrent,
+        let control = DownloadControl {
             active_downloads: Arc::new(Mutex::new(HashMap::new())),
+            wake: Arc::new(Notify::new()),
+            limits: Arc::new(DownloadLimits::new(max_concurrent)),
+            suspended: Arc::new(AtomicBool::new(false)),
+            http_client,
+            db_pool: db_pool.clone(),
+            app_handle: app_handle.clone(),
+        };
+
+        Self {
+            control,
             request_rx,
             db_pool,
             app_handle,
         }
     }
 
+    /// Handle used by commands to act on downloads while the manager runs
+    pub fn control(&self) -> DownloadControl {
+        self.control.clone()
+    }
+
+    /// The `download_queue` table is the source of truth: requests received on the
+    /// channel are persisted there, and downloads are started from it in
+    /// `priority DESC, added_at ASC` order
     pub async fn run(mut self) {
-        tracing::info!("Download manager started with max_concurrent={}", self.max_concurrent);
+        // Bandwidth and per-host limits are only known from settings
+        if let Err(e) = self.control.limits.reload(&self.db_pool).await {
+            tracing::error!("Failed to load download limits: {}", e);
+        }
+
+        tracing::info!(
+            "Download manager started with max_concurrent={}",
+            self.control.limits.max_concurrent()
+        );
 
-        while let Some(request) = self.request_rx.recv().await {
-            // Wait if max concurrent limit reached
-            while self.active_downloads.lock().await.len() >= self.max_concurrent {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+        // Downloads interrupted by a previous shutdown go back to the queue
+        match queue::recover_interrupted_downloads(&self.db_pool).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Re-queued {} interrupted download(s)", count),
+            Err(e) => tracing::error!("Failed to recover interrupted downloads: {}", e),
+        }
+
+        loop {
+            if let Err(e) = self.start_queued_downloads().await {
+                tracing::error!("Failed to start queued downloads: {}", e);
+            }
+
+            // Wake up on every minute boundary to follow the bandwidth schedule
+            let until_next_minute = Duration::from_secs(60 - Local::now().second() as u64);
+            let wait = self.time_until_next_retry().await.min(until_next_minute);
+
+            tokio::select! {
+                request = self.request_rx.recv() => {
+                    let Some(request) = request else { break };
+                    if let Err(e) = queue::add_to_queue(
+                        &self.db_pool,
+                        request.episode_id,
+                        Some(&request.output_path.display().to_string()),
+                    )
+                    .await
+                    {
+                        tracing::error!("Failed to queue episode {}: {}", request.episode_id, e);
+                    }
+                }
+                _ = self.control.wake.notified() => {}
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+
+        tracing::info!("Download manager stopped");
+    }
+
+    /// Fill free download slots with the next eligible items from the queue
+    async fn start_queued_downloads(&self) -> AppResult<()> {
+        if self.control.suspended.load(Ordering::SeqCst) || self.control.is_paused().await? {
+            return Ok(());
+        }
+
+        let limits = &self.control.limits;
+        limits.apply_schedule(Local::now().naive_local());
+        let schedule_paused = limits.is_schedule_paused();
+
+        // Held while spawning so a task can't remove itself before it is registered
+        let mut active = self.control.active_downloads.lock().await;
+
+        // A paused window interrupts running downloads, they resume when it ends
+        if schedule_paused {
+            for task in active.values().filter(|task| !task.ignore_schedule) {
+                task.stop(StopReason::Suspend);
+            }
+        }
+
+        let ready = queue::list_ready_queue(&self.db_pool).await?;
+
+        for item in ready {
+            if active.contains_key(&item.episode_id) {
+                continue;
+            }
+            if schedule_paused && !item.ignore_bandwidth_schedule {
+                continue;
+            }
+
+            // Leave the entry queued while its host is busy, another host may be free
+            let host = url_host(&item.audio_url);
+            if let (Some(max_per_host), Some(host)) = (limits.max_per_host(), &host) {
+                let connections = active
+                    .values()
+                    .filter(|task| task.host.as_ref() == Some(host))
+                    .count();
+                if connections >= max_per_host {
+                    continue;
+                }
             }
 
+            let Some(permit) = limits.try_acquire_slot() else {
+                break;
+            };
+
+            let request = match self.request_from_queue(&item).await {
+                Ok(request) => request,
+                Err(e) => {
+                    tracing::error!(
+                        "Dropping queue entry for episode {}: {}",
+                        item.episode_id,
+                        e
+                    );
+                    let _ = remove_from_queue(&self.db_pool, item.episode_id).await;
+                    limits.release_slot(permit);
+                    continue;
+                }
+            };
+
             tracing::info!("Starting download for episode {}", request.episode_id);
 
             // Spawn download worker
             let task = DownloadTask::spawn(
-                request.clone(),
+                request,
+                host,
+                item.ignore_bandwidth_schedule,
+                permit,
                 self.db_pool.clone(),
                 self.app_handle.clone(),
-                self.active_downloads.clone(),
+                self.control.clone(),
             );
 
-            self.active_downloads
-                .lock()
-                .await
-                .insert(request.episode_id, task);
+            active.insert(item.episode_id, task);
         }
 
-        tracing::info!("Download manager stopped");
+        Ok(())
+    }
+
+    /// Build a download request for a queue entry
+    /// Entries queued without an output path (e.g. recovered after a restart) use
+    /// the subscription's filename format
+    async fn request_from_queue(&self, item: &QueuedDownload) -> AppResult<DownloadRequest> {
+        let output_path = match &item.output_path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let episode = get_episode(&self.db_pool, item.episode_id).await?;
+                let subscription = get_subscription(&self.db_pool, item.subscription_id).await?;
+
+                episode_output_path(&subscription, &episode)
+            }
+        };
+
+        Ok(DownloadRequest {
+            episode_id: item.episode_id,
+            subscription_id: item.subscription_id,
+            url: item.audio_url.clone(),
+            output_path,
+        })
+    }
+
+    /// How long to sleep before a scheduled retry becomes eligible
+    async fn time_until_next_retry(&self) -> Duration {
+        const MAX_WAIT: Duration = Duration::from_secs(60);
+
+        match queue::get_next_retry_at(&self.db_pool).await {
+            Ok(Some(next_attempt_at)) => (next_attempt_at - Utc::now())
+                .to_std()
+                .unwrap_or(Duration::ZERO)
+                .min(MAX_WAIT),
+            Ok(None) => MAX_WAIT,
+            Err(e) => {
+                tracing::error!("Failed to get next retry time: {}", e);
+                MAX_WAIT
+            }
+        }
     }
 }
 
+/// Why a running download was interrupted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Discard the download and its partial file
+    Cancel,
+    /// Keep the partial file, the episode stays paused until resumed
+    Pause,
+    /// Keep the partial file and put the episode back in the queue (global pause)
+    Suspend,
+}
+
 #[allow(dead_code)]
 pub struct DownloadTask {
     pub episode_id: i64,
+    /// Host the file is downloaded from, for the per-host connection limit
+    pub host: Option<String>,
+    /// Downloads of urgent subscriptions are not throttled or paused by the schedule
+    pub ignore_schedule: bool,
     pub cancel_token: CancellationToken,
+    pub stop_reason: Arc<std::sync::Mutex<Option<StopReason>>>,
     pub handle: tokio::task::JoinHandle<()>,
 }
 
 impl DownloadTask {
     fn spawn(
         request: DownloadRequest,
+        host: Option<String>,
+        ignore_schedule: bool,
+        permit: OwnedSemaphorePermit,
         db_pool: SqlitePool,
         app_handle: AppHandle,
-        active_downloads: Arc<Mutex<HashMap<i64, DownloadTask>>>,
+        control: DownloadControl,
     ) -> Self {
+        let episode_id = request.episode_id;
         let cancel_token = CancellationToken::new();
         let token_clone = cancel_token.clone();
+        let stop_reason = Arc::new(std::sync::Mutex::new(None));
+        let stop_reason_clone = stop_reason.clone();
 
         let handle = tokio::spawn(async move {
-            // Mark as downloading in DB
-            if let Err(e) = mark_episode_downloading(&db_pool, request.episode_id).await {
-                tracing::error!("Failed to mark episode as downloading: {}", e);
-                return;
+            run_download(
+                &request,
+                &db_pool,
+                &app_handle,
+                &control,
+                control.limits.bandwidth_for(ignore_schedule),
+                token_clone,
+                stop_reason_clone,
+            )
+            .await;
+
+            // Remove from active downloads and let the manager start the next one
+            control
+                .active_downloads
+                .lock()
+                .await
+                .remove(&request.episode_id);
+            control.limits.release_slot(permit);
+            control.wake.notify_one();
+        });
+
+        Self {
+            episode_id,
+            host,
+            ignore_schedule,
+            cancel_token,
+            stop_reason,
+            handle,
+        }
+    }
+
+    /// Interrupt the download, `reason` decides what happens to the partial file
+    pub fn stop(&self, reason: StopReason) {
+        if let Ok(mut stop_reason) = self.stop_reason.lock() {
+            *stop_reason = Some(reason);
+        }
+        self.cancel_token.cancel();
+    }
+}
+
+/// Download a single episode and record the outcome
+async fn run_download(
+    request: &DownloadRequest,
+    db_pool: &SqlitePool,
+    app_handle: &AppHandle,
+    control: &DownloadControl,
+    bandwidth: &BandwidthLimiter,
+    cancel_token: CancellationToken,
+    stop_reason: Arc<std::sync::Mutex<Option<StopReason>>>,
+) {
+    let http_client = &control.http_client;
+
+    // Mark as downloading in DB
+    if let Err(e) = mark_episode_downloading(db_pool, request.episode_id).await {
+        tracing::error!("Failed to mark episode as downloading: {}", e);
+        return;
+    }
+
+    // Emit started event
+    let _ = app_handle.emit_all(
+        "download-started",
+        DownloadStartedPayload {
+            episode_id: request.episode_id,
+            subscription_id: request.subscription_id,
+        },
+    );
+
+    // Perform download
+    match download_file(
+        request,
+        db_pool,
+        app_handle,
+        http_client,
+        bandwidth,
+        cancel_token,
+    )
+    .await
+    {
+        Ok(output_path) => {
+            tracing::info!("Download completed for episode {}", request.episode_id);
+
+            // A file with its original tags is still a usable download
+            if let Err(e) = tag_downloaded_file(
+                db_pool,
+                http_client,
+                request.subscription_id,
+                request.episode_id,
+                &output_path,
+            )
+            .await
+            {
+                tracing::warn!(
+                    "Failed to write tags for episode {}: {}",
+                    request.episode_id,
+                    e
+                );
             }
 
-            // Emit started event
+            // Mark as completed, with the size of the tagged file
+            let file_size = tokio::fs::metadata(&output_path)
+                .await
+                .ok()
+                .map(|metadata| metadata.len() as i64);
+            if let Err(e) = mark_episode_completed(
+                db_pool,
+                request.episode_id,
+                output_path.display().to_string(),
+                file_size,
+            )
+            .await
+            {
+                tracing::error!("Failed to mark episode as completed: {}", e);
+            }
+            if let Err(e) = remove_replaced_file(db_pool, request.episode_id, &output_path).await {
+                tracing::warn!(
+                    "Failed to remove previous file of episode {}: {}",
+                    request.episode_id,
+                    e
+                );
+            }
+            spawn_hook(
+                db_pool,
+                app_handle,
+                request,
+                HookEvent::Completed,
+                Some(output_path.clone()),
+                None,
+            );
+
+            // Done with this queue entry
+            let _ = remove_from_queue(db_pool, request.episode_id).await;
+
+            // Increment download count
+            let _ = increment_download_count(db_pool, request.subscription_id).await;
+
+            // Apply the subscription's retention rules now there is one more episode
+            let _ = enforce_retention(db_pool, request.subscription_id, 0).await;
+
+            // Emit completed event
             let _ = app_handle.emit_all(
-                "download-started",
-                DownloadStartedPayload {
+                "download-completed",
+                DownloadCompletedPayload {
                     episode_id: request.episode_id,
                     subscription_id: request.subscription_id,
+                    file_path: output_path.display().to_string(),
                 },
             );
+        }
+        Err(AppError::DownloadCancelled) => {
+            let reason = stop_reason
+                .lock()
+                .ok()
+                .and_then(|reason| *reason)
+                .unwrap_or(StopReason::Cancel);
+            tracing::info!(
+                "Download for episode {} stopped ({:?})",
+                request.episode_id,
+                reason
+            );
 
-            // Remove from queue
-            let _ = remove_from_queue(&db_pool, request.episode_id).await;
-
-            // Perform download
-            match download_file(
-                &request.url,
-                &request.output_path,
+            if let Err(e) = finish_stopped_download(db_pool, request, reason).await {
+                tracing::error!("Failed to update stopped download: {}", e);
+            }
+        }
+        Err(AppError::InsufficientSpace {
+            required,
+            available,
+        }) => {
+            tracing::warn!(
+                "Not enough disk space for episode {} ({} bytes needed, {} free), pausing downloads",
                 request.episode_id,
-                &db_pool,
-                &app_handle,
-                token_clone,
-            )
-            .await
+                required,
+                available
+            );
+
+            // The partial file and the queue entry are kept until space is freed
+            // and the queue resumed
+            if let Err(e) =
+                update_episode_status_simple(db_pool, request.episode_id, "pending").await
             {
-                Ok(_) => {
-                    tracing::info!("Download completed for episode {}", request.episode_id);
+                tracing::error!("Failed to update stopped download: {}", e);
+            }
+            let directory = request.output_path.parent().unwrap_or(&request.output_path);
+            let _ = app_handle.emit_all(
+                "disk-space-low",
+                DiskSpaceLowPayload {
+                    episode_id: request.episode_id,
+                    directory: directory.display().to_string(),
+                    required_bytes: required,
+                    available_bytes: available,
+                },
+            );
+            if let Err(e) = control.pause_all().await {
+                tracing::error!("Failed to pause downloads: {}", e);
+            }
+        }
+        Err(e) => {
+            tracing::error!("Download failed for episode {}: {}", request.episode_id, e);
 
-                    // Mark as completed
-                    if let Err(e) = mark_episode_completed(
-                        &db_pool,
-                        request.episode_id,
-                        request.output_path.display().to_string(),
-                    )
-                    .await
-                    {
-                        tracing::error!("Failed to mark episode as completed: {}", e);
-                    }
+            // Give transient failures another chance before giving up
+            let retry = match schedule_retry(db_pool, request, &e).await {
+                Ok(retry) => retry,
+                Err(schedule_error) => {
+                    tracing::error!("Failed to schedule retry: {}", schedule_error);
+                    None
+                }
+            };
 
-                    // Increment download count
-                    let _ = increment_download_count(&db_pool, request.subscription_id).await;
+            if let Some((payload, delay)) = retry {
+                tracing::info!(
+                    "Retrying episode {} in {}s (attempt {}/{})",
+                    request.episode_id,
+                    delay.as_secs(),
+                    payload.attempt + 1,
+                    payload.max_attempts
+                );
 
-                    // Cleanup old episodes if max_episodes is set
-                    let _ = crate::db::subscriptions::cleanup_old_episodes(&db_pool, request.subscription_id).await;
+                let _ = app_handle.emit_all("download-retry-scheduled", payload);
+            } else {
+                // Leave the queue for good
+                let _ = remove_from_queue(db_pool, request.episode_id).await;
 
-                    // Emit completed event
-                    let _ = app_handle.emit_all(
-                        "download-completed",
-                        DownloadCompletedPayload {
-                            episode_id: request.episode_id,
-                            subscription_id: request.subscription_id,
-                            file_path: request.output_path.display().to_string(),
-                        },
-                    );
+                // Mark as failed
+                if let Err(e) =
+                    mark_episode_failed(db_pool, request.episode_id, e.to_string()).await
+                {
+                    tracing::error!("Failed to mark episode as failed: {}", e);
                 }
-                Err(e) => {
-                    tracing::error!("Download failed for episode {}: {}", request.episode_id, e);
+                spawn_hook(
+                    db_pool,
+                    app_handle,
+                    request,
+                    HookEvent::Failed,
+                    None,
+                    Some(e.to_string()),
+                );
 
-                    // Mark as failed
-                    if let Err(e) =
-                        mark_episode_failed(&db_pool, request.episode_id, e.to_string()).await
-                    {
-                        tracing::error!("Failed to mark episode as failed: {}", e);
-                    }
+                // Emit failed event
+                let _ = app_handle.emit_all(
+                    "download-failed",
+                    DownloadFailedPayload {
+                        episode_id: request.episode_id,
+                        error: e.to_string(),
+                    },
+                );
+            }
+        }
+    }
+}
 
-                    // Emit failed event
-                    let _ = app_handle.emit_all(
-                        "download-failed",
-                        DownloadFailedPayload {
-                            episode_id: request.episode_id,
-                            error: e.to_string(),
-                        },
-                    );
-                }
+/// Remove the previous file of an updated episode now its new one is in place
+async fn remove_replaced_file(
+    db_pool: &SqlitePool,
+    episode_id: i64,
+    output_path: &Path,
+) -> AppResult<()> {
+    let episode = get_episode(db_pool, episode_id).await?;
+    let Some(replaced_path) = episode.replaced_path.map(PathBuf::from) else {
+        return Ok(());
+    };
+
+    if replaced_path != output_path && replaced_path.exists() {
+        tokio::fs::remove_file(&replaced_path).await?;
+        tracing::info!("Removed previous file {}", replaced_path.display());
+    }
+    set_episode_replaced_path(db_pool, episode_id, None).await
+}
+
+/// Run the hook of a download outcome in the background, it doesn't hold a download slot
+fn spawn_hook(
+    db_pool: &SqlitePool,
+    app_handle: &AppHandle,
+    request: &DownloadRequest,
+    event: HookEvent,
+    path: Option<PathBuf>,
+    error: Option<String>,
+) {
+    let db_pool = db_pool.clone();
+    let app_handle = app_handle.clone();
+    let episode_id = request.episode_id;
+    let subscription_id = request.subscription_id;
+
+    tokio::spawn(async move {
+        match run_download_hook(
+            &db_pool,
+            episode_id,
+            event,
+            path.as_deref(),
+            error.as_deref(),
+        )
+        .await
+        {
+            Ok(Some(succeeded)) => {
+                let _ = app_handle.emit_all(
+                    "download-hook-finished",
+                    HookFinishedPayload {
+                        episode_id,
+                        subscription_id,
+                        event: event.as_str().to_string(),
+                        succeeded,
+                    },
+                );
             }
+            Ok(None) => {}
+            Err(e) => tracing::error!(
+                "Failed to run {} hook for episode {}: {}",
+                event.as_str(),
+                episode_id,
+                e
+            ),
+        }
+    });
+}
 
-            // Remove from active downloads
-            active_downloads.lock().await.remove(&request.episode_id);
-        });
+/// Record the outcome of a download interrupted by a pause or cancel request
+async fn finish_stopped_download(
+    db_pool: &SqlitePool,
+    request: &DownloadRequest,
+    reason: StopReason,
+) -> AppResult<()> {
+    match reason {
+        StopReason::Cancel => {
+            discard_partial_file(db_pool, request.episode_id, &request.output_path).await;
+            update_episode_status_simple(db_pool, request.episode_id, "cancelled").await
+        }
+        StopReason::Pause => {
+            update_episode_status_simple(db_pool, request.episode_id, "paused").await
+        }
+        StopReason::Suspend => {
+            update_episode_status_simple(db_pool, request.episode_id, "pending").await
+        }
+    }
+}
 
-        Self {
-            episode_id: request.episode_id,
-            cancel_token,
-            handle,
+/// Delete the partial file of a download and forget its resume state
+async fn discard_partial_file(db_pool: &SqlitePool, episode_id: i64, output_path: &Path) {
+    let _ = tokio::fs::remove_file(part_path(output_path)).await;
+    let _ = update_resume_state(db_pool, episode_id, 0, None, None).await;
+}
+
+/// Cloneable handle to pause, resume and cancel downloads while the manager runs
+#[derive(Clone)]
+pub struct DownloadControl {
+    active_downloads: Arc<Mutex<HashMap<i64, DownloadTask>>>,
+    /// Wakes the manager up when the queue changed or a slot was freed
+    wake: Arc<Notify>,
+    limits: Arc<DownloadLimits>,
+    /// Set while the database is being replaced, nothing starts until `restart`
+    suspended: Arc<AtomicBool>,
+    http_client: HttpClient,
+    db_pool: SqlitePool,
+    app_handle: AppHandle,
+}
+
+impl DownloadControl {
+    /// Whether the whole queue is paused
+    pub async fn is_paused(&self) -> AppResult<bool> {
+        get_setting_bool(&self.db_pool, "downloads_paused", false).await
+    }
+
+    /// Cancel an episode's download, whether it is running or waiting in the queue
+    /// The partial file is deleted and the episode leaves the queue
+    pub async fn cancel(&self, episode_id: i64) -> AppResult<()> {
+        let episode = get_episode(&self.db_pool, episode_id).await?;
+        let output_path = queue::get_queued_output_path(&self.db_pool, episode_id).await?;
+        queue::remove_from_queue(&self.db_pool, episode_id).await?;
+
+        // A running task cleans up after itself once it stopped
+        if !self.stop_active(episode_id, StopReason::Cancel).await {
+            if let Some(path) = output_path {
+                discard_partial_file(&self.db_pool, episode_id, Path::new(&path)).await;
+            }
+            update_episode_status_simple(&self.db_pool, episode_id, "cancelled").await?;
         }
+
+        let _ = self.app_handle.emit_all(
+            "download-cancelled",
+            DownloadStateChangedPayload {
+                episode_id,
+                subscription_id: episode.subscription_id,
+            },
+        );
+
+        Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn cancel(&self) {
-        self.cancel_token.cancel();
+    /// Pause an episode's download, keeping its partial file for a later resume
+    pub async fn pause(&self, episode_id: i64) -> AppResult<()> {
+        let episode = get_episode(&self.db_pool, episode_id).await?;
+        if !queue::set_queue_paused(&self.db_pool, episode_id, true).await? {
+            return Err(AppError::InvalidInput(format!(
+                "Episode {} is not queued for download",
+                episode_id
+            )));
+        }
+
+        if !self.stop_active(episode_id, StopReason::Pause).await {
+            update_episode_status_simple(&self.db_pool, episode_id, "paused").await?;
+        }
+
+        let _ = self.app_handle.emit_all(
+            "download-paused",
+            DownloadStateChangedPayload {
+                episode_id,
+                subscription_id: episode.subscription_id,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Move a queued episode to the front, it starts as soon as a slot is free
+    pub async fn bump(&self, episode_id: i64) -> AppResult<()> {
+        if !queue::bump_queue_entry(&self.db_pool, episode_id).await? {
+            return Err(AppError::InvalidInput(format!(
+                "Episode {} is not queued for download",
+                episode_id
+            )));
+        }
+        self.wake.notify_one();
+
+        Ok(())
+    }
+
+    /// Resume a paused (or cancelled) episode, continuing from its partial file if any
+    pub async fn resume(&self, episode_id: i64) -> AppResult<()> {
+        let episode = get_episode(&self.db_pool, episode_id).await?;
+        if episode.download_status == "downloading" || episode.download_status == "completed" {
+            return Err(AppError::InvalidInput(format!(
+                "Episode {} is already {}",
+                episode_id, episode.download_status
+            )));
+        }
+
+        queue::add_to_queue(&self.db_pool, episode_id, None).await?;
+        queue::set_queue_paused(&self.db_pool, episode_id, false).await?;
+        update_episode_status_simple(&self.db_pool, episode_id, "pending").await?;
+        self.wake.notify_one();
+
+        let _ = self.app_handle.emit_all(
+            "download-resumed",
+            DownloadStateChangedPayload {
+                episode_id,
+                subscription_id: episode.subscription_id,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Pause the whole queue: running downloads are interrupted and go back to the queue
+    pub async fn pause_all(&self) -> AppResult<()> {
+        set_setting(&self.db_pool, "downloads_paused", "true").await?;
+
+        for task in self.active_downloads.lock().await.values() {
+            task.stop(StopReason::Suspend);
+        }
+
+        let _ = self.app_handle.emit_all(
+            "download-queue-paused",
+            DownloadQueueStatePayload { paused: true },
+        );
+
+        Ok(())
+    }
+
+    /// Resume the whole queue
+    pub async fn resume_all(&self) -> AppResult<()> {
+        set_setting(&self.db_pool, "downloads_paused", "false").await?;
+        self.wake.notify_one();
+
+        let _ = self.app_handle.emit_all(
+            "download-queue-resumed",
+            DownloadQueueStatePayload { paused: false },
+        );
+
+        Ok(())
     }
+
+    /// Apply changed limit settings to the running manager
+    /// A lower concurrency limit lets running downloads finish, it never interrupts them
+    pub async fn reload_limits(&self) -> AppResult<()> {
+        self.limits.reload(&self.db_pool).await?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Interrupt every download and hold the queue, e.g. while the database is replaced
+    /// Waits for running tasks to record their state so nothing writes afterwards
+    pub async fn suspend(&self) {
+        self.suspended.store(true, Ordering::SeqCst);
+
+        for task in self.active_downloads.lock().await.values() {
+            task.stop(StopReason::Suspend);
+        }
+
+        for _ in 0..100 {
+            if self.active_downloads.lock().await.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        tracing::warn!("Some downloads were still stopping after 10 seconds");
+    }
+
+    /// Start downloading again after `suspend`, from the queue and limits now in the database
+    pub async fn restart(&self) -> AppResult<()> {
+        self.suspended.store(false, Ordering::SeqCst);
+        queue::recover_interrupted_downloads(&self.db_pool).await?;
+        self.reload_limits().await
+    }
+
+    /// Stop a running download, returns false if the episode isn't downloading
+    async fn stop_active(&self, episode_id: i64, reason: StopReason) -> bool {
+        match self.active_downloads.lock().await.get(&episode_id) {
+            Some(task) => {
+                task.stop(reason);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Schedule another attempt for a failed download if the retry policy allows it
+/// Returns the event payload and the delay before the next attempt
+async fn schedule_retry(
+    db_pool: &SqlitePool,
+    request: &DownloadRequest,
+    error: &AppError,
+) -> AppResult<Option<(DownloadRetryScheduledPayload, Duration)>> {
+    let episode = get_episode(db_pool, request.episode_id).await?;
+    let subscription = get_subscription(db_pool, request.subscription_id).await?;
+    let policy = RetryPolicy::load(db_pool, &subscription).await?;
+
+    // Only failures count, pausing or suspending a download doesn't use up its retries
+    let attempts = episode.download_attempts + 1;
+    if !policy.should_retry(attempts, error) {
+        return Ok(None);
+    }
+
+    let delay = policy.delay_for_attempt(attempts);
+    let next_attempt_at =
+        Utc::now() + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero());
+
+    mark_episode_retry_scheduled(db_pool, request.episode_id, error.to_string()).await?;
+    queue::schedule_retry(db_pool, request.episode_id, next_attempt_at).await?;
+
+    Ok(Some((
+        DownloadRetryScheduledPayload {
+            episode_id: request.episode_id,
+            attempt: attempts,
+            max_attempts: policy.max_attempts,
+            next_attempt_at,
+            error: error.to_string(),
+        },
+        delay,
+    )))
 }
 
 async fn download_file(
-    url: &str,
-    output_path: &PathBuf,
-    episode_id: i64,
+    request: &DownloadRequest,
     db_pool: &SqlitePool,
     app_handle: &AppHandle,
+    http_client: &HttpClient,
+    bandwidth: &BandwidthLimiter,
     cancel_token: CancellationToken,
-) -> AppResult<()> {
+) -> AppResult<PathBuf> {
+    let url = request.url.as_str();
+    let output_path = &request.output_path;
+    let episode_id = request.episode_id;
+
     // Ensure output directory exists
     if let Some(parent) = output_path.parent() {
         tokio::fs::create_dir_all(parent).await?;
     }
 
+    let part_path = part_path(output_path);
+    let episode = get_episode(db_pool, episode_id).await?;
+
+    // Resume only when a previous attempt left a partial file and a validator
+    // we can send back with If-Range
+    let existing_len = match tokio::fs::metadata(&part_path).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+    let validator = resume_validator(
+        episode.resume_etag.as_deref(),
+        episode.resume_last_modified.as_deref(),
+    );
+    let resume_from = match validator {
+        Some(_) if existing_len > 0 => existing_len,
+        _ => 0,
+    };
+
+    let client = http_client.client();
+    let read_timeout = http_client.read_timeout();
+    let mut http_request = client.get(url);
+    if let Some(validator) = validator.filter(|_| resume_from > 0) {
+        tracing::info!(
+            "Resuming download for episode {} from byte {}",
+            episode_id,
+            resume_from
+        );
+        http_request = http_request
+            .header(RANGE, format!("bytes={}-", resume_from))
+            .header(IF_RANGE, validator);
+    }
+
+    if cancel_token.is_cancelled() {
+        return Err(AppError::DownloadCancelled);
+    }
+
     // Start HTTP request
-    let response = reqwest::get(url).await?;
+    let mut response = http_request.send().await?;
+
+    // The partial file no longer matches anything the server can serve, start over
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        tracing::warn!(
+            "Server rejected resume range for episode {}, restarting download",
+            episode_id
+        );
+        response = client.get(url).send().await?;
+    }
 
     let status = response.status();
     if !status.is_success() {
-        return Err(crate::utils::AppError::Other(format!(
-            "HTTP error {}: Failed to download file",
-            status
-        )));
+        return Err(AppError::HttpStatus(status));
+    }
+
+    // A 206 with the expected range means we can append to the partial file,
+    // anything else (usually a 200 because the validator changed) is a full download
+    let resumed = resume_from > 0
+        && status == StatusCode::PARTIAL_CONTENT
+        && response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_content_range_start)
+            == Some(resume_from);
+
+    if resume_from > 0 && !resumed {
+        tracing::info!(
+            "Remote file changed or range not honored for episode {}, downloading from scratch",
+            episode_id
+        );
     }
 
-    let total_size = response.content_length();
+    let start_offset = if resumed { resume_from } else { 0 };
+    let total_size = response.content_length().map(|len| len + start_offset);
+
+    // Remember validators for a later resume, only if the server supports ranges
+    let accepts_ranges = resumed
+        || response
+            .headers()
+            .get(ACCEPT_RANGES)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+    let (etag, last_modified) = if accepts_ranges {
+        (
+            header_string(&response, ETAG),
+            header_string(&response, LAST_MODIFIED),
+        )
+    } else {
+        (None, None)
+    };
+    update_resume_state(
+        db_pool,
+        episode_id,
+        start_offset as i64,
+        etag,
+        last_modified,
+    )
+    .await?;
+
+    let content_type = header_string(&response, CONTENT_TYPE);
+    let content_disposition = header_string(&response, CONTENT_DISPOSITION);
 
-    // Create output file
-    let mut file = File::create(output_path).await?;
+    // Make room for what is left to download before writing anything
+    let remaining = estimated_file_size(total_size, episode.audio_size_bytes)
+        .map_or(0, |size| size.saturating_sub(start_offset));
+    enforce_retention(db_pool, request.subscription_id, remaining).await?;
+    let directory = output_path.parent().unwrap_or(output_path);
+    let min_free = min_free_space(db_pool).await?;
+    check_free_space(directory, remaining, min_free)?;
+
+    // Open partial file, appending when resuming
+    let mut file = if resumed {
+        OpenOptions::new().append(true).open(&part_path).await?
+    } else {
+        File::create(&part_path).await?
+    };
     let mut stream = response.bytes_stream();
 
-    let mut downloaded: u64 = 0;
+    let mut downloaded: u64 = start_offset;
     let mut last_progress_update = Instant::now();
-    let mut last_downloaded = 0u64;
+    let mut last_downloaded = downloaded;
+    let mut last_chunk_len = 0;
 
-    while let Some(chunk_result) = stream.next().await {
-        // Check cancellation
-        if cancel_token.is_cancelled() {
-            // Delete partial file
-            let _ = tokio::fs::remove_file(output_path).await;
-            return Err(crate::utils::AppError::DownloadCancelled);
-        }
+    loop {
+        // Stop promptly on cancellation, even if the server stalls or we are throttled
+        // The partial file is kept, the caller decides whether to discard it
+        let chunk_result = tokio::select! {
+            biased;
+            _ = cancel_token.cancelled() => {
+                file.flush().await?;
+                let _ = update_resume_offset(db_pool, episode_id, downloaded as i64).await;
+                return Err(AppError::DownloadCancelled);
+            }
+            chunk = async {
+                // Wait for the bandwidth used by the previous chunk before reading more
+                bandwidth.acquire(last_chunk_len).await;
+                tokio::time::timeout(read_timeout, stream.next()).await
+            } => chunk,
+        };
+        // A stalled server counts as a dropped connection, so it is retried
+        let chunk_result = chunk_result.map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("No data received for {}s", read_timeout.as_secs()),
+            )
+        })?;
+        let Some(chunk_result) = chunk_result else {
+            break;
+        };
 
         let chunk = chunk_result?;
         file.write_all(&chunk).await?;
         downloaded += chunk.len() as u64;
+        last_chunk_len = chunk.len();
 
         // Emit progress event every 500ms
         let now = Instant::now();
@@ -260,6 +1047,10 @@ async fn download_file(
 
             // Update DB
             let _ = update_episode_progress(db_pool, episode_id, progress).await;
+            let _ = update_resume_offset(db_pool, episode_id, downloaded as i64).await;
+
+            // Other downloads or programs may have filled the disk in the meantime
+            check_free_space(directory, 0, min_free)?;
 
             // Emit event
             let _ = app_handle.emit_all(
@@ -278,13 +1069,257 @@ async fn download_file(
         }
     }
 
+    // Make sure everything hit the disk before the file becomes visible
     file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+
+    // Only the server's Content-Length is exact enough to reject a download over
+    if let Some(expected) = total_size {
+        if expected != downloaded {
+            // The partial data is unusable, don't try to resume from it
+            let _ = tokio::fs::remove_file(&part_path).await;
+            let _ = update_resume_state(db_pool, episode_id, 0, None, None).await;
+            return Err(AppError::SizeMismatch {
+                expected,
+                actual: downloaded,
+            });
+        }
+    } else if let Some(estimate) = estimated_file_size(None, episode.audio_size_bytes) {
+        if estimate != downloaded {
+            tracing::warn!(
+                "Episode {} is {} bytes, the feed says {}",
+                episode_id,
+                downloaded,
+                estimate
+            );
+        }
+    }
+
+    // The extension was guessed from the feed, fix it from what was actually served
+    let head = read_head(&part_path).await?;
+    let final_path = match detect_extension(
+        &head,
+        content_type.as_deref(),
+        content_disposition.as_deref(),
+    ) {
+        Some(extension)
+            if output_path.extension().and_then(|e| e.to_str()) != Some(extension.as_str()) =>
+        {
+            let final_path = available_path(output_path.with_extension(&extension));
+            tracing::info!(
+                "Episode {} is {} content, saving as {}",
+                episode_id,
+                extension,
+                final_path.display()
+            );
+            final_path
+        }
+        _ => output_path.clone(),
+    };
+
+    // Move the verified file into place
+    tokio::fs::rename(&part_path, &final_path).await?;
 
     tracing::info!(
         "Downloaded {} bytes to {}",
         downloaded,
-        output_path.display()
+        final_path.display()
     );
 
-    Ok(())
+    Ok(final_path)
+}
+
+/// First bytes of a file, fewer if it is shorter
+async fn read_head(path: &Path) -> AppResult<Vec<u8>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)
+        .await?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)
+        .await?;
+
+    Ok(head)
+}
+
+/// Path of the hidden partial file used while a download is in progress
+/// Lives next to the final file so the rename on completion is atomic
+pub fn part_path(output_path: &Path) -> PathBuf {
+    let mut file_name = std::ffi::OsString::from(".");
+    if let Some(name) = output_path.file_name() {
+        file_name.push(name);
+    }
+    file_name.push(".part");
+    output_path.with_file_name(file_name)
+}
+
+/// Size the finished file is expected to have, for space estimates
+/// Content-Length is authoritative, the enclosure length is only used when the
+/// server didn't send one (publishers often put approximate values in feeds)
+fn estimated_file_size(content_length: Option<u64>, enclosure_length: Option<i64>) -> Option<u64> {
+    content_length.or_else(|| {
+        enclosure_length
+            .filter(|len| *len > 0)
+            .map(|len| len as u64)
+    })
+}
+
+/// Pick the validator to send with If-Range (weak ETags are not allowed there)
+fn resume_validator<'a>(etag: Option<&'a str>, last_modified: Option<&'a str>) -> Option<&'a str> {
+    etag.filter(|e| !e.starts_with("W/")).or(last_modified)
+}
+
+/// Parse the first byte position from a Content-Range header ("bytes 100-199/200")
+fn parse_content_range_start(content_range: &str) -> Option<u64> {
+    content_range
+        .trim()
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Lowercased host of a download URL
+fn url_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(|host| host.to_ascii_lowercase())
+}
+
+fn header_string(response: &reqwest::Response, name: HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::{CreateSubscriptionData, Episode};
+    use crate::db::subscriptions::create_subscription;
+    use crate::db::test_support::{self, add_episode, TestDb};
+    use crate::download::queue_download;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_part_path() {
+        assert_eq!(
+            part_path(Path::new("/music/Show/Show-Episode.mp3")),
+            PathBuf::from("/music/Show/.Show-Episode.mp3.part")
+        );
+    }
+
+    #[test]
+    fn test_estimated_file_size() {
+        assert_eq!(estimated_file_size(Some(1000), Some(900)), Some(1000));
+        assert_eq!(estimated_file_size(None, Some(900)), Some(900));
+        assert_eq!(estimated_file_size(None, Some(0)), None);
+        assert_eq!(estimated_file_size(None, None), None);
+    }
+
+    #[test]
+    fn test_resume_validator() {
+        assert_eq!(
+            resume_validator(Some("\"abc\""), Some("Tue, 01 Oct 2024 10:00:00 GMT")),
+            Some("\"abc\"")
+        );
+        assert_eq!(
+            resume_validator(Some("W/\"abc\""), Some("Tue, 01 Oct 2024 10:00:00 GMT")),
+            Some("Tue, 01 Oct 2024 10:00:00 GMT")
+        );
+        assert_eq!(resume_validator(Some("W/\"abc\""), None), None);
+        assert_eq!(resume_validator(None, None), None);
+    }
+
+    #[test]
+    fn test_parse_content_range_start() {
+        assert_eq!(parse_content_range_start("bytes 100-199/200"), Some(100));
+        assert_eq!(parse_content_range_start("bytes 0-99/*"), Some(0));
+        assert_eq!(parse_content_range_start("bytes */200"), None);
+        assert_eq!(parse_content_range_start("invalid"), None);
+    }
+
+    #[tokio::test]
+    async fn test_retries_count_failed_attempts_only() {
+        let db = TestDb::new().await;
+        let pool = &db.pool;
+
+        let data = CreateSubscriptionData {
+            retry_max_attempts: Some(2),
+            ..test_support::subscription_data()
+        };
+        let subscription = create_subscription(pool, data).await.unwrap();
+        let episode = Episode {
+            subscription_id: subscription.id,
+            ..test_support::episode(1)
+        };
+        let episode = add_episode(pool, &episode).await;
+        let request = queue_download(pool, &subscription, &episode).await.unwrap();
+        let error = AppError::HttpStatus(StatusCode::SERVICE_UNAVAILABLE);
+
+        // Paused and resumed a few times before failing once
+        for _ in 0..3 {
+            mark_episode_downloading(pool, episode.id).await.unwrap();
+        }
+        let (payload, _) = schedule_retry(pool, &request, &error)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload.attempt, 1);
+        let stored = get_episode(pool, episode.id).await.unwrap();
+        assert_eq!(stored.download_attempts, 1);
+
+        // The second failure uses up both attempts
+        mark_episode_downloading(pool, episode.id).await.unwrap();
+        assert!(schedule_retry(pool, &request, &error)
+            .await
+            .unwrap()
+            .is_none());
+
+        db.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_previous_file_removed_once_replaced() {
+        let db = TestDb::new().await;
+        let pool = &db.pool;
+        let subscription = create_subscription(pool, test_support::subscription_data())
+            .await
+            .unwrap();
+        let episode = Episode {
+            subscription_id: subscription.id,
+            ..test_support::episode(1)
+        };
+        let episode = add_episode(pool, &episode).await;
+
+        let previous = db.directory.join("Episode.mp3");
+        let output_path = db.directory.join("Episode.m4a");
+        tokio::fs::write(&previous, b"previous").await.unwrap();
+        set_episode_replaced_path(pool, episode.id, Some(&previous.display().to_string()))
+            .await
+            .unwrap();
+
+        // A replacement downloaded under the same name overwrote the previous file
+        remove_replaced_file(pool, episode.id, &previous)
+            .await
+            .unwrap();
+        assert!(previous.exists());
+        let stored = get_episode(pool, episode.id).await.unwrap();
+        assert_eq!(stored.replaced_path, None);
+
+        set_episode_replaced_path(pool, episode.id, Some(&previous.display().to_string()))
+            .await
+            .unwrap();
+        remove_replaced_file(pool, episode.id, &output_path)
+            .await
+            .unwrap();
+        assert!(!previous.exists());
+
+        db.close().await;
+    }
 }
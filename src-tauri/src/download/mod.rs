@@ -1,3 +1,13 @@
+pub mod file_type;
+pub mod hooks;
+pub mod limits;
 pub mod manager;
+pub mod output_path;
+pub mod retention;
+pub mod retry;
+pub mod schedule;
+pub mod storage;
+pub mod tags;
 
-pub use manager::{DownloadManager, DownloadRequest};
+pub use manager::{DownloadControl, DownloadManager, DownloadRequest};
+pub use output_path::{download_request, episode_extension, queue_download};
@@ -0,0 +1,60 @@
+use sqlx::SqlitePool;
+use std::path::PathBuf;
+
+use crate::db::models::{Episode, Subscription};
+use crate::db::queue::add_to_queue;
+use crate::download::manager::DownloadRequest;
+use crate::utils::template::TemplateValues;
+use crate::utils::{
+    build_output_path_with_format, extension_from_mime, extract_extension, AppResult,
+};
+
+/// Extension an episode's file is expected to have: from its MIME type, then its URL,
+/// mp3 otherwise. The download corrects it from what the server actually sends
+pub fn episode_extension(episode: &Episode) -> String {
+    episode
+        .audio_type
+        .as_deref()
+        .and_then(extension_from_mime)
+        .or_else(|| extract_extension(&episode.audio_url))
+        .unwrap_or_else(|| "mp3".to_string())
+}
+
+/// Where an episode is downloaded, from the subscription's directory and filename format
+/// New episodes, retries, pending episodes and the download queue all resolve paths
+/// here so a file keeps the same name whichever way it gets downloaded
+pub fn episode_output_path(subscription: &Subscription, episode: &Episode) -> PathBuf {
+    build_output_path_with_format(
+        &subscription.output_directory,
+        &subscription.filename_format,
+        &TemplateValues::new(subscription, episode),
+        &episode_extension(episode),
+    )
+}
+
+/// Download of an episode under the name `episode_output_path` gives it
+pub fn download_request(subscription: &Subscription, episode: &Episode) -> DownloadRequest {
+    DownloadRequest {
+        episode_id: episode.id,
+        subscription_id: episode.subscription_id,
+        url: episode.audio_url.clone(),
+        output_path: episode_output_path(subscription, episode),
+    }
+}
+
+/// Add an episode to the download queue, returns the request to send to the manager
+pub async fn queue_download(
+    db_pool: &SqlitePool,
+    subscription: &Subscription,
+    episode: &Episode,
+) -> AppResult<DownloadRequest> {
+    let request = download_request(subscription, episode);
+    add_to_queue(
+        db_pool,
+        episode.id,
+        Some(&request.output_path.display().to_string()),
+    )
+    .await?;
+
+    Ok(request)
+}
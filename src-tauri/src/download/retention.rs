@@ -0,0 +1,487 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::db::episodes::{delete_episode, list_episodes_by_subscription, mark_episode_evicted};
+use crate::db::models::{Episode, RetentionAction, Subscription};
+use crate::db::retention::insert_retention_history;
+use crate::db::subscriptions::get_subscription;
+use crate::utils::{available_path, AppError, AppResult};
+
+/// A subscription's retention rules, None disables a rule
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Episodes kept, whether they were downloaded or not
+    pub max_episodes: Option<usize>,
+    /// Days a file is kept after its download
+    pub max_age_days: Option<i64>,
+    /// Completed episodes keeping their file, the latest ones
+    pub keep_completed: Option<usize>,
+    /// Bytes the subscription's files may use
+    pub max_storage_bytes: Option<u64>,
+    /// Where removed files are moved instead of being deleted
+    pub archive_directory: Option<PathBuf>,
+}
+
+impl RetentionPolicy {
+    pub fn of_subscription(subscription: &Subscription) -> Self {
+        Self {
+            max_episodes: subscription
+                .max_episodes
+                .filter(|max| *max > 0)
+                .map(|max| max as usize),
+            max_age_days: subscription
+                .retention_days
+                .filter(|days| *days > 0)
+                .map(i64::from),
+            keep_completed: subscription
+                .retention_keep_completed
+                .filter(|count| *count > 0)
+                .map(|count| count as usize),
+            max_storage_bytes: subscription
+                .max_storage_bytes
+                .filter(|quota| *quota > 0)
+                .map(|quota| quota as u64),
+            archive_directory: subscription
+                .archive_directory
+                .as_deref()
+                .map(str::trim)
+                .filter(|directory| !directory.is_empty())
+                .map(PathBuf::from),
+        }
+    }
+
+    /// What the rules remove from a subscription's episodes, making room for a download
+    /// of `incoming` bytes
+    /// Candidates are taken failed first, then completed, oldest first; protected,
+    /// queued, downloading and skipped episodes are never removed
+    pub fn plan(
+        &self,
+        episodes: &[Episode],
+        incoming: u64,
+        now: DateTime<Utc>,
+    ) -> AppResult<Vec<RetentionAction>> {
+        if let Some(quota) = self.max_storage_bytes.filter(|quota| incoming > *quota) {
+            return Err(AppError::StorageQuota {
+                size: incoming,
+                quota,
+            });
+        }
+
+        let mut candidates: Vec<&Episode> = episodes
+            .iter()
+            .filter(|episode| !episode.protected)
+            .filter(|episode| {
+                !matches!(
+                    episode.download_status.as_str(),
+                    "pending" | "paused" | "downloading" | "skipped" | "removed"
+                )
+            })
+            .collect();
+        candidates.sort_by_key(|episode| {
+            let rank = match episode.download_status.as_str() {
+                "failed" => 1,
+                "completed" => 2,
+                _ => 3,
+            };
+            (rank, episode.pub_date, episode.discovered_at)
+        });
+
+        let mut actions = Vec::new();
+        let mut removed = HashSet::new();
+        let mut remove = |episode: &Episode, remove_episode: bool, reason: String| {
+            if removed.insert(episode.id) {
+                actions.push(self.action(episode, remove_episode, reason));
+            }
+        };
+
+        // Episode limit, the oldest records go whether they have a file or not
+        if let Some(max_episodes) = self.max_episodes {
+            let counted = episodes
+                .iter()
+                .filter(|episode| {
+                    !matches!(episode.download_status.as_str(), "skipped" | "removed")
+                })
+                .count();
+            for episode in candidates.iter().take(counted.saturating_sub(max_episodes)) {
+                remove(
+                    episode,
+                    true,
+                    format!("Over the limit of {} episodes", max_episodes),
+                );
+            }
+        }
+
+        let stored: Vec<&Episode> = candidates
+            .iter()
+            .copied()
+            .filter(|episode| episode.download_path.is_some())
+            .collect();
+
+        if let Some(days) = self.max_age_days {
+            let cutoff = now - Duration::days(days);
+            for episode in &stored {
+                if episode
+                    .download_completed_at
+                    .is_some_and(|completed_at| completed_at < cutoff)
+                {
+                    remove(
+                        episode,
+                        false,
+                        format!("Downloaded more than {} days ago", days),
+                    );
+                }
+            }
+        }
+
+        if let Some(keep) = self.keep_completed {
+            let completed = stored
+                .iter()
+                .rev()
+                .filter(|episode| episode.download_status == "completed");
+            for episode in completed.skip(keep) {
+                remove(
+                    episode,
+                    false,
+                    format!("Not among the latest {} completed episodes", keep),
+                );
+            }
+        }
+
+        // Protected files take room too, only the others can make some
+        if let Some(quota) = self.max_storage_bytes {
+            let used: u64 = episodes
+                .iter()
+                .filter(|episode| episode.download_path.is_some())
+                .filter(|episode| !removed.contains(&episode.id))
+                .map(file_size)
+                .sum();
+            let mut excess = (used + incoming).saturating_sub(quota);
+
+            for episode in &stored {
+                if excess == 0 {
+                    break;
+                }
+                if !removed.insert(episode.id) {
+                    continue;
+                }
+                actions.push(self.action(
+                    episode,
+                    false,
+                    format!(
+                        "Removed to stay within the storage quota of {} bytes",
+                        quota
+                    ),
+                ));
+                excess = excess.saturating_sub(file_size(episode));
+            }
+        }
+
+        Ok(actions)
+    }
+
+    fn action(&self, episode: &Episode, remove_episode: bool, reason: String) -> RetentionAction {
+        RetentionAction {
+            episode_id: episode.id,
+            title: episode.title.clone(),
+            file_path: episode.download_path.clone(),
+            archive: self.archive_directory.is_some() && episode.download_path.is_some(),
+            remove_episode,
+            reason,
+        }
+    }
+}
+
+/// Size of an episode's file, the enclosure length for files downloaded before it was recorded
+fn file_size(episode: &Episode) -> u64 {
+    episode
+        .download_size_bytes
+        .or(episode.audio_size_bytes)
+        .unwrap_or(0)
+        .max(0) as u64
+}
+
+/// What a subscription's retention rules would remove now, without touching anything
+pub async fn preview_retention(
+    db_pool: &SqlitePool,
+    subscription: &Subscription,
+    incoming: u64,
+) -> AppResult<Vec<RetentionAction>> {
+    let episodes = list_episodes_by_subscription(db_pool, subscription.id).await?;
+
+    RetentionPolicy::of_subscription(subscription).plan(&episodes, incoming, Utc::now())
+}
+
+/// Apply a subscription's retention rules, making room for a download of `incoming` bytes
+/// Every removal is recorded in the retention history
+pub async fn enforce_retention(
+    db_pool: &SqlitePool,
+    subscription_id: i64,
+    incoming: u64,
+) -> AppResult<Vec<RetentionAction>> {
+    let subscription = get_subscription(db_pool, subscription_id).await?;
+    let actions = preview_retention(db_pool, &subscription, incoming).await?;
+    let policy = RetentionPolicy::of_subscription(&subscription);
+
+    let mut applied = Vec::new();
+    for action in actions {
+        // A file that couldn't be archived stays where it is, with its episode
+        let archive_path = match (&action.file_path, &policy.archive_directory) {
+            (Some(path), Some(archive_directory)) if action.archive => {
+                match archive_file(Path::new(path), &subscription, archive_directory).await {
+                    Ok(archive_path) => Some(archive_path.display().to_string()),
+                    Err(e) => {
+                        tracing::error!("Failed to archive {}: {}", path, e);
+                        continue;
+                    }
+                }
+            }
+            (Some(path), _) => {
+                if let Err(e) = tokio::fs::remove_file(path).await {
+                    tracing::warn!("Failed to delete old episode file {}: {}", path, e);
+                } else {
+                    tracing::info!("Deleted old episode file: {}", path);
+                }
+                None
+            }
+            (None, _) => None,
+        };
+
+        if action.remove_episode {
+            delete_episode(db_pool, action.episode_id).await?;
+        } else {
+            mark_episode_evicted(db_pool, action.episode_id, &action.reason).await?;
+        }
+        insert_retention_history(db_pool, subscription_id, &action, archive_path.as_deref())
+            .await?;
+
+        applied.push(action);
+    }
+
+    if !applied.is_empty() {
+        tracing::info!(
+            "Retention removed {} episode(s) of subscription {}",
+            applied.len(),
+            subscription_id
+        );
+    }
+
+    Ok(applied)
+}
+
+/// Move a file into the archive directory, under the same path as in the output directory
+async fn archive_file(
+    path: &Path,
+    subscription: &Subscription,
+    archive_directory: &Path,
+) -> AppResult<PathBuf> {
+    let relative_path = match path.strip_prefix(&subscription.output_directory) {
+        Ok(relative_path) => relative_path.to_path_buf(),
+        Err(_) => PathBuf::from(path.file_name().unwrap_or(path.as_os_str())),
+    };
+    let archive_path = available_path(archive_directory.join(relative_path));
+    if let Some(parent) = archive_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    // Renaming fails across disks, copy the file there instead
+    if tokio::fs::rename(path, &archive_path).await.is_err() {
+        tokio::fs::copy(path, &archive_path).await?;
+        tokio::fs::remove_file(path).await?;
+    }
+    tracing::info!(
+        "Archived old episode file {} to {}",
+        path.display(),
+        archive_path.display()
+    );
+
+    Ok(archive_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::episodes::{
+        get_episode, list_skipped_episodes, mark_episode_completed, mark_episode_skipped,
+    };
+    use crate::db::models::CreateSubscriptionData;
+    use crate::db::retention::list_retention_history;
+    use crate::db::subscriptions::create_subscription;
+    use crate::db::test_support::{self, add_episode, TestDb};
+    use chrono::TimeZone;
+
+    fn episode(id: i64, status: &str, day: u32) -> Episode {
+        let date = Utc.with_ymd_and_hms(2024, 3, day, 8, 0, 0).unwrap();
+
+        Episode {
+            pub_date: Some(date),
+            download_status: status.to_string(),
+            download_path: (status == "completed").then(|| format!("/podcasts/{}.mp3", id)),
+            download_progress: 100,
+            download_completed_at: Some(date),
+            discovered_at: date,
+            download_size_bytes: Some(100),
+            ..test_support::episode(id)
+        }
+    }
+
+    fn removed(actions: &[RetentionAction]) -> Vec<(i64, bool)> {
+        actions
+            .iter()
+            .map(|action| (action.episode_id, action.remove_episode))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut episodes = vec![
+            episode(1, "completed", 1),
+            episode(2, "failed", 2),
+            episode(3, "completed", 3),
+            episode(4, "completed", 10),
+            episode(5, "pending", 11),
+            episode(6, "completed", 12),
+        ];
+        episodes[0].protected = true;
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 8, 0, 0).unwrap();
+
+        let policy = RetentionPolicy {
+            max_age_days: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(
+            removed(&policy.plan(&episodes, 0, now).unwrap()),
+            vec![(3, false)]
+        );
+
+        let policy = RetentionPolicy {
+            keep_completed: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            removed(&policy.plan(&episodes, 0, now).unwrap()),
+            vec![(4, false), (3, false)]
+        );
+
+        // The failed episode goes first, the queued one is never removed
+        let policy = RetentionPolicy {
+            max_episodes: Some(4),
+            archive_directory: Some(PathBuf::from("/archive")),
+            ..Default::default()
+        };
+        let actions = policy.plan(&episodes, 0, now).unwrap();
+        assert_eq!(removed(&actions), vec![(2, true), (3, true)]);
+        assert!(!actions[0].archive);
+        assert!(actions[1].archive);
+        assert_eq!(actions[1].reason, "Over the limit of 4 episodes");
+    }
+
+    #[tokio::test]
+    async fn test_enforce_storage_quota() {
+        let db = TestDb::new().await;
+        let pool = &db.pool;
+
+        let data = CreateSubscriptionData {
+            max_storage_bytes: Some(300),
+            ..test_support::subscription_data()
+        };
+        let subscription = create_subscription(pool, data).await.unwrap();
+
+        let mut episode_ids = Vec::new();
+        for day in 1..=3 {
+            let episode = Episode {
+                subscription_id: subscription.id,
+                pub_date: Some(Utc.with_ymd_and_hms(2024, 3, day, 8, 0, 0).unwrap()),
+                ..test_support::episode(day as i64)
+            };
+            let episode = add_episode(pool, &episode).await;
+            let path = format!("/nonexistent/{}.mp3", day);
+            mark_episode_completed(pool, episode.id, path, Some(100))
+                .await
+                .unwrap();
+            episode_ids.push(episode.id);
+        }
+
+        assert!(matches!(
+            enforce_retention(pool, subscription.id, 400).await,
+            Err(AppError::StorageQuota {
+                size: 400,
+                quota: 300
+            })
+        ));
+
+        // 150 more bytes need the two oldest files gone
+        let preview = preview_retention(pool, &subscription, 150).await.unwrap();
+        let applied = enforce_retention(pool, subscription.id, 150).await.unwrap();
+        assert_eq!(preview, applied);
+
+        let mut statuses = Vec::new();
+        for id in &episode_ids {
+            let episode = get_episode(pool, *id).await.unwrap();
+            statuses.push((episode.download_status, episode.download_path.is_some()));
+        }
+        assert_eq!(
+            statuses,
+            vec![
+                ("removed".to_string(), false),
+                ("removed".to_string(), false),
+                ("completed".to_string(), true),
+            ]
+        );
+
+        let history = list_retention_history(pool, Some(subscription.id), 10)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|entry| !entry.episode_removed));
+
+        db.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_backfill_ignores_removed_episodes() {
+        let db = TestDb::new().await;
+        let pool = &db.pool;
+
+        let data = CreateSubscriptionData {
+            retention_keep_completed: Some(1),
+            ..test_support::subscription_data()
+        };
+        let subscription = create_subscription(pool, data).await.unwrap();
+
+        let mut episode_ids = Vec::new();
+        for day in 1..=3 {
+            let episode = Episode {
+                subscription_id: subscription.id,
+                pub_date: Some(Utc.with_ymd_and_hms(2024, 3, day, 8, 0, 0).unwrap()),
+                ..test_support::episode(day as i64)
+            };
+            episode_ids.push(add_episode(pool, &episode).await.id);
+        }
+        for id in &episode_ids[1..] {
+            mark_episode_completed(pool, *id, format!("/nonexistent/{}.mp3", id), Some(100))
+                .await
+                .unwrap();
+        }
+        mark_episode_skipped(pool, episode_ids[0], "Filtered out")
+            .await
+            .unwrap();
+
+        let applied = enforce_retention(pool, subscription.id, 0).await.unwrap();
+        assert_eq!(removed(&applied), vec![(episode_ids[1], false)]);
+
+        // Only the filtered episode can be backfilled, not the one retention removed
+        let skipped = list_skipped_episodes(pool, subscription.id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            skipped.iter().map(|episode| episode.id).collect::<Vec<_>>(),
+            vec![episode_ids[0]]
+        );
+        let evicted = get_episode(pool, episode_ids[1]).await.unwrap();
+        assert_eq!(evicted.download_status, "removed");
+
+        db.close().await;
+    }
+}
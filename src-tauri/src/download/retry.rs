@@ -0,0 +1,188 @@
+use rand::Rng;
+use sqlx::SqlitePool;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use crate::db::models::Subscription;
+use crate::db::settings::get_setting_int;
+use crate::utils::{AppError, AppResult};
+
+/// Retry policy applied when a download fails
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: i32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Random spread applied to each delay, in percent (20 = ±20%)
+    pub jitter_percent: i32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(3600),
+            jitter_percent: 20,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Load the global policy from settings, with subscription overrides applied
+    pub async fn load(pool: &SqlitePool, subscription: &Subscription) -> AppResult<Self> {
+        let defaults = Self::default();
+
+        let max_attempts = match subscription.retry_max_attempts {
+            Some(value) => value,
+            None => get_setting_int(pool, "retry_max_attempts", defaults.max_attempts).await?,
+        };
+        let base_delay_seconds = match subscription.retry_base_delay_seconds {
+            Some(value) => value,
+            None => {
+                get_setting_int(
+                    pool,
+                    "retry_base_delay_seconds",
+                    defaults.base_delay.as_secs() as i32,
+                )
+                .await?
+            }
+        };
+        let max_delay_seconds = get_setting_int(
+            pool,
+            "retry_max_delay_seconds",
+            defaults.max_delay.as_secs() as i32,
+        )
+        .await?;
+        let jitter_percent =
+            get_setting_int(pool, "retry_jitter_percent", defaults.jitter_percent).await?;
+
+        Ok(Self {
+            max_attempts: max_attempts.max(1),
+            base_delay: Duration::from_secs(base_delay_seconds.max(0) as u64),
+            max_delay: Duration::from_secs(max_delay_seconds.max(0) as u64),
+            jitter_percent: jitter_percent.clamp(0, 100),
+        })
+    }
+
+    /// Whether another attempt should be made after `attempts` failed attempts
+    pub fn should_retry(&self, attempts: i32, error: &AppError) -> bool {
+        attempts < self.max_attempts && is_retryable(error)
+    }
+
+    /// Delay before the next attempt, without jitter
+    /// Doubles after each failed attempt: base, 2*base, 4*base... capped at max_delay
+    pub fn backoff(&self, attempts: i32) -> Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 30) as u32;
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_delay)
+    }
+
+    /// Delay before the next attempt, with jitter applied
+    pub fn delay_for_attempt(&self, attempts: i32) -> Duration {
+        let backoff = self.backoff(attempts);
+        if self.jitter_percent == 0 {
+            return backoff;
+        }
+
+        let spread = self.jitter_percent as f64 / 100.0;
+        let factor = rand::thread_rng().gen_range((1.0 - spread)..=(1.0 + spread));
+        backoff.mul_f64(factor)
+    }
+}
+
+/// Whether a download error is worth retrying
+/// Server errors, timeouts and dropped connections are transient,
+/// client errors like 404/410 and local IO failures are not
+pub fn is_retryable(error: &AppError) -> bool {
+    match error {
+        AppError::HttpStatus(status) => {
+            status.is_server_error()
+                || *status == reqwest::StatusCode::REQUEST_TIMEOUT
+                || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        AppError::Http(e) => match e.status() {
+            Some(status) => status.is_server_error(),
+            None => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+        },
+        AppError::Io(e) => matches!(
+            e.kind(),
+            ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::TimedOut
+                | ErrorKind::UnexpectedEof
+                | ErrorKind::BrokenPipe
+        ),
+        AppError::SizeMismatch { .. } => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(60),
+            jitter_percent: 0,
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = policy();
+        assert_eq!(policy.backoff(1), Duration::from_secs(10));
+        assert_eq!(policy.backoff(2), Duration::from_secs(20));
+        assert_eq!(policy.backoff(3), Duration::from_secs(40));
+        assert_eq!(policy.backoff(4), Duration::from_secs(60));
+        assert_eq!(policy.backoff(100), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_delay_jitter() {
+        let policy = RetryPolicy {
+            jitter_percent: 20,
+            ..policy()
+        };
+        for _ in 0..100 {
+            let delay = policy.delay_for_attempt(2);
+            assert!(delay >= Duration::from_secs(16) && delay <= Duration::from_secs(24));
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&AppError::HttpStatus(
+            StatusCode::SERVICE_UNAVAILABLE
+        )));
+        assert!(is_retryable(&AppError::HttpStatus(
+            StatusCode::TOO_MANY_REQUESTS
+        )));
+        assert!(!is_retryable(&AppError::HttpStatus(StatusCode::NOT_FOUND)));
+        assert!(!is_retryable(&AppError::HttpStatus(StatusCode::GONE)));
+        assert!(is_retryable(&AppError::Io(
+            ErrorKind::ConnectionReset.into()
+        )));
+        assert!(!is_retryable(&AppError::Io(
+            ErrorKind::PermissionDenied.into()
+        )));
+        assert!(!is_retryable(&AppError::DownloadCancelled));
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = policy();
+        let error = AppError::HttpStatus(StatusCode::BAD_GATEWAY);
+        assert!(policy.should_retry(1, &error));
+        assert!(policy.should_retry(3, &error));
+        assert!(!policy.should_retry(4, &error));
+        assert!(!policy.should_retry(1, &AppError::HttpStatus(StatusCode::NOT_FOUND)));
+    }
+}
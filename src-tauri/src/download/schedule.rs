@@ -0,0 +1,178 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{AppError, AppResult};
+
+/// What downloads may do while a schedule window is active
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ScheduleMode {
+    /// No bandwidth cap at all
+    Unlimited,
+    /// Cap the total bandwidth to this many bytes per second
+    Limited { limit_bytes_per_sec: u64 },
+    /// Stop downloading until the window ends
+    Paused,
+}
+
+/// A recurring weekly time window, e.g. 01:00-06:00 every night
+/// Windows ending before they start span midnight
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleWindow {
+    /// Days the window starts on, 0 = Monday ... 6 = Sunday (empty = every day)
+    #[serde(default)]
+    pub days: Vec<u32>,
+    /// Local start time, "HH:MM"
+    pub start: String,
+    /// Local end time, "HH:MM"
+    pub end: String,
+    #[serde(flatten)]
+    pub mode: ScheduleMode,
+}
+
+/// Weekly bandwidth schedule stored as JSON in the `bandwidth_schedule` setting
+/// Outside of every window the regular bandwidth limit applies
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthSchedule {
+    windows: Vec<ParsedWindow>,
+}
+
+#[derive(Debug, Clone)]
+struct ParsedWindow {
+    days: Vec<u32>,
+    start: u32,
+    end: u32,
+    mode: ScheduleMode,
+}
+
+impl BandwidthSchedule {
+    /// Parse and validate the JSON form of a schedule
+    pub fn parse(json: &str) -> AppResult<Self> {
+        if json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        let windows: Vec<ScheduleWindow> = serde_json::from_str(json)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid bandwidth schedule: {}", e)))?;
+
+        let windows = windows
+            .into_iter()
+            .map(|window| {
+                if let Some(day) = window.days.iter().find(|day| **day > 6) {
+                    return Err(AppError::InvalidInput(format!(
+                        "Invalid day {} in bandwidth schedule, expected 0 (Monday) to 6 (Sunday)",
+                        day
+                    )));
+                }
+
+                Ok(ParsedWindow {
+                    days: window.days,
+                    start: parse_minutes(&window.start)?,
+                    end: parse_minutes(&window.end)?,
+                    mode: window.mode,
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(Self { windows })
+    }
+
+    /// Mode of the first window active at `now`, None outside of every window
+    pub fn mode_at(&self, now: NaiveDateTime) -> Option<ScheduleMode> {
+        let weekday = now.weekday().num_days_from_monday();
+        let previous_day = (weekday + 6) % 7;
+        let minute = now.hour() * 60 + now.minute();
+
+        self.windows
+            .iter()
+            .find(|window| {
+                let runs_on = |day: u32| window.days.is_empty() || window.days.contains(&day);
+
+                if window.start < window.end {
+                    runs_on(weekday) && minute >= window.start && minute < window.end
+                } else if window.start > window.end {
+                    // Started today, or started yesterday and not over yet
+                    (runs_on(weekday) && minute >= window.start)
+                        || (runs_on(previous_day) && minute < window.end)
+                } else {
+                    // Same start and end: the whole day
+                    runs_on(weekday)
+                }
+            })
+            .map(|window| window.mode)
+    }
+}
+
+/// Minutes since midnight for a "HH:MM" time
+fn parse_minutes(time: &str) -> AppResult<u32> {
+    let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| {
+        AppError::InvalidInput(format!(
+            "Invalid time '{}' in bandwidth schedule, expected HH:MM",
+            time
+        ))
+    })?;
+
+    Ok(time.hour() * 60 + time.minute())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2024-01-01 is a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_schedule_windows() {
+        let schedule = BandwidthSchedule::parse(
+            r#"[
+                {"start": "01:00", "end": "06:00", "mode": "unlimited"},
+                {"days": [0, 1, 2, 3, 4], "start": "07:00", "end": "09:00", "mode": "paused"},
+                {"days": [4], "start": "22:00", "end": "02:00", "mode": "limited", "limit_bytes_per_sec": 1000}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(schedule.mode_at(at(1, 3, 0)), Some(ScheduleMode::Unlimited));
+        assert_eq!(schedule.mode_at(at(1, 6, 0)), None);
+        assert_eq!(schedule.mode_at(at(1, 8, 30)), Some(ScheduleMode::Paused));
+
+        // No live show on Saturday
+        assert_eq!(schedule.mode_at(at(6, 8, 30)), None);
+
+        // Friday night window runs past midnight, earlier windows win
+        let limited = Some(ScheduleMode::Limited {
+            limit_bytes_per_sec: 1000,
+        });
+        assert_eq!(schedule.mode_at(at(5, 23, 0)), limited);
+        assert_eq!(schedule.mode_at(at(6, 0, 30)), limited);
+        assert_eq!(
+            schedule.mode_at(at(6, 1, 30)),
+            Some(ScheduleMode::Unlimited)
+        );
+        assert_eq!(schedule.mode_at(at(4, 23, 0)), None);
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        assert!(BandwidthSchedule::parse("")
+            .unwrap()
+            .mode_at(at(1, 0, 0))
+            .is_none());
+        assert!(BandwidthSchedule::parse("not json").is_err());
+        assert!(BandwidthSchedule::parse(
+            r#"[{"start": "25:00", "end": "06:00", "mode": "paused"}]"#
+        )
+        .is_err());
+        assert!(BandwidthSchedule::parse(
+            r#"[{"days": [7], "start": "01:00", "end": "06:00", "mode": "paused"}]"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use sqlx::SqlitePool;
+use std::path::Path;
+
+use crate::db::settings::get_setting;
+use crate::utils::{AppError, AppResult};
+
+/// Setting holding the free space to keep on the download disk, in bytes
+pub const MIN_FREE_SPACE_SETTING: &str = "min_free_space_bytes";
+
+const DEFAULT_MIN_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+/// Free space to keep on the download disk, 0 when the check is disabled
+pub async fn min_free_space(db_pool: &SqlitePool) -> AppResult<u64> {
+    let value = get_setting(db_pool, MIN_FREE_SPACE_SETTING).await?;
+
+    Ok(value
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_MIN_FREE_SPACE))
+}
+
+/// Space available on the disk holding `path`, which may not exist yet
+/// None when it can't be read, e.g. on a network share that doesn't report it
+pub fn free_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+
+    fs2::available_space(existing).ok()
+}
+
+/// Check `incoming` more bytes fit in `directory` while keeping `min_free` bytes free
+/// A disk whose free space is unknown is never reported as full
+pub fn check_free_space(directory: &Path, incoming: u64, min_free: u64) -> AppResult<()> {
+    if incoming == 0 && min_free == 0 {
+        return Ok(());
+    }
+    let Some(available) = free_space(directory) else {
+        return Ok(());
+    };
+
+    let required = incoming.saturating_add(min_free);
+    if available < required {
+        return Err(AppError::InsufficientSpace {
+            required,
+            available,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_free_space() {
+        let directory = std::env::temp_dir()
+            .join("podcastsync-missing")
+            .join("show");
+        let available = free_space(&directory).unwrap();
+
+        assert!(check_free_space(&directory, 0, 0).is_ok());
+        assert!(check_free_space(&directory, 1, 0).is_ok());
+        assert!(matches!(
+            check_free_space(&directory, available, u64::MAX),
+            Err(AppError::InsufficientSpace {
+                required: u64::MAX,
+                ..
+            })
+        ));
+    }
+}
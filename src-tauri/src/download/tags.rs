@@ -0,0 +1,229 @@
+use chrono::{DateTime, Utc};
+use lofty::config::WriteOptions;
+use lofty::file::{AudioFile, TaggedFileExt};
+use lofty::picture::{Picture, PictureType};
+use lofty::tag::{Accessor, ItemKey, Tag};
+use sqlx::SqlitePool;
+use std::path::Path;
+
+use crate::db::episodes::get_episode;
+use crate::db::models::{Episode, Subscription};
+use crate::db::subscriptions::get_subscription;
+use crate::utils::template::{Template, TemplateValues};
+use crate::utils::{AppError, AppResult, HttpClient};
+
+/// Cover art larger than this is not embedded
+const MAX_COVER_BYTES: usize = 5 * 1024 * 1024;
+
+/// Tags written into a downloaded file, None leaves the publisher's value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EpisodeTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub comment: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+}
+
+impl EpisodeTags {
+    /// Tags from the subscription's tag formats
+    pub fn build(subscription: &Subscription, episode: &Episode) -> AppResult<Self> {
+        let values = TemplateValues::new(subscription, episode);
+        let render = |format: &str| -> AppResult<Option<String>> {
+            let value = Template::parse(format)?.render_text(&values);
+            Ok(Some(value).filter(|value| !value.is_empty()))
+        };
+
+        Ok(Self {
+            title: render(&subscription.tag_title_format)?,
+            artist: render(&subscription.tag_artist_format)?,
+            album: render(&subscription.tag_album_format)?,
+            comment: render(&subscription.tag_comment_format)?,
+            date: episode.pub_date,
+        })
+    }
+}
+
+/// Write tags into a downloaded file when its subscription asks for it
+/// The tag format follows the file: ID3v2.4 for MP3, Vorbis comments for FLAC, atoms for M4A
+pub async fn tag_downloaded_file(
+    db_pool: &SqlitePool,
+    http_client: &HttpClient,
+    subscription_id: i64,
+    episode_id: i64,
+    path: &Path,
+) -> AppResult<()> {
+    let subscription = get_subscription(db_pool, subscription_id).await?;
+    if !subscription.write_tags {
+        return Ok(());
+    }
+
+    let episode = get_episode(db_pool, episode_id).await?;
+    let tags = EpisodeTags::build(&subscription, &episode)?;
+
+    // Missing cover art doesn't prevent the other tags
+    let cover = match episode.image_url.as_deref() {
+        Some(url) => match fetch_cover(http_client, url).await {
+            Ok(cover) => Some(cover),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to fetch cover art for episode {}: {}",
+                    episode_id,
+                    e
+                );
+                None
+            }
+        },
+        None => None,
+    };
+
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || write_tags(&path, &tags, cover))
+        .await
+        .map_err(|e| AppError::Other(format!("Tagging task failed: {}", e)))??;
+
+    tracing::info!("Tags written for episode {}", episode_id);
+
+    Ok(())
+}
+
+async fn fetch_cover(http_client: &HttpClient, url: &str) -> AppResult<Picture> {
+    let mut response = http_client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(AppError::HttpStatus(response.status()));
+    }
+
+    let too_large = |size| AppError::InvalidInput(format!("Cover art too large ({} bytes)", size));
+
+    // Reject declared oversize covers before reading any of the body
+    if let Some(length) = response.content_length() {
+        if length > MAX_COVER_BYTES as u64 {
+            return Err(too_large(length as usize));
+        }
+    }
+
+    // The length can be missing or wrong, stop reading as soon as the limit is passed
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if data.len() + chunk.len() > MAX_COVER_BYTES {
+            return Err(too_large(data.len() + chunk.len()));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let mut cover = Picture::from_reader(&mut data.as_slice())?;
+    cover.set_pic_type(PictureType::CoverFront);
+
+    Ok(cover)
+}
+
+/// Write tags into the file's primary tag, creating it if needed
+pub fn write_tags(path: &Path, tags: &EpisodeTags, cover: Option<Picture>) -> AppResult<()> {
+    let mut tagged_file = lofty::read_from_path(path)?;
+
+    if tagged_file.primary_tag().is_none() {
+        tagged_file.insert_tag(Tag::new(tagged_file.primary_tag_type()));
+    }
+    let tag = tagged_file
+        .primary_tag_mut()
+        .ok_or_else(|| AppError::Other("File format doesn't support tags".to_string()))?;
+
+    if let Some(title) = &tags.title {
+        tag.set_title(title.clone());
+    }
+    if let Some(artist) = &tags.artist {
+        tag.set_artist(artist.clone());
+    }
+    if let Some(album) = &tags.album {
+        tag.set_album(album.clone());
+    }
+    if let Some(comment) = &tags.comment {
+        tag.set_comment(comment.clone());
+    }
+    if let Some(date) = tags.date {
+        tag.insert_text(ItemKey::RecordingDate, date.format("%Y-%m-%d").to_string());
+    }
+    if let Some(cover) = cover {
+        tag.remove_picture_type(PictureType::CoverFront);
+        tag.push_picture(cover);
+    }
+
+    tagged_file.save_to_path(path, WriteOptions::default())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+    use chrono::TimeZone;
+
+    fn subscription() -> Subscription {
+        Subscription {
+            output_directory: "/tmp".to_string(),
+            filename_format: "{show} - {episode}".to_string(),
+            write_tags: true,
+            tag_title_format: "{date} {episode}".to_string(),
+            tag_album_format: "{show} {year}".to_string(),
+            tag_comment_format: String::new(),
+            ..test_support::subscription()
+        }
+    }
+
+    fn episode() -> Episode {
+        Episode {
+            title: "Interview".to_string(),
+            description: Some("<p>Guests &amp; <b>news</b></p>".to_string()),
+            pub_date: Some(Utc.with_ymd_and_hms(2024, 3, 5, 8, 0, 0).unwrap()),
+            audio_url: "https://example.com/interview.flac".to_string(),
+            audio_type: Some("audio/flac".to_string()),
+            download_status: "completed".to_string(),
+            download_progress: 100,
+            ..test_support::episode(2)
+        }
+    }
+
+    #[test]
+    fn test_build_tags() {
+        let subscription = subscription();
+        let mut episode = episode();
+
+        let tags = EpisodeTags::build(&subscription, &episode).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("2024-03-05 Interview"));
+        assert_eq!(tags.artist.as_deref(), Some("Morning Show"));
+        assert_eq!(tags.album.as_deref(), Some("Morning Show 2024"));
+        assert_eq!(tags.comment, None);
+
+        episode.program_name = Some("Breakfast Club".to_string());
+        let tags = EpisodeTags::build(&subscription, &episode).unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Breakfast Club"));
+    }
+
+    #[test]
+    fn test_write_flac_tags() {
+        // fLaC marker, a STREAMINFO block (4096 samples per block, 44.1kHz stereo 16-bit)
+        // and a last PADDING block, without audio frames
+        let mut flac = b"fLaC".to_vec();
+        flac.extend_from_slice(&[0x00, 0x00, 0x00, 0x22]);
+        flac.extend_from_slice(&[0x10, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0, 0]);
+        flac.extend_from_slice(&[0x0A, 0xC4, 0x42, 0xF0, 0, 0, 0, 0]);
+        flac.extend_from_slice(&[0; 16]);
+        flac.extend_from_slice(&[0x81, 0x00, 0x00, 0x10]);
+        flac.extend_from_slice(&[0; 16]);
+
+        let path = std::env::temp_dir().join(format!("podcastsync-{}.flac", uuid::Uuid::new_v4()));
+        std::fs::write(&path, flac).unwrap();
+
+        let tags = EpisodeTags::build(&subscription(), &episode()).unwrap();
+        write_tags(&path, &tags, None).unwrap();
+
+        let tagged_file = lofty::read_from_path(&path).unwrap();
+        let tag = tagged_file.primary_tag().unwrap();
+        assert_eq!(tag.title().as_deref(), Some("2024-03-05 Interview"));
+        assert_eq!(tag.album().as_deref(), Some("Morning Show 2024"));
+        assert_eq!(tag.get_string(&ItemKey::RecordingDate), Some("2024-03-05"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,9 +1,11 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod backup;
 mod commands;
 mod db;
 mod download;
+mod opml;
 mod rss;
 mod scheduler;
 mod state;
@@ -18,8 +20,9 @@ use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 use commands::*;
 use db::init_database;
 use download::DownloadManager;
-use scheduler::{start_feed_checker, start_update_checker};
-use state::AppState;
+use scheduler::{start_backup_scheduler, start_feed_checker, start_update_checker};
+use state::{AppState, RestoreLock};
+use utils::HttpClient;
 
 fn main() {
     // Initialize logging
@@ -70,33 +73,61 @@ fn main() {
                     .await
                     .unwrap_or(3) as usize;
 
-                // Create app state
-                let app_state = AppState::new(db_pool.clone(), download_tx.clone());
-
-                // Store app state
-                app_handle_clone.manage(app_state);
+                // Shared HTTP client for feeds, downloads and update checks
+                let http_client = HttpClient::load(&db_pool).await;
 
-                // Start download manager
+                // Create download manager
                 let download_manager = DownloadManager::new(
                     max_concurrent,
                     download_rx,
                     db_pool.clone(),
+                    http_client.clone(),
                     app_handle_clone.clone(),
                 );
 
+                // Create app state
+                let restore_lock = RestoreLock::default();
+                let app_state = AppState::new(
+                    db_pool.clone(),
+                    download_tx.clone(),
+                    download_manager.control(),
+                    http_client.clone(),
+                    restore_lock.clone(),
+                );
+
+                // Store app state
+                app_handle_clone.manage(app_state);
+
+                // Start download manager
                 tauri::async_runtime::spawn(async move {
                     download_manager.run().await;
                 });
 
+                // Start automatic backups
+                let db_pool_for_backups = db_pool.clone();
+                let restore_lock_for_backups = restore_lock.clone();
+                tauri::async_runtime::spawn(async move {
+                    start_backup_scheduler(db_pool_for_backups, app_dir, restore_lock_for_backups)
+                        .await;
+                });
+
                 // Start feed checker
                 let app_handle_for_checker = app_handle_clone.clone();
+                let http_client_for_checker = http_client.clone();
                 tauri::async_runtime::spawn(async move {
-                    start_feed_checker(db_pool, download_tx, app_handle_clone).await;
+                    start_feed_checker(
+                        db_pool,
+                        http_client,
+                        download_tx,
+                        restore_lock,
+                        app_handle_clone,
+                    )
+                    .await;
                 });
 
                 // Start update checker (checks on startup and every 6 hours)
                 tauri::async_runtime::spawn(async move {
-                    start_update_checker(app_handle_for_checker).await;
+                    start_update_checker(app_handle_for_checker, http_client_for_checker).await;
                 });
 
                 tracing::info!("Application initialized successfully");
@@ -107,24 +138,34 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             // Subscription commands
             create_subscription,
+            get_subscription_defaults,
             list_subscriptions,
             get_subscription,
             update_subscription,
             delete_subscription,
             toggle_subscription,
+            get_episode_filter,
+            set_episode_filter,
+            preview_retention_policy,
+            apply_retention_policy,
+            list_retention_history,
             check_subscription_now,
             fetch_rss_title,
+            preview_filename_format,
+            rekey_subscription,
             // Episode commands
             list_episodes,
             list_episodes_by_subscription,
             list_episodes_by_status,
             get_episode,
             retry_episode,
+            backfill_subscription,
             process_pending_episodes,
             verify_episode_file,
             verify_subscription_files,
             get_episode_available_media,
             delete_episode,
+            set_episode_protected,
             get_episode_stats,
             // Settings commands
             get_all_settings,
@@ -132,11 +173,26 @@ fn main() {
             set_setting,
             // Download commands
             get_queue_size,
+            list_download_queue,
             clear_queue,
+            cancel_download,
+            pause_download,
+            resume_download,
+            bump_download,
+            pause_all_downloads,
+            resume_all_downloads,
+            get_downloads_paused,
             select_directory,
             open_in_file_manager,
             // Updater commands
             check_updates,
+            // OPML commands
+            import_opml,
+            export_opml,
+            // Backup commands
+            create_backup,
+            list_backups,
+            restore_backup,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,389 @@
+use chrono::Utc;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::{NsReader, Writer};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+
+use crate::db::models::{CreateSubscriptionData, Subscription};
+use crate::db::settings::{get_setting, get_subscription_defaults};
+use crate::db::subscriptions::{create_subscription, list_subscriptions};
+use crate::utils::template::Template;
+use crate::utils::{AppError, AppResult};
+
+/// Namespace of the attributes carrying PodcastSync-specific fields
+const NAMESPACE: &str = "https://github.com/Synapsr/PodcastSync/opml";
+const PREFIX: &str = "podcastsync";
+
+/// A feed listed in an OPML file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpmlOutline {
+    pub title: String,
+    pub xml_url: String,
+    pub preferred_quality: Option<String>,
+    pub filename_format: Option<String>,
+    pub max_episodes: Option<i32>,
+    pub radio_slug: Option<String>,
+    pub automation_name: Option<String>,
+}
+
+impl From<&Subscription> for OpmlOutline {
+    fn from(subscription: &Subscription) -> Self {
+        Self {
+            title: subscription.name.clone(),
+            xml_url: subscription.rss_url.clone(),
+            preferred_quality: Some(subscription.preferred_quality.clone()),
+            filename_format: Some(subscription.filename_format.clone()),
+            max_episodes: subscription.max_episodes,
+            radio_slug: subscription.radio_slug.clone(),
+            automation_name: subscription.automation_name.clone(),
+        }
+    }
+}
+
+/// Result of an OPML import
+#[derive(Debug, Clone, Serialize)]
+pub struct OpmlImportReport {
+    pub imported: Vec<Subscription>,
+    /// Feed URLs already subscribed to, or listed twice in the file
+    pub duplicates: Vec<String>,
+    /// Feeds that couldn't be created, with the reason
+    pub failed: Vec<String>,
+}
+
+/// Read every feed outline of an OPML document, categories are flattened
+pub fn parse_opml(xml: &str) -> AppResult<Vec<OpmlOutline>> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut outlines = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) | Event::Empty(element)
+                if element.local_name().as_ref() == b"outline" =>
+            {
+                if let Some(outline) = parse_outline(&element, &reader)? {
+                    outlines.push(outline);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(outlines)
+}
+
+/// Outline with a feed URL, None for categories and links
+/// Extension attributes are recognised by their namespace, whatever prefix it is bound to
+fn parse_outline(element: &BytesStart, reader: &NsReader<&[u8]>) -> AppResult<Option<OpmlOutline>> {
+    let mut outline = OpmlOutline::default();
+    let mut text = None;
+
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        let value = attribute
+            .decode_and_unescape_value(reader.decoder())?
+            .trim()
+            .to_string();
+        if value.is_empty() {
+            continue;
+        }
+
+        let (namespace, local_name) = reader.resolve_attribute(attribute.key);
+        let name = String::from_utf8_lossy(local_name.as_ref()).to_string();
+        let extension = match &namespace {
+            ResolveResult::Bound(Namespace(uri)) => *uri == NAMESPACE.as_bytes(),
+            // Files written by hand sometimes use the prefix without declaring it
+            ResolveResult::Unknown(prefix) => prefix.as_slice() == PREFIX.as_bytes(),
+            ResolveResult::Unbound => false,
+        };
+
+        if extension {
+            match name.as_str() {
+                "preferred_quality" => outline.preferred_quality = Some(value),
+                "filename_format" => outline.filename_format = Some(value),
+                "max_episodes" => outline.max_episodes = value.parse().ok(),
+                "radio_slug" => outline.radio_slug = Some(value),
+                "automation_name" => outline.automation_name = Some(value),
+                _ => {}
+            }
+        } else if namespace == ResolveResult::Unbound {
+            // Some exporters don't respect the attribute case
+            match name.to_ascii_lowercase().as_str() {
+                "xmlurl" => outline.xml_url = value,
+                "title" => outline.title = value,
+                "text" => text = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    if outline.xml_url.is_empty() {
+        return Ok(None);
+    }
+    if outline.title.is_empty() {
+        outline.title = text.unwrap_or_else(|| outline.xml_url.clone());
+    }
+
+    Ok(Some(outline))
+}
+
+/// OPML 2.0 document listing the given feeds
+pub fn write_opml(outlines: &[OpmlOutline]) -> AppResult<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    let namespace = format!("xmlns:{}", PREFIX);
+
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    writer.write_event(Event::Start(
+        BytesStart::new("opml")
+            .with_attributes([("version", "2.0"), (namespace.as_str(), NAMESPACE)]),
+    ))?;
+
+    writer.write_event(Event::Start(BytesStart::new("head")))?;
+    writer.write_event(Event::Start(BytesStart::new("title")))?;
+    writer.write_event(Event::Text(BytesText::new("PodcastSync subscriptions")))?;
+    writer.write_event(Event::End(BytesEnd::new("title")))?;
+    writer.write_event(Event::Start(BytesStart::new("dateCreated")))?;
+    writer.write_event(Event::Text(BytesText::new(&Utc::now().to_rfc2822())))?;
+    writer.write_event(Event::End(BytesEnd::new("dateCreated")))?;
+    writer.write_event(Event::End(BytesEnd::new("head")))?;
+
+    writer.write_event(Event::Start(BytesStart::new("body")))?;
+    for outline in outlines {
+        let mut element = BytesStart::new("outline");
+        element.push_attribute(("type", "rss"));
+        element.push_attribute(("text", outline.title.as_str()));
+        element.push_attribute(("title", outline.title.as_str()));
+        element.push_attribute(("xmlUrl", outline.xml_url.as_str()));
+
+        let max_episodes = outline.max_episodes.map(|max| max.to_string());
+        let fields = [
+            ("preferred_quality", outline.preferred_quality.as_deref()),
+            ("filename_format", outline.filename_format.as_deref()),
+            ("max_episodes", max_episodes.as_deref()),
+            ("radio_slug", outline.radio_slug.as_deref()),
+            ("automation_name", outline.automation_name.as_deref()),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                let key = format!("{}:{}", PREFIX, name);
+                element.push_attribute((key.as_str(), value));
+            }
+        }
+
+        writer.write_event(Event::Empty(element))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("body")))?;
+    writer.write_event(Event::End(BytesEnd::new("opml")))?;
+
+    String::from_utf8(writer.into_inner())
+        .map_err(|e| AppError::Other(format!("Invalid OPML output: {}", e)))
+}
+
+/// Export every subscription as OPML
+pub async fn export_subscriptions(pool: &SqlitePool) -> AppResult<String> {
+    let subscriptions = list_subscriptions(pool).await?;
+    let outlines: Vec<OpmlOutline> = subscriptions.iter().map(OpmlOutline::from).collect();
+
+    write_opml(&outlines)
+}
+
+/// Create a subscription for every new feed of an OPML document
+/// Fields missing from the file come from the default settings
+pub async fn import_subscriptions(
+    pool: &SqlitePool,
+    xml: &str,
+    output_directory: Option<String>,
+) -> AppResult<OpmlImportReport> {
+    let outlines = parse_opml(xml)?;
+
+    let output_directory = match output_directory {
+        Some(directory) => Some(directory),
+        None => get_setting(pool, "default_output_directory").await?,
+    }
+    .filter(|directory| !directory.trim().is_empty())
+    .ok_or_else(|| {
+        AppError::InvalidInput("Choose an output directory for imported subscriptions".to_string())
+    })?;
+    let defaults = get_subscription_defaults(pool).await?;
+
+    let mut known: HashSet<String> = list_subscriptions(pool)
+        .await?
+        .into_iter()
+        .map(|subscription| subscription.rss_url)
+        .collect();
+
+    let mut report = OpmlImportReport {
+        imported: Vec::new(),
+        duplicates: Vec::new(),
+        failed: Vec::new(),
+    };
+
+    for outline in outlines {
+        if !known.insert(outline.xml_url.clone()) {
+            report.duplicates.push(outline.xml_url);
+            continue;
+        }
+
+        let data = CreateSubscriptionData {
+            name: outline.title,
+            rss_url: outline.xml_url.clone(),
+            radio_slug: outline.radio_slug,
+            automation_name: outline.automation_name,
+            check_frequency_minutes: defaults.check_frequency_minutes,
+            output_directory: output_directory.clone(),
+            max_items_to_check: defaults.max_items_to_check,
+            preferred_quality: outline
+                .preferred_quality
+                .unwrap_or_else(|| defaults.preferred_quality.clone()),
+            max_episodes: outline.max_episodes,
+            max_storage_bytes: None,
+            retention_days: None,
+            retention_keep_completed: None,
+            archive_directory: None,
+            filename_format: outline
+                .filename_format
+                .filter(|format| Template::parse_path(format).is_ok())
+                .unwrap_or_else(|| defaults.filename_format.clone()),
+            retry_max_attempts: None,
+            retry_base_delay_seconds: None,
+            ignore_bandwidth_schedule: false,
+            download_priority: None,
+            identity_strategy: None,
+            write_tags: None,
+            tag_title_format: None,
+            tag_artist_format: None,
+            tag_album_format: None,
+            tag_comment_format: None,
+            on_completed_command: None,
+            on_failed_command: None,
+            initial_download: None,
+            initial_download_count: None,
+            initial_download_since: None,
+        };
+
+        match create_subscription(pool, data).await {
+            Ok(subscription) => report.imported.push(subscription),
+            Err(e) => report.failed.push(format!("{}: {}", outline.xml_url, e)),
+        }
+    }
+
+    tracing::info!(
+        "OPML import: {} imported, {} duplicates, {} failed",
+        report.imported.len(),
+        report.duplicates.len(),
+        report.failed.len()
+    );
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::settings::set_setting;
+    use crate::db::test_support::TestDb;
+
+    #[test]
+    fn test_parse_foreign_opml() {
+        let xml = r#"<?xml version="1.0"?>
+            <opml version="1.0">
+              <head><title>Podcasts</title></head>
+              <body>
+                <outline text="News">
+                  <outline type="rss" text="Daily &amp; More" xmlUrl="https://example.com/daily.xml"/>
+                  <outline type="link" text="Website" url="https://example.com"/>
+                </outline>
+                <outline text="Untitled" xmlurl="https://example.com/other.xml"></outline>
+              </body>
+            </opml>"#;
+
+        let outlines = parse_opml(xml).unwrap();
+        assert_eq!(
+            outlines,
+            vec![
+                OpmlOutline {
+                    title: "Daily & More".to_string(),
+                    xml_url: "https://example.com/daily.xml".to_string(),
+                    ..OpmlOutline::default()
+                },
+                OpmlOutline {
+                    title: "Untitled".to_string(),
+                    xml_url: "https://example.com/other.xml".to_string(),
+                    ..OpmlOutline::default()
+                },
+            ]
+        );
+
+        assert!(parse_opml("<opml><body><outline").is_err());
+    }
+
+    #[test]
+    fn test_opml_round_trip() {
+        let outlines = vec![
+            OpmlOutline {
+                title: "Morning <Show>".to_string(),
+                xml_url: "https://radio.example.com/feed?show=morning&format=rss".to_string(),
+                preferred_quality: Some("flac".to_string()),
+                filename_format: Some("{date}_{episode}".to_string()),
+                max_episodes: Some(10),
+                radio_slug: Some("radio".to_string()),
+                automation_name: Some("Morning".to_string()),
+            },
+            OpmlOutline {
+                title: "Evening".to_string(),
+                xml_url: "https://radio.example.com/evening.xml".to_string(),
+                ..OpmlOutline::default()
+            },
+        ];
+
+        let xml = write_opml(&outlines).unwrap();
+        assert!(xml.contains(r#"<opml version="2.0" xmlns:podcastsync="#));
+        assert_eq!(parse_opml(&xml).unwrap(), outlines);
+    }
+
+    #[test]
+    fn test_extension_attributes_by_namespace() {
+        let xml = format!(
+            r#"<opml version="2.0" xmlns:ps="{}" xmlns:other="https://example.com/other">
+              <body>
+                <outline text="Bound" xmlUrl="https://example.com/a.xml" ps:max_episodes="5"
+                  other:preferred_quality="flac"/>
+                <outline text="Undeclared" xmlUrl="https://example.com/b.xml"
+                  podcastsync:preferred_quality="mp3"/>
+              </body>
+            </opml>"#,
+            NAMESPACE
+        );
+
+        let outlines = parse_opml(&xml).unwrap();
+        assert_eq!(outlines[0].max_episodes, Some(5));
+        assert_eq!(outlines[0].preferred_quality, None);
+        assert_eq!(outlines[1].preferred_quality.as_deref(), Some("mp3"));
+    }
+
+    #[tokio::test]
+    async fn test_import_uses_subscription_defaults() {
+        let db = TestDb::new().await;
+        let pool = &db.pool;
+        set_setting(pool, "default_check_frequency_minutes", "60")
+            .await
+            .unwrap();
+        set_setting(pool, "default_max_items_to_check", "20")
+            .await
+            .unwrap();
+
+        let xml = r#"<opml><body><outline text="Daily" xmlUrl="https://example.com/daily.xml"/></body></opml>"#;
+        let report = import_subscriptions(pool, xml, Some("/podcasts".to_string()))
+            .await
+            .unwrap();
+        let imported = &report.imported[0];
+        assert_eq!(imported.check_frequency_minutes, 60);
+        assert_eq!(imported.max_items_to_check, 20);
+        assert_eq!(imported.preferred_quality, "enclosure");
+
+        db.close().await;
+    }
+}
@@ -0,0 +1,335 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeZone, Timelike, Utc};
+
+/// Month names and abbreviations in the languages seen in real feeds
+/// (English, French, German, Spanish, Italian, Portuguese, Dutch)
+const MONTHS: [&[&str]; 12] = [
+    &[
+        "january", "janvier", "januar", "enero", "gennaio", "janeiro", "januari",
+    ],
+    &[
+        "february",
+        "février",
+        "fevrier",
+        "februar",
+        "febrero",
+        "febbraio",
+        "fevereiro",
+        "februari",
+    ],
+    &[
+        "march", "mars", "märz", "maerz", "marzo", "março", "marco", "maart",
+    ],
+    &["april", "avril", "abril", "aprile"],
+    &["may", "mai", "mayo", "maggio", "maio", "mei"],
+    &["june", "juin", "juni", "junio", "giugno", "junho"],
+    &["july", "juillet", "juli", "julio", "luglio", "julho"],
+    &["august", "août", "aout", "agosto", "augustus"],
+    &[
+        "september",
+        "septembre",
+        "septiembre",
+        "settembre",
+        "setembro",
+    ],
+    &[
+        "october", "octobre", "oktober", "octubre", "ottobre", "outubro",
+    ],
+    &["november", "novembre", "noviembre", "novembro"],
+    &[
+        "december",
+        "décembre",
+        "decembre",
+        "dezember",
+        "diciembre",
+        "dicembre",
+        "dezembro",
+    ],
+];
+
+/// Time zone abbreviations still found in feeds, with their offset in hours
+const ZONES: [(&str, i32); 14] = [
+    ("est", -5),
+    ("edt", -4),
+    ("cst", -6),
+    ("cdt", -5),
+    ("mst", -7),
+    ("mdt", -6),
+    ("pst", -8),
+    ("pdt", -7),
+    ("bst", 1),
+    ("cet", 1),
+    ("cest", 2),
+    ("mez", 1),
+    ("mesz", 2),
+    ("eet", 2),
+];
+
+/// Parse a feed date leniently
+/// Strict RFC 2822 and RFC 3339 are tried first, then a token based parser that
+/// accepts missing or non-English weekdays, non-English month names, two-digit
+/// years, ISO dates without offset and offsets like `GMT+2` or `EST`
+/// Dates without an offset are assumed to be UTC
+pub fn parse_date(input: &str) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc2822(input) {
+        return Some(date.with_timezone(&Utc));
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(input) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    parse_lenient(input)
+}
+
+fn parse_lenient(input: &str) -> Option<DateTime<Utc>> {
+    let mut date: Option<NaiveDate> = None;
+    let mut day: Option<u32> = None;
+    let mut month: Option<u32> = None;
+    let mut year: Option<i32> = None;
+    let mut time: Option<NaiveTime> = None;
+    let mut offset: Option<FixedOffset> = None;
+    let mut pm: Option<bool> = None;
+
+    let tokens = input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|token| token.trim_matches(|c| c == '(' || c == ')'))
+        .filter(|token| !token.is_empty());
+
+    for token in tokens {
+        let lower = token.to_lowercase();
+
+        if let Some((iso_date, rest)) = parse_iso_date(token) {
+            date = Some(iso_date);
+            if let Some(rest) = rest {
+                let (time_part, zone) = split_zone(rest);
+                time = parse_time(time_part);
+                if let Some(zone) = zone {
+                    offset = parse_zone(zone);
+                }
+            }
+        } else if token.contains(':') && token.starts_with(|c: char| c.is_ascii_digit()) {
+            let (time_part, zone) = split_zone(token);
+            time = parse_time(time_part);
+            if let Some(zone) = zone {
+                offset = parse_zone(zone);
+            }
+        } else if let Some(zone) = parse_zone(token) {
+            offset = Some(zone);
+        } else if let Some(number) = parse_number(token) {
+            if token.len() == 4 || day.is_some() {
+                year = Some(number as i32);
+            } else {
+                day = Some(number);
+            }
+        } else if lower == "am" || lower == "pm" {
+            pm = Some(lower == "pm");
+        } else if let Some(m) = parse_month(&lower) {
+            // A weekday abbreviation can look like a month ("Mar" is Tuesday
+            // in Spanish), the month always comes after it
+            month = Some(m);
+        }
+    }
+
+    let date = match date {
+        Some(date) => date,
+        None => NaiveDate::from_ymd_opt(expand_year(year?), month?, day?)?,
+    };
+
+    let mut time = time.unwrap_or(NaiveTime::MIN);
+    if let Some(pm) = pm {
+        let hour = time.hour() % 12 + if pm { 12 } else { 0 };
+        time = time.with_hour(hour)?;
+    }
+
+    let offset = offset.unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    let local = offset.from_local_datetime(&date.and_time(time)).single()?;
+
+    Some(local.with_timezone(&Utc))
+}
+
+/// ISO date ("2024-01-02", "2024/01/02"), optionally followed by "T" and a time
+fn parse_iso_date(token: &str) -> Option<(NaiveDate, Option<&str>)> {
+    let bytes = token.as_bytes();
+    if bytes.len() < 8
+        || !bytes[..4].iter().all(u8::is_ascii_digit)
+        || !matches!(bytes[4], b'-' | b'/')
+    {
+        return None;
+    }
+
+    let (date_part, rest) = match token.find(['T', 't']) {
+        Some(index) => (&token[..index], Some(&token[index + 1..])),
+        None => (token, None),
+    };
+
+    let date = NaiveDate::parse_from_str(&date_part.replace('/', "-"), "%Y-%m-%d").ok()?;
+    Some((date, rest))
+}
+
+/// Split "07:00:00+0200" or "07:00Z" into the time and its offset
+fn split_zone(token: &str) -> (&str, Option<&str>) {
+    match token.find(['+', '-', 'Z', 'z']) {
+        Some(index) => (&token[..index], Some(&token[index..])),
+        None => (token, None),
+    }
+}
+
+/// "HH:MM" or "HH:MM:SS", fractional seconds are dropped
+fn parse_time(token: &str) -> Option<NaiveTime> {
+    let mut parts = token.split(':');
+    let hour = parts.next()?.parse().ok()?;
+    let minute = parts.next()?.parse().ok()?;
+    let second = match parts.next() {
+        Some(second) => second.split('.').next()?.parse().ok()?,
+        None => 0,
+    };
+
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+/// Offsets: "Z", "GMT", "UTC", "+0200", "-05:00", "+2", "GMT+2", "UTC-03:30", "CEST"
+fn parse_zone(token: &str) -> Option<FixedOffset> {
+    let lower = token.to_lowercase();
+
+    let numeric = ["gmt", "utc", "ut"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .unwrap_or(&lower);
+
+    if numeric.is_empty() || numeric == "z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    if let Some((_, hours)) = ZONES.iter().find(|(name, _)| *name == numeric) {
+        return FixedOffset::east_opt(hours * 3600);
+    }
+
+    let (sign, digits) = match numeric.as_bytes().first()? {
+        b'+' => (1, &numeric[1..]),
+        b'-' => (-1, &numeric[1..]),
+        _ => return None,
+    };
+
+    let digits = digits.replace(':', "");
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes): (i32, i32) = match digits.len() {
+        1 | 2 => (digits.parse().ok()?, 0),
+        3 | 4 => {
+            let split = digits.len() - 2;
+            (digits[..split].parse().ok()?, digits[split..].parse().ok()?)
+        }
+        _ => return None,
+    };
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Day or year number, ordinal suffixes ("2nd", "1er") are accepted
+fn parse_number(token: &str) -> Option<u32> {
+    let digits = token.trim_end_matches(|c: char| c.is_alphabetic() || c == '.');
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || digits.len() > 4 {
+        return None;
+    }
+
+    // Only plain numbers or short ordinal suffixes, not words like "2024abc"
+    if token.len() - digits.len() > 2 {
+        return None;
+    }
+
+    digits.parse().ok()
+}
+
+/// Month number from a full name or an abbreviation of at least 3 letters
+fn parse_month(token: &str) -> Option<u32> {
+    let token = token.trim_end_matches('.');
+    if token.chars().count() < 3 {
+        return None;
+    }
+
+    MONTHS
+        .iter()
+        .position(|names| names.iter().any(|name| name.starts_with(token)))
+        .map(|index| index as u32 + 1)
+}
+
+/// Two-digit years: 00-69 are 2000-2069, 70-99 are 1970-1999
+fn expand_year(year: i32) -> i32 {
+    match year {
+        0..=69 => 2000 + year,
+        70..=99 => 1900 + year,
+        _ => year,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> Option<String> {
+        parse_date(s).map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+    }
+
+    #[test]
+    fn test_parse_date_corpus() {
+        let corpus = [
+            // RFC 2822
+            ("Tue, 02 Jan 2024 07:00:00 +0000", "2024-01-02 07:00:00"),
+            ("Tue, 02 Jan 2024 09:00:00 +0200", "2024-01-02 07:00:00"),
+            ("Tue, 02 Jan 2024 07:00:00 GMT", "2024-01-02 07:00:00"),
+            // RFC 3339 / ISO 8601
+            ("2024-01-02T07:00:00Z", "2024-01-02 07:00:00"),
+            ("2024-01-02T09:00:00+02:00", "2024-01-02 07:00:00"),
+            ("2024-01-02T07:00:00.123Z", "2024-01-02 07:00:00"),
+            ("2024-01-02T07:00:00", "2024-01-02 07:00:00"),
+            ("2024-01-02 07:00:00", "2024-01-02 07:00:00"),
+            ("2024-01-02", "2024-01-02 00:00:00"),
+            ("2024/01/02 07:00", "2024-01-02 07:00:00"),
+            // Missing or wrong weekday
+            ("02 Jan 2024 07:00:00 +0000", "2024-01-02 07:00:00"),
+            ("Mon, 02 Jan 2024 07:00:00 +0000", "2024-01-02 07:00:00"),
+            ("Tues, 2 Jan 2024 07:00:00 +0000", "2024-01-02 07:00:00"),
+            // Non-English day and month names
+            ("Mar, 02 Ene 2024 07:00:00 +0000", "2024-01-02 07:00:00"),
+            (
+                "mardi, 2 janvier 2024 08:00:00 +0100",
+                "2024-01-02 07:00:00",
+            ),
+            ("Di, 02 Jan 2024 07:00:00 GMT", "2024-01-02 07:00:00"),
+            ("Mi, 14 Feb 2024 07:00:00 MEZ", "2024-02-14 06:00:00"),
+            ("15 août 2024 12:00", "2024-08-15 12:00:00"),
+            ("1er déc. 2023 10:00", "2023-12-01 10:00:00"),
+            ("Sa, 02 Mär 2024 07:00:00 +0000", "2024-03-02 07:00:00"),
+            // Two-digit years
+            ("Tue, 02 Jan 24 07:00:00 +0000", "2024-01-02 07:00:00"),
+            ("Fri, 31 Dec 99 23:00:00 GMT", "1999-12-31 23:00:00"),
+            // GMT+2 style and named offsets
+            ("Tue, 02 Jan 2024 09:00:00 GMT+2", "2024-01-02 07:00:00"),
+            ("Tue, 02 Jan 2024 09:00:00 UTC+02:00", "2024-01-02 07:00:00"),
+            ("Tue, 02 Jan 2024 02:00:00 EST", "2024-01-02 07:00:00"),
+            ("Tue, 02 Jan 2024 09:00:00 CEST", "2024-01-02 07:00:00"),
+            ("Tue, 02 Jan 2024 08:00:00 (CET)", "2024-01-02 07:00:00"),
+            ("Tue, 02 Jan 2024 07:00 +0000", "2024-01-02 07:00:00"),
+            // US style
+            ("January 2, 2024 7:00 AM", "2024-01-02 07:00:00"),
+            ("Jan 2nd 2024 7:30 PM", "2024-01-02 19:30:00"),
+        ];
+
+        for (input, expected) in corpus {
+            assert_eq!(utc(input).as_deref(), Some(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_date_invalid() {
+        assert_eq!(utc(""), None);
+        assert_eq!(utc("not a date"), None);
+        assert_eq!(utc("Tue, 32 Jan 2024 07:00:00 +0000"), None);
+        assert_eq!(utc("2024-13-02"), None);
+    }
+}
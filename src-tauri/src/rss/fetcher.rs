@@ -1,12 +1,33 @@
-use crate::utils::AppResult;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+
+use crate::utils::{AppResult, HttpClient};
+
+/// Feed body with the validators needed for the next conditional request
+pub struct FetchedFeed {
+    pub body: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Outcome of a conditional feed request
+pub enum FeedResponse {
+    Modified(FetchedFeed),
+    /// 304, the previously fetched body is still current
+    NotModified,
+}
 
 /// Fetch RSS feed content from URL
-pub async fn fetch_rss(url: &str) -> AppResult<String> {
-    fetch_rss_with_limit(url, None).await
+pub async fn fetch_rss(http: &HttpClient, url: &str) -> AppResult<String> {
+    fetch_rss_with_limit(http, url, None).await
 }
 
 /// Fetch RSS feed content from URL with optional limit parameter
-pub async fn fetch_rss_with_limit(url: &str, limit: Option<i32>) -> AppResult<String> {
+pub async fn fetch_rss_with_limit(
+    http: &HttpClient,
+    url: &str,
+    limit: Option<i32>,
+) -> AppResult<String> {
     let final_url = if let Some(limit_value) = limit {
         if url.contains('?') {
             format!("{}&limit={}", url, limit_value)
@@ -19,7 +40,7 @@ pub async fn fetch_rss_with_limit(url: &str, limit: Option<i32>) -> AppResult<St
 
     tracing::info!("Fetching RSS feed from: {}", final_url);
 
-    let response = reqwest::get(&final_url).await?;
+    let response = http.get(&final_url).send().await?;
 
     let status = response.status();
     if !status.is_success() {
@@ -35,3 +56,56 @@ pub async fn fetch_rss_with_limit(url: &str, limit: Option<i32>) -> AppResult<St
 
     Ok(content)
 }
+
+/// Fetch RSS feed content, sending validators from a previous fetch
+/// so unchanged feeds cost a 304 instead of the whole body
+pub async fn fetch_rss_conditional(
+    http: &HttpClient,
+    url: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> AppResult<FeedResponse> {
+    tracing::info!("Fetching RSS feed from: {}", url);
+
+    let mut request = http.get(url);
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = request.send().await?;
+
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        tracing::debug!("RSS feed not modified: {}", url);
+        return Ok(FeedResponse::NotModified);
+    }
+    if !status.is_success() {
+        return Err(crate::utils::AppError::Other(format!(
+            "HTTP error {}: Failed to fetch RSS feed",
+            status
+        )));
+    }
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+
+    let body = response.text().await?;
+
+    tracing::debug!("Fetched {} bytes from RSS feed", body.len());
+
+    Ok(FeedResponse::Modified(FetchedFeed {
+        body,
+        etag,
+        last_modified,
+    }))
+}
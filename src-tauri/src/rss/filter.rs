@@ -0,0 +1,224 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::db::models::EpisodeFilter;
+use crate::rss::parser::ParsedItem;
+use crate::utils::template::plain_text;
+use crate::utils::{AppError, AppResult};
+
+/// A subscription's filter rules with their patterns compiled, ready to check feed items
+///
+/// Patterns match anywhere in the text, ignoring case; a missing description or author
+/// is matched as empty text. Duration, size and date limits only apply when the feed
+/// gives the value
+#[derive(Debug, Clone, Default)]
+pub struct ItemFilter {
+    title_include: Option<Regex>,
+    title_exclude: Option<Regex>,
+    description_include: Option<Regex>,
+    description_exclude: Option<Regex>,
+    author_include: Option<Regex>,
+    rules: EpisodeFilter,
+}
+
+impl ItemFilter {
+    pub fn new(filter: &EpisodeFilter) -> AppResult<Self> {
+        Ok(Self {
+            title_include: compile("title", &filter.title_include)?,
+            title_exclude: compile("title", &filter.title_exclude)?,
+            description_include: compile("description", &filter.description_include)?,
+            description_exclude: compile("description", &filter.description_exclude)?,
+            author_include: compile("author", &filter.author_include)?,
+            rules: filter.clone(),
+        })
+    }
+
+    /// Why an item is filtered out, None when it passes every rule
+    pub fn rejection(&self, item: &ParsedItem) -> Option<String> {
+        let description = item.description.as_deref().map(plain_text);
+        let texts = [
+            (
+                "Title",
+                Some(item.title.as_str()),
+                &self.title_include,
+                &self.title_exclude,
+            ),
+            (
+                "Description",
+                description.as_deref(),
+                &self.description_include,
+                &self.description_exclude,
+            ),
+            (
+                "Author",
+                item.author.as_deref(),
+                &self.author_include,
+                &None,
+            ),
+        ];
+        for (name, text, include, exclude) in texts {
+            let text = text.unwrap_or_default();
+            if let Some(include) = include.as_ref().filter(|re| !re.is_match(text)) {
+                return Some(format!("{} doesn't match \"{}\"", name, include.as_str()));
+            }
+            if let Some(exclude) = exclude.as_ref().filter(|re| re.is_match(text)) {
+                return Some(format!("{} matches \"{}\"", name, exclude.as_str()));
+            }
+        }
+
+        let rules = &self.rules;
+        if let Some(duration) = item.duration.filter(|duration| *duration > 0) {
+            if let Some(min) = rules.min_duration_seconds.filter(|min| duration < *min) {
+                return Some(format!("Shorter than {}s ({}s)", min, duration));
+            }
+            if let Some(max) = rules.max_duration_seconds.filter(|max| duration > *max) {
+                return Some(format!("Longer than {}s ({}s)", max, duration));
+            }
+        }
+
+        let size = item
+            .enclosure
+            .as_ref()
+            .and_then(|enclosure| enclosure.length)
+            .filter(|size| *size > 0);
+        if let Some(size) = size {
+            if let Some(min) = rules.min_size_bytes.filter(|min| size < *min) {
+                return Some(format!("Smaller than {} bytes ({} bytes)", min, size));
+            }
+            if let Some(max) = rules.max_size_bytes.filter(|max| size > *max) {
+                return Some(format!("Larger than {} bytes ({} bytes)", max, size));
+            }
+        }
+
+        if let (Some(cutoff), Some(pub_date)) = (rules.published_after, item.pub_date) {
+            if pub_date < cutoff {
+                return Some(format!("Published before {}", cutoff.format("%Y-%m-%d")));
+            }
+        }
+
+        None
+    }
+}
+
+/// Compile a filter pattern, blank patterns disable the rule
+fn compile(field: &str, pattern: &Option<String>) -> AppResult<Option<Regex>> {
+    let Some(pattern) = pattern.as_deref().map(str::trim).filter(|p| !p.is_empty()) else {
+        return Ok(None);
+    };
+
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map(Some)
+        .map_err(|e| {
+            AppError::InvalidInput(format!("Invalid {} pattern \"{}\": {}", field, pattern, e))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rss::parser::Enclosure;
+    use chrono::{TimeZone, Utc};
+
+    fn item(title: &str, author: Option<&str>, duration: Option<i32>, size: i64) -> ParsedItem {
+        ParsedItem {
+            guid: title.to_string(),
+            title: title.to_string(),
+            description: Some("<p>With <b>guests</b></p>".to_string()),
+            pub_date: Some(Utc.with_ymd_and_hms(2024, 3, 5, 8, 0, 0).unwrap()),
+            enclosure: Some(Enclosure {
+                url: "https://example.com/a.mp3".to_string(),
+                mime_type: None,
+                length: Some(size),
+            }),
+            standard_enclosure: None,
+            image_url: None,
+            author: author.map(str::to_string),
+            duration,
+            season: None,
+            episode_number: None,
+        }
+    }
+
+    #[test]
+    fn test_patterns() {
+        let filter = ItemFilter::new(&EpisodeFilter {
+            title_include: Some("^(morning|evening) news".to_string()),
+            title_exclude: Some("rerun".to_string()),
+            description_include: Some("with guests".to_string()),
+            author_include: Some("  ".to_string()),
+            ..EpisodeFilter::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            filter.rejection(&item("Morning News - Tuesday", None, None, 0)),
+            None
+        );
+        assert_eq!(
+            filter.rejection(&item("Sports", None, None, 0)).as_deref(),
+            Some("Title doesn't match \"^(morning|evening) news\"")
+        );
+        assert_eq!(
+            filter
+                .rejection(&item("Evening news (RERUN)", None, None, 0))
+                .as_deref(),
+            Some("Title matches \"rerun\"")
+        );
+
+        let filter = ItemFilter::new(&EpisodeFilter {
+            author_include: Some("newsroom".to_string()),
+            ..EpisodeFilter::default()
+        })
+        .unwrap();
+        assert_eq!(
+            filter.rejection(&item("News", Some("The Newsroom"), None, 0)),
+            None
+        );
+        assert!(filter.rejection(&item("News", None, None, 0)).is_some());
+
+        assert!(ItemFilter::new(&EpisodeFilter {
+            title_exclude: Some("(unclosed".to_string()),
+            ..EpisodeFilter::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let filter = ItemFilter::new(&EpisodeFilter {
+            min_duration_seconds: Some(600),
+            max_duration_seconds: Some(3600),
+            max_size_bytes: Some(100_000_000),
+            published_after: Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()),
+            ..EpisodeFilter::default()
+        })
+        .unwrap();
+
+        assert_eq!(filter.rejection(&item("News", None, Some(1800), 0)), None);
+        assert_eq!(
+            filter
+                .rejection(&item("News", None, Some(90), 0))
+                .as_deref(),
+            Some("Shorter than 600s (90s)")
+        );
+        assert!(filter
+            .rejection(&item("News", None, Some(7200), 0))
+            .is_some());
+        assert!(filter
+            .rejection(&item("News", None, None, 200_000_000))
+            .is_some());
+
+        // Values missing from the feed don't exclude anything
+        let mut undated = item("News", None, None, 0);
+        undated.pub_date = None;
+        assert_eq!(filter.rejection(&undated), None);
+
+        let mut old = item("News", None, None, 0);
+        old.pub_date = Some(Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap());
+        assert_eq!(
+            filter.rejection(&old).as_deref(),
+            Some("Published before 2024-01-01")
+        );
+    }
+}
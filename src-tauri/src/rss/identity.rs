@@ -0,0 +1,527 @@
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::db::models::Episode;
+use crate::rss::parser::ParsedItem;
+use crate::utils::{AppError, AppResult};
+
+/// How episodes of a subscription are recognised from one feed check to the next
+/// The resulting key is stored in `episodes.guid`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityStrategy {
+    /// The item GUID, falling back to its link, then title and date
+    Guid,
+    /// The enclosure URL as published
+    EnclosureUrl,
+    /// The enclosure host and path, without scheme, query tokens or fragment
+    NormalizedUrl,
+    /// A hash of the publication date and enclosure file name, so edited titles, new
+    /// hosts and corrected files of another size keep the key
+    /// Undated items fall back to the GUID
+    ContentHash,
+}
+
+impl IdentityStrategy {
+    pub fn parse(value: &str) -> AppResult<Self> {
+        match value {
+            "guid" => Ok(Self::Guid),
+            "enclosure_url" => Ok(Self::EnclosureUrl),
+            "normalized_url" => Ok(Self::NormalizedUrl),
+            "content_hash" => Ok(Self::ContentHash),
+            other => Err(AppError::InvalidInput(format!(
+                "Invalid identity strategy '{}', expected guid, enclosure_url, normalized_url or content_hash",
+                other
+            ))),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Guid => "guid",
+            Self::EnclosureUrl => "enclosure_url",
+            Self::NormalizedUrl => "normalized_url",
+            Self::ContentHash => "content_hash",
+        }
+    }
+
+    /// Keys of the items of one feed, in the same order
+    /// Items that would share a key, like segments published at the same time, fall
+    /// back to their GUID so none of them is taken for an already known episode
+    pub fn item_keys(&self, items: &[ParsedItem]) -> Vec<String> {
+        let keys: Vec<String> = items.iter().map(|item| self.item_key(item)).collect();
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for key in &keys {
+            *counts.entry(key.as_str()).or_default() += 1;
+        }
+
+        keys.iter()
+            .zip(items)
+            .map(|(key, item)| match counts[key.as_str()] {
+                1 => key.clone(),
+                _ => item.guid.clone(),
+            })
+            .collect()
+    }
+
+    /// Key of a single feed item, URL strategies fall back to the GUID without enclosure
+    /// and the content hash without publication date
+    fn item_key(&self, item: &ParsedItem) -> String {
+        let enclosure_url = item.enclosure.as_ref().map(|e| e.url.as_str());
+        self.key(&item.guid, item.pub_date, enclosure_url)
+    }
+
+    /// Key of a stored episode, from the details saved when it was discovered
+    /// Not available for GUIDs, which are replaced by the key
+    fn episode_key(&self, episode: &Episode) -> Option<String> {
+        (*self != Self::Guid)
+            .then(|| self.key(&episode.guid, episode.pub_date, Some(&episode.audio_url)))
+    }
+
+    fn key(
+        &self,
+        guid: &str,
+        pub_date: Option<DateTime<Utc>>,
+        enclosure_url: Option<&str>,
+    ) -> String {
+        match (self, enclosure_url) {
+            (Self::EnclosureUrl, Some(url)) => url.to_string(),
+            (Self::NormalizedUrl, Some(url)) => normalize_url(url),
+            (Self::ContentHash, _) => match pub_date {
+                Some(pub_date) => {
+                    let mut hasher = Sha256::new();
+                    hasher.update(pub_date.to_rfc3339());
+                    hasher.update([0u8]);
+                    hasher.update(enclosure_url.map(file_name).unwrap_or_default());
+                    format!("sha256:{:x}", hasher.finalize())
+                }
+                None => guid.to_string(),
+            },
+            _ => guid.to_string(),
+        }
+    }
+}
+
+/// Host and path of a URL, so the same file served over http or https,
+/// or with rotating query tokens, keeps the same key
+fn normalize_url(url: &str) -> String {
+    match Url::parse(url.trim()) {
+        Ok(parsed) => {
+            let host = parsed.host_str().unwrap_or_default().to_lowercase();
+            let path = parsed.path().trim_end_matches('/');
+            match parsed.port() {
+                Some(port) => format!("{}:{}{}", host, port, path),
+                None => format!("{}{}", host, path),
+            }
+        }
+        Err(_) => url.split(['?', '#']).next().unwrap_or_default().to_string(),
+    }
+}
+
+/// Last segment of a normalized URL, the same file moved to another host or behind
+/// a tracking prefix keeps it
+fn file_name(url: &str) -> &str {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    url.trim_end_matches('/').rsplit('/').next().unwrap_or(url)
+}
+
+/// How the audio a feed lists for a known episode compares to what was recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnclosureChange {
+    Unchanged,
+    /// The same file at another URL, or in another quality since the preference changed
+    Moved,
+    /// Another file, the publisher replaced the audio
+    Replaced,
+}
+
+/// Compare the audio of a feed item with the one recorded for an episode
+/// Replacements are told from the standard enclosure, which doesn't depend on the quality
+/// preference, by path so new hosts, tracking prefixes and rotating query tokens don't count
+/// Episodes recorded without a standard enclosure are moved, so it gets recorded
+pub fn enclosure_change(episode: &Episode, item: &ParsedItem) -> EnclosureChange {
+    let (Some(enclosure), Some(standard)) = (&item.enclosure, &item.standard_enclosure) else {
+        return EnclosureChange::Unchanged;
+    };
+    let Some(recorded) = &episode.enclosure_url else {
+        return EnclosureChange::Moved;
+    };
+
+    let size_changed = matches!(
+        (episode.enclosure_length, standard.length),
+        (Some(stored), Some(length)) if stored > 0 && length > 0 && stored != length
+    );
+    if size_changed || !same_file(recorded, &standard.url) {
+        EnclosureChange::Replaced
+    } else if normalize_url(&episode.audio_url) != normalize_url(&enclosure.url) {
+        EnclosureChange::Moved
+    } else {
+        EnclosureChange::Unchanged
+    }
+}
+
+/// Whether two URLs point to the same file, also on another host or behind a tracking prefix
+fn same_file(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize_url(a), normalize_url(b));
+    let path = |url: &str| url.find('/').map(|start| url[start..].to_string());
+
+    a == b
+        || a.ends_with(&format!("/{}", b))
+        || b.ends_with(&format!("/{}", a))
+        || path(&a).is_some_and(|path_a| Some(path_a) == path(&b))
+}
+
+/// Outcome of re-keying the episodes of a subscription
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RekeySummary {
+    /// Episodes whose key changed
+    pub updated: usize,
+    /// Episodes whose key is already right
+    pub unchanged: usize,
+    /// Episodes missing from the feed that had to keep their key
+    pub unmatched: usize,
+    /// Duplicate episodes that would share a key, the first one keeps it
+    pub conflicts: usize,
+}
+
+/// New keys of existing episodes when switching from one strategy to another
+/// Episodes are matched to feed items by their current key or enclosure URL,
+/// those no longer in the feed are keyed from their stored details
+pub fn plan_rekey(
+    from: IdentityStrategy,
+    to: IdentityStrategy,
+    episodes: &[Episode],
+    items: &[ParsedItem],
+) -> (Vec<(i64, String)>, RekeySummary) {
+    let mut summary = RekeySummary::default();
+
+    // Completed episodes keep their key first when duplicates collide
+    let mut ordered: Vec<&Episode> = episodes.iter().collect();
+    ordered.sort_by_key(|episode| (episode.download_status != "completed", episode.id));
+
+    let from_keys = from.item_keys(items);
+    let to_keys = to.item_keys(items);
+    let mut targets: HashMap<i64, String> = HashMap::new();
+    for episode in &ordered {
+        let index = items.iter().zip(&from_keys).position(|(item, key)| {
+            *key == episode.guid
+                || item.enclosure.as_ref().map(|e| e.url.as_str()) == Some(&episode.audio_url)
+        });
+
+        let target = match index {
+            Some(index) => to_keys[index].clone(),
+            None => to.episode_key(episode).unwrap_or_else(|| {
+                summary.unmatched += 1;
+                episode.guid.clone()
+            }),
+        };
+        targets.insert(episode.id, target);
+    }
+
+    // Current keys are unique, so reverting to them always ends the loop
+    loop {
+        let mut owners: HashMap<&str, &Episode> = HashMap::new();
+        let mut reverted = None;
+
+        for episode in &ordered {
+            let target = targets[&episode.id].as_str();
+            match owners.get(target) {
+                None => {
+                    owners.insert(target, *episode);
+                }
+                Some(_) if target != episode.guid => {
+                    reverted = Some(*episode);
+                    break;
+                }
+                Some(owner) => {
+                    reverted = Some(*owner);
+                    break;
+                }
+            }
+        }
+
+        let Some(episode) = reverted else {
+            break;
+        };
+        summary.conflicts += 1;
+        targets.insert(episode.id, episode.guid.clone());
+    }
+
+    let mut keys = Vec::new();
+    for episode in ordered {
+        let target = targets.remove(&episode.id).unwrap_or_default();
+        if target == episode.guid {
+            summary.unchanged += 1;
+        } else {
+            summary.updated += 1;
+            keys.push((episode.id, target));
+        }
+    }
+
+    (keys, summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+    use crate::rss::parser::Enclosure;
+    use chrono::{Duration, TimeZone};
+
+    fn item(guid: &str, url: &str) -> ParsedItem {
+        ParsedItem {
+            guid: guid.to_string(),
+            title: format!("Episode {}", guid),
+            description: None,
+            pub_date: None,
+            enclosure: Some(Enclosure {
+                url: url.to_string(),
+                mime_type: None,
+                length: Some(1024),
+            }),
+            standard_enclosure: Some(Enclosure {
+                url: url.to_string(),
+                mime_type: None,
+                length: Some(1024),
+            }),
+            image_url: None,
+            author: None,
+            duration: None,
+            season: None,
+            episode_number: None,
+        }
+    }
+
+    fn episode(id: i64, guid: &str, url: &str, status: &str) -> Episode {
+        Episode {
+            guid: guid.to_string(),
+            title: format!("Episode {}", guid),
+            audio_url: url.to_string(),
+            audio_size_bytes: Some(1024),
+            enclosure_url: Some(url.to_string()),
+            enclosure_length: Some(1024),
+            download_status: status.to_string(),
+            ..test_support::episode(id)
+        }
+    }
+
+    #[test]
+    fn test_item_keys() {
+        let first = item("a", "https://CDN.example.com/show/1.mp3?token=abc#t=10");
+        let second = item("b", "http://cdn.example.com/show/1.mp3?token=def");
+
+        assert_eq!(IdentityStrategy::Guid.item_key(&first), "a");
+        assert_eq!(
+            IdentityStrategy::EnclosureUrl.item_key(&second),
+            "http://cdn.example.com/show/1.mp3?token=def"
+        );
+        assert_eq!(
+            IdentityStrategy::NormalizedUrl.item_key(&first),
+            "cdn.example.com/show/1.mp3"
+        );
+        assert_eq!(
+            IdentityStrategy::NormalizedUrl.item_key(&first),
+            IdentityStrategy::NormalizedUrl.item_key(&second)
+        );
+
+        // Regenerated GUIDs and URLs and edited titles keep the same content hash
+        let published = Utc.with_ymd_and_hms(2024, 3, 5, 8, 0, 0).unwrap();
+        let dated = ParsedItem {
+            pub_date: Some(published),
+            ..first.clone()
+        };
+        let renamed = ParsedItem {
+            title: "Episode A: the full interview".to_string(),
+            pub_date: Some(published),
+            ..item("c", "https://other.example.com/1.mp3")
+        };
+        let next_day = ParsedItem {
+            pub_date: Some(published + Duration::days(1)),
+            ..second
+        };
+        let hash = IdentityStrategy::ContentHash.item_key(&dated);
+        assert!(hash.starts_with("sha256:"));
+        assert_eq!(hash, IdentityStrategy::ContentHash.item_key(&renamed));
+        assert_ne!(hash, IdentityStrategy::ContentHash.item_key(&next_day));
+
+        // A corrected file of another size keeps the key, so it is seen as an update
+        let corrected = ParsedItem {
+            enclosure: Some(Enclosure {
+                length: Some(2048),
+                ..dated.enclosure.clone().unwrap()
+            }),
+            ..dated.clone()
+        };
+        assert_eq!(hash, IdentityStrategy::ContentHash.item_key(&corrected));
+
+        // Undated items can't be told apart by their content
+        assert_eq!(IdentityStrategy::ContentHash.item_key(&first), "a");
+
+        let no_enclosure = ParsedItem {
+            enclosure: None,
+            ..item("d", "")
+        };
+        assert_eq!(IdentityStrategy::NormalizedUrl.item_key(&no_enclosure), "d");
+
+        assert_eq!(
+            IdentityStrategy::parse("content_hash").unwrap(),
+            IdentityStrategy::ContentHash
+        );
+        assert!(IdentityStrategy::parse("title").is_err());
+    }
+
+    #[test]
+    fn test_shared_keys_fall_back_to_guid() {
+        // Segments published at the same time, the last two under the same file name
+        let published = Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap();
+        let segment = |guid: &str, url: &str| ParsedItem {
+            pub_date: Some(published),
+            ..item(guid, url)
+        };
+        let items = vec![
+            segment("a", "https://cdn.example.com/part-1.mp3"),
+            segment("b", "https://cdn.example.com/part-2.mp3"),
+            segment("c", "https://cdn.example.com/2024/part-3.mp3"),
+            segment("d", "https://mirror.example.com/part-3.mp3"),
+        ];
+
+        let keys = IdentityStrategy::ContentHash.item_keys(&items);
+        assert!(keys[0].starts_with("sha256:"));
+        assert!(keys[1].starts_with("sha256:"));
+        assert_ne!(keys[0], keys[1]);
+        assert_eq!(keys[2..], ["c", "d"]);
+    }
+
+    #[test]
+    fn test_plan_rekey() {
+        let items = vec![
+            item("a", "https://cdn.example.com/1.mp3?t=2"),
+            item("b-new", "https://cdn.example.com/2.mp3?t=2"),
+        ];
+        let episodes = vec![
+            // Still in the feed with the same GUID
+            episode(1, "a", "https://cdn.example.com/1.mp3?t=1", "completed"),
+            // GUID regenerated, matched by enclosure URL
+            episode(2, "b", "https://cdn.example.com/2.mp3?t=2", "completed"),
+            // Gone from the feed, keyed from the stored URL
+            episode(3, "old", "https://cdn.example.com/0.mp3", "completed"),
+            // Duplicate of episode 1 downloaded after its URL changed
+            episode(4, "a-dup", "https://cdn.example.com/1.mp3?t=9", "pending"),
+        ];
+
+        let (keys, summary) = plan_rekey(
+            IdentityStrategy::Guid,
+            IdentityStrategy::NormalizedUrl,
+            &episodes,
+            &items,
+        );
+        assert_eq!(
+            keys,
+            vec![
+                (1, "cdn.example.com/1.mp3".to_string()),
+                (2, "cdn.example.com/2.mp3".to_string()),
+                (3, "cdn.example.com/0.mp3".to_string()),
+            ]
+        );
+        assert_eq!(
+            summary,
+            RekeySummary {
+                updated: 3,
+                unchanged: 1,
+                unmatched: 0,
+                conflicts: 1,
+            }
+        );
+
+        // Back to GUIDs, episodes missing from the feed can't be re-keyed
+        let rekeyed: Vec<Episode> = episodes
+            .iter()
+            .take(3)
+            .zip(&keys)
+            .map(|(e, (_, key))| Episode {
+                guid: key.clone(),
+                ..e.clone()
+            })
+            .collect();
+        let (keys, summary) = plan_rekey(
+            IdentityStrategy::NormalizedUrl,
+            IdentityStrategy::Guid,
+            &rekeyed,
+            &items,
+        );
+        assert_eq!(keys, vec![(1, "a".to_string()), (2, "b-new".to_string())]);
+        assert_eq!(summary.unmatched, 1);
+    }
+
+    #[test]
+    fn test_enclosure_change() {
+        let stored = episode(
+            1,
+            "a",
+            "https://cdn.example.com/show/1.mp3?token=old",
+            "completed",
+        );
+        let change = |standard_url: &str, length: Option<i64>, url: &str| {
+            let standard = ParsedItem {
+                standard_enclosure: Some(Enclosure {
+                    url: standard_url.to_string(),
+                    mime_type: None,
+                    length,
+                }),
+                ..item("a", url)
+            };
+            enclosure_change(&stored, &standard)
+        };
+        let url = "https://cdn.example.com/show/1.mp3?token=new";
+
+        // Rotating tokens and missing lengths aren't changes
+        assert_eq!(change(url, Some(1024), url), EnclosureChange::Unchanged);
+        assert_eq!(
+            change("https://cdn.example.com/show/1.mp3", None, url),
+            EnclosureChange::Unchanged
+        );
+
+        // Neither are new hosts, tracking prefixes or another preferred quality, the
+        // stored URL only follows them
+        assert_eq!(
+            change(
+                "https://media.example.net/show/1.mp3",
+                Some(1024),
+                "https://media.example.net/show/1.mp3"
+            ),
+            EnclosureChange::Moved
+        );
+        assert_eq!(
+            change(
+                "https://dts.example.org/redirect.mp3/cdn.example.com/show/1.mp3",
+                Some(1024),
+                url
+            ),
+            EnclosureChange::Unchanged
+        );
+        assert_eq!(
+            change(url, Some(1024), "https://cdn.example.com/show/1.flac"),
+            EnclosureChange::Moved
+        );
+
+        // A corrected file under another name or with another size is
+        assert_eq!(
+            change("https://cdn.example.com/show/1-fixed.mp3", Some(1024), url),
+            EnclosureChange::Replaced
+        );
+        assert_eq!(change(url, Some(2048), url), EnclosureChange::Replaced);
+
+        // Episodes recorded before standard enclosures were kept get it recorded
+        let unrecorded = Episode {
+            enclosure_url: None,
+            ..stored.clone()
+        };
+        assert_eq!(
+            enclosure_change(&unrecorded, &item("a", url)),
+            EnclosureChange::Moved
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+
+use crate::db::models::Subscription;
+use crate::rss::parser::ParsedItem;
+use crate::utils::{AppError, AppResult};
+
+/// A feed item left out with the reason
+pub type SkippedItem<K> = (K, ParsedItem, String);
+
+/// What the first check of a new subscription downloads from the feed's archive
+/// Items it leaves out are recorded as skipped, so they can be backfilled later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitialDownload {
+    /// Every item, within the usual limits
+    All,
+    /// Nothing, only episodes published afterwards are downloaded
+    None,
+    /// The given number of most recent items
+    Latest(usize),
+    /// Items published from a date on, undated items included
+    Since(DateTime<Utc>),
+}
+
+impl InitialDownload {
+    pub fn parse(mode: &str, count: Option<i32>, since: Option<DateTime<Utc>>) -> AppResult<Self> {
+        match mode {
+            "all" => Ok(Self::All),
+            "none" => Ok(Self::None),
+            "latest" => match count {
+                Some(count) if count > 0 => Ok(Self::Latest(count as usize)),
+                _ => Err(AppError::InvalidInput(
+                    "Downloading the latest episodes needs a count above 0".to_string(),
+                )),
+            },
+            "since" => since.map(Self::Since).ok_or_else(|| {
+                AppError::InvalidInput("Downloading since a date needs a date".to_string())
+            }),
+            other => Err(AppError::InvalidInput(format!(
+                "Invalid initial download '{}', expected all, none, latest or since",
+                other
+            ))),
+        }
+    }
+
+    /// Mode still to apply to a subscription, None once its first check is done
+    pub fn of_subscription(subscription: &Subscription) -> AppResult<Option<Self>> {
+        subscription
+            .initial_download
+            .as_deref()
+            .map(|mode| {
+                Self::parse(
+                    mode,
+                    subscription.initial_download_count,
+                    subscription.initial_download_since,
+                )
+            })
+            .transpose()
+    }
+
+    /// Split new items, most recent first, into those to download and those to skip
+    /// with the reason
+    pub fn split<K>(
+        &self,
+        items: Vec<(K, ParsedItem)>,
+    ) -> (Vec<(K, ParsedItem)>, Vec<SkippedItem<K>>) {
+        let mut kept = Vec::new();
+        let mut skipped = Vec::new();
+        for (index, (key, item)) in items.into_iter().enumerate() {
+            match self.rejection(index, &item) {
+                Some(reason) => skipped.push((key, item, reason)),
+                None => kept.push((key, item)),
+            }
+        }
+
+        (kept, skipped)
+    }
+
+    fn rejection(&self, index: usize, item: &ParsedItem) -> Option<String> {
+        match *self {
+            Self::All => None,
+            Self::None => Some("Published before the subscription was added".to_string()),
+            Self::Latest(count) => (index >= count).then(|| {
+                format!(
+                    "Not among the latest {} episodes when the subscription was added",
+                    count
+                )
+            }),
+            Self::Since(since) => item
+                .pub_date
+                .filter(|pub_date| *pub_date < since)
+                .map(|_| format!("Published before {}", since.format("%Y-%m-%d"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn items() -> Vec<(i32, ParsedItem)> {
+        [Some(2024), Some(2023), None, Some(2021)]
+            .into_iter()
+            .enumerate()
+            .map(|(index, year)| {
+                let item = ParsedItem {
+                    guid: index.to_string(),
+                    title: format!("Episode {}", index),
+                    description: None,
+                    pub_date: year.map(|year| Utc.with_ymd_and_hms(year, 6, 1, 0, 0, 0).unwrap()),
+                    enclosure: None,
+                    standard_enclosure: None,
+                    image_url: None,
+                    author: None,
+                    duration: None,
+                    season: None,
+                    episode_number: None,
+                };
+                (index as i32, item)
+            })
+            .collect()
+    }
+
+    fn kept_keys(mode: InitialDownload) -> Vec<i32> {
+        let (kept, skipped) = mode.split(items());
+        assert_eq!(kept.len() + skipped.len(), 4);
+        kept.into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(kept_keys(InitialDownload::All), vec![0, 1, 2, 3]);
+        assert!(kept_keys(InitialDownload::None).is_empty());
+        assert_eq!(kept_keys(InitialDownload::Latest(2)), vec![0, 1]);
+
+        let since = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(kept_keys(InitialDownload::Since(since)), vec![0, 1, 2]);
+
+        let (_, skipped) = InitialDownload::Since(since).split(items());
+        assert_eq!(skipped[0].2, "Published before 2023-01-01");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            InitialDownload::parse("latest", Some(5), None).unwrap(),
+            InitialDownload::Latest(5)
+        );
+        assert_eq!(
+            InitialDownload::parse("none", None, None).unwrap(),
+            InitialDownload::None
+        );
+        assert!(InitialDownload::parse("latest", Some(0), None).is_err());
+        assert!(InitialDownload::parse("since", None, None).is_err());
+        assert!(InitialDownload::parse("newest", None, None).is_err());
+    }
+}
@@ -1,5 +1,11 @@
+pub mod dates;
 pub mod fetcher;
+pub mod filter;
+pub mod identity;
+pub mod initial_download;
 pub mod parser;
 
-pub use fetcher::{fetch_rss, fetch_rss_with_limit};
+pub use fetcher::{fetch_rss, fetch_rss_conditional, fetch_rss_with_limit, FeedResponse};
+pub use identity::IdentityStrategy;
+pub use initial_download::InitialDownload;
 pub use parser::parse_rss_with_quality;
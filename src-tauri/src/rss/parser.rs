@@ -1,6 +1,9 @@
+use atom_syndication::Feed;
 use chrono::{DateTime, Utc};
 use rss::Channel;
 
+use crate::rss::dates::parse_date;
+use crate::rss::identity::IdentityStrategy;
 use crate::utils::AppResult;
 
 #[derive(Debug, Clone)]
@@ -17,10 +20,15 @@ pub struct ParsedItem {
     pub title: String,
     pub description: Option<String>,
     pub pub_date: Option<DateTime<Utc>>,
+    /// Enclosure of the subscription's preferred quality
     pub enclosure: Option<Enclosure>,
+    /// Enclosure the feed lists first, whatever quality is preferred
+    pub standard_enclosure: Option<Enclosure>,
     pub image_url: Option<String>,
     pub author: Option<String>,
     pub duration: Option<i32>,
+    pub season: Option<i32>,
+    pub episode_number: Option<i32>,
 }
 
 #[derive(Debug, Clone)]
@@ -36,8 +44,64 @@ pub fn parse_rss(xml: &str) -> AppResult<ParsedFeed> {
     parse_rss_with_quality(xml, "enclosure")
 }
 
-/// Parse RSS feed content with quality preference
+/// Syndication format of a feed document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+}
+
+/// Detect the feed format from the document's root element
+/// Anything that isn't an Atom `<feed>` is handed to the RSS parser
+pub fn detect_feed_format(xml: &str) -> FeedFormat {
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        // Skip the XML declaration, processing instructions, comments and doctype as a
+        // whole, markup inside them isn't the root element
+        let end = if rest.starts_with("!--") {
+            Some(rest.find("-->").map_or(rest.len(), |end| end + 3))
+        } else if rest.starts_with('?') {
+            Some(rest.find("?>").map_or(rest.len(), |end| end + 2))
+        } else if rest.starts_with('!') {
+            // A doctype can carry an internal subset with declarations in brackets
+            let subset_end = match (rest.find('['), rest.find('>')) {
+                (Some(open), Some(close)) if open < close => rest.find(']').unwrap_or(open),
+                _ => 0,
+            };
+            let close = rest[subset_end..].find('>');
+            Some(close.map_or(rest.len(), |end| subset_end + end + 1))
+        } else {
+            None
+        };
+        if let Some(end) = end {
+            rest = &rest[end..];
+            continue;
+        }
+
+        let name = rest
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()
+            .unwrap_or_default();
+        let local_name = name.rsplit(':').next().unwrap_or(name);
+
+        return if local_name == "feed" {
+            FeedFormat::Atom
+        } else {
+            FeedFormat::Rss
+        };
+    }
+
+    FeedFormat::Rss
+}
+
+/// Parse RSS or Atom feed content with quality preference
 pub fn parse_rss_with_quality(xml: &str, quality: &str) -> AppResult<ParsedFeed> {
+    if detect_feed_format(xml) == FeedFormat::Atom {
+        return parse_atom_with_quality(xml, quality);
+    }
+
     tracing::info!("Parsing RSS feed with quality: {}", quality);
 
     let channel = Channel::read_from(xml.as_bytes())?;
@@ -45,16 +109,7 @@ pub fn parse_rss_with_quality(xml: &str, quality: &str) -> AppResult<ParsedFeed>
     let items: Vec<ParsedItem> = channel
         .items()
         .iter()
-        .map(|item| ParsedItem {
-            guid: extract_guid(item),
-            title: item.title().unwrap_or("Untitled").to_string(),
-            description: item.description().map(|d| d.to_string()),
-            pub_date: extract_pub_date(item),
-            enclosure: extract_enclosure_with_quality(item, quality),
-            image_url: extract_image_url(item),
-            author: extract_author(item),
-            duration: extract_duration(item),
-        })
+        .map(|item| parse_rss_item(item, quality))
         .collect();
 
     tracing::info!("Parsed {} items from RSS feed", items.len());
@@ -66,6 +121,161 @@ pub fn parse_rss_with_quality(xml: &str, quality: &str) -> AppResult<ParsedFeed>
     })
 }
 
+/// Parse Atom feed content, entries are mapped like RSS items
+/// and their `link rel="enclosure"` elements like RSS enclosures
+fn parse_atom_with_quality(xml: &str, quality: &str) -> AppResult<ParsedFeed> {
+    tracing::info!("Parsing Atom feed with quality: {}", quality);
+
+    let feed = Feed::read_from(xml.as_bytes())?;
+
+    let items: Vec<ParsedItem> = feed
+        .entries()
+        .iter()
+        .map(|entry| parse_atom_entry(entry, quality))
+        .collect();
+
+    tracing::info!("Parsed {} items from Atom feed", items.len());
+
+    Ok(ParsedFeed {
+        title: feed.title().as_str().to_string(),
+        description: feed.subtitle().map(|s| s.as_str().to_string()),
+        items,
+    })
+}
+
+/// Map an RSS item with quality preference
+fn parse_rss_item(item: &rss::Item, quality: &str) -> ParsedItem {
+    ParsedItem {
+        guid: extract_guid(item),
+        title: item.title().unwrap_or("Untitled").to_string(),
+        description: item.description().map(|d| d.to_string()),
+        pub_date: extract_pub_date(item),
+        enclosure: extract_enclosure_with_quality(item, quality),
+        standard_enclosure: extract_enclosure(item).or_else(|| extract_best_from_media_group(item)),
+        image_url: extract_image_url(item),
+        author: extract_author(item),
+        duration: extract_duration(item),
+        season: extract_itunes_number(item, |ext| ext.season()),
+        episode_number: extract_itunes_number(item, |ext| ext.episode()),
+    }
+}
+
+/// Map an Atom entry like an RSS item
+fn parse_atom_entry(entry: &atom_syndication::Entry, quality: &str) -> ParsedItem {
+    ParsedItem {
+        guid: extract_atom_guid(entry),
+        title: non_empty_text(entry.title().as_str()).unwrap_or_else(|| "Untitled".to_string()),
+        description: entry.summary().map(|s| s.as_str().to_string()).or_else(|| {
+            entry
+                .content()
+                .and_then(|c| c.value())
+                .map(|v| v.to_string())
+        }),
+        pub_date: Some(
+            entry
+                .published()
+                .unwrap_or_else(|| entry.updated())
+                .with_timezone(&Utc),
+        ),
+        enclosure: extract_atom_enclosure(entry, quality),
+        standard_enclosure: extract_atom_enclosure(entry, "enclosure"),
+        image_url: extract_atom_image_url(entry),
+        author: entry.authors().first().map(|a| a.name().to_string()),
+        duration: extract_atom_duration(entry),
+        season: None,
+        episode_number: None,
+    }
+}
+
+/// Extract GUID from an Atom entry (fallback to its link)
+fn extract_atom_guid(entry: &atom_syndication::Entry) -> String {
+    non_empty_text(entry.id())
+        .or_else(|| {
+            entry
+                .links()
+                .iter()
+                .find(|l| l.rel() == "alternate")
+                .map(|l| l.href().to_string())
+        })
+        .unwrap_or_else(|| {
+            format!(
+                "{}_{}",
+                entry.title().as_str(),
+                entry.updated().to_rfc3339()
+            )
+        })
+}
+
+/// Extract the audio enclosure of an Atom entry
+/// With several enclosures, the one whose MIME type matches the quality wins
+fn extract_atom_enclosure(entry: &atom_syndication::Entry, quality: &str) -> Option<Enclosure> {
+    let enclosures: Vec<Enclosure> = entry
+        .links()
+        .iter()
+        .filter(|l| l.rel() == "enclosure")
+        .filter(|l| !l.mime_type().is_some_and(|t| t.starts_with("image/")))
+        .map(|l| Enclosure {
+            url: l.href().to_string(),
+            mime_type: l.mime_type().map(|t| t.to_string()),
+            length: l.length().and_then(|len| len.parse().ok()),
+        })
+        .collect();
+
+    let preferred_types: &[&str] = match quality {
+        "original" => &["wav", "aiff"],
+        "flac" => &["flac"],
+        "mp3" => &["mpeg", "mp3"],
+        _ => &[],
+    };
+
+    enclosures
+        .iter()
+        .find(|e| {
+            e.mime_type
+                .as_ref()
+                .is_some_and(|t| preferred_types.iter().any(|p| t.contains(p)))
+        })
+        .or_else(|| enclosures.first())
+        .cloned()
+}
+
+/// Extract image URL of an Atom entry (media thumbnail, then image enclosure)
+fn extract_atom_image_url(entry: &atom_syndication::Entry) -> Option<String> {
+    let thumbnail = entry
+        .extensions()
+        .get("media")
+        .and_then(|media| media.get("thumbnail"))
+        .and_then(|thumbnails| thumbnails.first())
+        .and_then(|thumbnail| thumbnail.attrs.get("url"))
+        .map(|url| url.to_string());
+
+    thumbnail.or_else(|| {
+        entry
+            .links()
+            .iter()
+            .find(|l| {
+                l.rel() == "enclosure" && l.mime_type().is_some_and(|t| t.starts_with("image/"))
+            })
+            .map(|l| l.href().to_string())
+    })
+}
+
+/// Extract duration of an Atom entry (iTunes duration extension)
+fn extract_atom_duration(entry: &atom_syndication::Entry) -> Option<i32> {
+    entry
+        .extensions()
+        .get("itunes")
+        .and_then(|itunes| itunes.get("duration"))
+        .and_then(|durations| durations.first())
+        .and_then(|duration| duration.value.as_deref())
+        .and_then(|d| parse_duration(d.trim()))
+}
+
+fn non_empty_text(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
 /// Extract GUID from item (fallback to link)
 fn extract_guid(item: &rss::Item) -> String {
     item.guid()
@@ -81,11 +291,12 @@ fn extract_guid(item: &rss::Item) -> String {
         })
 }
 
-/// Extract publication date
+/// Extract publication date, falling back to dc:date
 fn extract_pub_date(item: &rss::Item) -> Option<DateTime<Utc>> {
-    item.pub_date()
-        .and_then(|d| DateTime::parse_from_rfc2822(d).ok())
-        .map(|d| d.with_timezone(&Utc))
+    item.pub_date().and_then(parse_date).or_else(|| {
+        item.dublin_core_ext()
+            .and_then(|dc| dc.dates().iter().find_map(|d| parse_date(d)))
+    })
 }
 
 /// Extract enclosure (audio file)
@@ -208,8 +419,53 @@ fn extract_from_media_group(item: &rss::Item, quality: &str) -> Option<Enclosure
     None
 }
 
+/// Standard, original, FLAC and MP3 URLs of an episode
+pub type MediaUrls = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Find the media URLs of the episode with the given key in an RSS or Atom feed
+/// Keys are computed with the subscription's identity strategy and quality
+/// Atom entries only have a standard enclosure
+pub fn find_media_urls(
+    xml: &str,
+    key: &str,
+    strategy: IdentityStrategy,
+    quality: &str,
+) -> AppResult<Option<MediaUrls>> {
+    if detect_feed_format(xml) == FeedFormat::Atom {
+        let feed = Feed::read_from(xml.as_bytes())?;
+        let parsed: Vec<ParsedItem> = feed
+            .entries()
+            .iter()
+            .map(|entry| parse_atom_entry(entry, quality))
+            .collect();
+        let keys = strategy.item_keys(&parsed);
+        return Ok(keys.iter().position(|k| k == key).map(|index| {
+            let standard_url =
+                extract_atom_enclosure(&feed.entries()[index], "enclosure").map(|e| e.url);
+            (standard_url, None, None, None)
+        }));
+    }
+
+    let channel = Channel::read_from(xml.as_bytes())?;
+    let parsed: Vec<ParsedItem> = channel
+        .items()
+        .iter()
+        .map(|item| parse_rss_item(item, quality))
+        .collect();
+    let keys = strategy.item_keys(&parsed);
+    Ok(keys
+        .iter()
+        .position(|k| k == key)
+        .map(|index| extract_all_media_urls(&channel.items()[index])))
+}
+
 /// Extract all available media URLs from an item
-pub fn extract_all_media_urls(item: &rss::Item) -> (Option<String>, Option<String>, Option<String>, Option<String>) {
+pub fn extract_all_media_urls(item: &rss::Item) -> MediaUrls {
     // Standard enclosure
     let standard_url = extract_enclosure(item).map(|e| e.url);
 
@@ -259,6 +515,16 @@ fn extract_duration(item: &rss::Item) -> Option<i32> {
         .and_then(|d| parse_duration(d))
 }
 
+/// Extract <itunes:season> or <itunes:episode>
+fn extract_itunes_number(
+    item: &rss::Item,
+    field: impl Fn(&rss::extension::itunes::ITunesItemExtension) -> Option<&str>,
+) -> Option<i32> {
+    item.itunes_ext()
+        .and_then(field)
+        .and_then(|number| number.trim().parse().ok())
+}
+
 /// Parse duration from iTunes format (can be seconds or HH:MM:SS)
 fn parse_duration(duration_str: &str) -> Option<i32> {
     // Try parsing as pure seconds first
@@ -290,6 +556,205 @@ fn parse_duration(duration_str: &str) -> Option<i32> {
 mod tests {
     use super::*;
 
+    const RSS_FEED: &str = include_str!("../../tests/fixtures/rss_feed.xml");
+    const ATOM_FEED: &str = include_str!("../../tests/fixtures/atom_feed.xml");
+
+    #[test]
+    fn test_detect_feed_format() {
+        assert_eq!(detect_feed_format(RSS_FEED), FeedFormat::Rss);
+        assert_eq!(detect_feed_format(ATOM_FEED), FeedFormat::Atom);
+        assert_eq!(
+            detect_feed_format(r#"<atom:feed xmlns:atom="http://www.w3.org/2005/Atom"/>"#),
+            FeedFormat::Atom
+        );
+
+        // Markup inside comments and the doctype isn't the root element
+        assert_eq!(
+            detect_feed_format("<?xml version=\"1.0\"?><!-- <feed> --><rss version=\"2.0\"/>"),
+            FeedFormat::Rss
+        );
+        assert_eq!(
+            detect_feed_format("<!DOCTYPE rss [<!ENTITY feed \"<feed>\">]><rss/>"),
+            FeedFormat::Rss
+        );
+        assert_eq!(
+            detect_feed_format("<!DOCTYPE feed><!-- rss --><feed/>"),
+            FeedFormat::Atom
+        );
+    }
+
+    #[test]
+    fn test_parse_rss_fixture() {
+        let feed = parse_rss(RSS_FEED).unwrap();
+        assert_eq!(feed.title, "Morning Show");
+        assert_eq!(feed.items.len(), 2);
+
+        let item = &feed.items[0];
+        assert_eq!(item.guid, "morning-2");
+        assert_eq!(item.title, "Episode 2");
+        assert_eq!(item.author.as_deref(), Some("Jane Host"));
+        assert_eq!(item.duration, Some(3723));
+        assert_eq!((item.season, item.episode_number), (Some(2), Some(14)));
+        assert_eq!(feed.items[1].season, None);
+        assert_eq!(
+            item.pub_date.map(|d| d.to_rfc3339()).as_deref(),
+            Some("2024-01-02T07:00:00+00:00")
+        );
+
+        // Best quality from media:group by default
+        let enclosure = item.enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "https://cdn.example.com/morning-2-raw.flac");
+
+        let mp3 = parse_rss_with_quality(RSS_FEED, "mp3").unwrap();
+        let enclosure = mp3.items[0].enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "https://cdn.example.com/morning-2-std.mp3");
+        assert_eq!(enclosure.length, Some(2048));
+
+        // The standard enclosure doesn't follow the quality
+        for feed in [&feed, &mp3] {
+            let standard = feed.items[0].standard_enclosure.as_ref().unwrap();
+            assert_eq!(standard.url, "https://cdn.example.com/morning-2.mp3");
+        }
+
+        let enclosure = feed.items[1].enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "https://cdn.example.com/morning-1.mp3");
+        assert_eq!(enclosure.mime_type.as_deref(), Some("audio/mpeg"));
+    }
+
+    #[test]
+    fn test_parse_atom_fixture() {
+        let feed = parse_rss(ATOM_FEED).unwrap();
+        assert_eq!(feed.title, "Evening Show");
+        assert_eq!(feed.description.as_deref(), Some("The daily evening show"));
+        assert_eq!(feed.items.len(), 2);
+
+        let item = &feed.items[0];
+        assert_eq!(item.guid, "urn:uuid:evening-2");
+        assert_eq!(item.description.as_deref(), Some("Second episode"));
+        assert_eq!(item.author.as_deref(), Some("John Host"));
+        assert_eq!(item.duration, Some(2730));
+        assert_eq!(
+            item.image_url.as_deref(),
+            Some("https://cdn.example.com/evening-2.jpg")
+        );
+        assert_eq!(
+            item.pub_date.map(|d| d.to_rfc3339()).as_deref(),
+            Some("2024-01-02T17:00:00+00:00")
+        );
+
+        // Image enclosures are skipped, the first audio one is the default
+        let enclosure = item.enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "https://cdn.example.com/evening-2.mp3");
+        assert_eq!(enclosure.mime_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(enclosure.length, Some(4096));
+
+        let flac = parse_rss_with_quality(ATOM_FEED, "flac").unwrap();
+        let enclosure = flac.items[0].enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "https://cdn.example.com/evening-2.flac");
+
+        // No published date, updated is used instead
+        let item = &feed.items[1];
+        assert_eq!(item.description.as_deref(), Some("First episode"));
+        assert_eq!(
+            item.pub_date.map(|d| d.to_rfc3339()).as_deref(),
+            Some("2024-01-01T19:00:00+00:00")
+        );
+        let enclosure = item.enclosure.as_ref().unwrap();
+        assert_eq!(enclosure.url, "https://cdn.example.com/evening-1.m4a");
+        assert_eq!(enclosure.mime_type, None);
+    }
+
+    #[test]
+    fn test_pub_date_fallbacks() {
+        let xml = r#"<?xml version="1.0"?>
+            <rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <channel>
+                <title>Dates</title>
+                <item>
+                  <guid>lenient</guid>
+                  <pubDate>mardi, 2 janvier 2024 08:00:00 GMT+1</pubDate>
+                </item>
+                <item>
+                  <guid>dc-date</guid>
+                  <dc:date>2024-01-03T07:00:00Z</dc:date>
+                </item>
+                <item>
+                  <guid>invalid</guid>
+                  <pubDate>sometime</pubDate>
+                  <dc:date>2024-01-04</dc:date>
+                </item>
+              </channel>
+            </rss>"#;
+
+        let feed = parse_rss(xml).unwrap();
+        let dates: Vec<_> = feed
+            .items
+            .iter()
+            .map(|item| item.pub_date.map(|d| d.to_rfc3339()))
+            .collect();
+        assert_eq!(
+            dates,
+            vec![
+                Some("2024-01-02T07:00:00+00:00".to_string()),
+                Some("2024-01-03T07:00:00+00:00".to_string()),
+                Some("2024-01-04T00:00:00+00:00".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_media_urls() {
+        let (standard, _, flac, mp3) =
+            find_media_urls(RSS_FEED, "morning-2", IdentityStrategy::Guid, "enclosure")
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            standard.as_deref(),
+            Some("https://cdn.example.com/morning-2.mp3")
+        );
+        assert_eq!(
+            flac.as_deref(),
+            Some("https://cdn.example.com/morning-2-raw.flac")
+        );
+        assert_eq!(
+            mp3.as_deref(),
+            Some("https://cdn.example.com/morning-2-std.mp3")
+        );
+
+        let (standard, ..) = find_media_urls(
+            ATOM_FEED,
+            "urn:uuid:evening-2",
+            IdentityStrategy::Guid,
+            "enclosure",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            standard.as_deref(),
+            Some("https://cdn.example.com/evening-2.mp3")
+        );
+
+        assert!(
+            find_media_urls(ATOM_FEED, "missing", IdentityStrategy::Guid, "enclosure")
+                .unwrap()
+                .is_none()
+        );
+
+        // Episodes keyed by the URL picked for their quality are found by that URL
+        let (standard, ..) = find_media_urls(
+            RSS_FEED,
+            "cdn.example.com/morning-2-raw.flac",
+            IdentityStrategy::NormalizedUrl,
+            "enclosure",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            standard.as_deref(),
+            Some("https://cdn.example.com/morning-2.mp3")
+        );
+    }
+
     #[test]
     fn test_parse_duration() {
         assert_eq!(parse_duration("1785"), Some(1785));
@@ -0,0 +1,63 @@
+use chrono::Local;
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use tokio::time::{interval, Duration};
+
+use crate::backup::{backup_directory, create_backup_in, list_backups, rotate_backups};
+use crate::db::settings::get_setting_int;
+use crate::state::RestoreLock;
+use crate::utils::AppResult;
+
+/// Start the automatic backup scheduler
+/// Checks every hour whether the newest backup is older than the configured interval
+pub async fn start_backup_scheduler(
+    db_pool: SqlitePool,
+    app_data_dir: PathBuf,
+    restore_lock: RestoreLock,
+) {
+    let mut ticker = interval(Duration::from_secs(60 * 60));
+
+    tracing::info!("Backup scheduler started");
+
+    loop {
+        ticker.tick().await;
+
+        let _pass = restore_lock.read().await;
+        if let Err(e) = run_scheduled_backup(&db_pool, &app_data_dir).await {
+            tracing::error!("Automatic backup failed: {}", e);
+        }
+    }
+}
+
+async fn run_scheduled_backup(db_pool: &SqlitePool, app_data_dir: &Path) -> AppResult<()> {
+    let interval_hours = get_setting_int(db_pool, "auto_backup_interval_hours", 24).await?;
+    if interval_hours <= 0 {
+        return Ok(());
+    }
+
+    let directory = backup_directory(db_pool, app_data_dir).await?;
+    let due = match list_backups(&directory).await?.first() {
+        Some(latest) => {
+            Local::now().naive_local() - latest.created_at
+                >= chrono::Duration::hours(interval_hours as i64)
+        }
+        None => true,
+    };
+    if !due {
+        return Ok(());
+    }
+
+    let path = create_backup_in(db_pool, &directory).await?;
+    let keep = get_setting_int(db_pool, "auto_backup_keep", 7)
+        .await?
+        .max(1);
+    let deleted = rotate_backups(&directory, keep as usize).await?;
+
+    tracing::info!(
+        "Automatic backup written to {} ({} old backup(s) deleted)",
+        path.display(),
+        deleted
+    );
+
+    Ok(())
+}
@@ -1,23 +1,53 @@
 use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tauri::{AppHandle, Manager};
 use tokio::sync::mpsc;
 use tokio::time::{interval, Duration};
 
-use crate::db::episodes::{count_all_episodes, episode_exists, insert_episode};
-use crate::db::models::{EpisodeDiscoveredPayload, SubscriptionCheckedPayload};
+use crate::db::episodes::{
+    count_all_episodes, get_episode_by_guid, insert_episode, mark_episode_content_updated,
+    mark_episode_skipped, rebase_episode_audio, reset_episode_for_retry, set_episode_enclosure,
+    set_episode_replaced_path,
+};
+use crate::db::feed_cache::{get_feed_cache, save_feed_cache};
+use crate::db::filters::get_episode_filter;
+use crate::db::models::{
+    Episode, EpisodeDiscoveredPayload, EpisodeUpdatedPayload, Subscription,
+    SubscriptionCheckedPayload,
+};
 use crate::db::queue::add_to_queue;
-use crate::db::subscriptions::{get_subscription, get_subscriptions_to_check, update_subscription_checked};
-use crate::download::DownloadRequest;
-use crate::rss::{fetch_rss, parse_rss_with_quality};
-use crate::utils::{build_output_path_with_format, extension_from_mime};
+use crate::db::settings::get_setting;
+use crate::db::subscriptions::{
+    clear_initial_download, get_subscription, get_subscriptions_to_check,
+    update_subscription_checked,
+};
+use crate::download::retention::enforce_retention;
+use crate::download::{episode_extension, queue_download, DownloadRequest};
+use crate::rss::filter::ItemFilter;
+use crate::rss::identity::{enclosure_change, EnclosureChange};
+use crate::rss::parser::ParsedItem;
+use crate::rss::{
+    fetch_rss_conditional, parse_rss_with_quality, FeedResponse, IdentityStrategy, InitialDownload,
+};
+use crate::state::RestoreLock;
+use crate::utils::{previous_version_path, AppError, AppResult, HttpClient};
+
+/// Updated episodes re-downloaded by one check of a subscription at most
+const MAX_REDOWNLOADS_PER_CHECK: usize = 5;
 
 /// Check a single subscription immediately (called from commands)
 pub async fn check_single_subscription_now(
     subscription_id: i64,
     db_pool: SqlitePool,
+    http_client: HttpClient,
     download_tx: mpsc::Sender<DownloadRequest>,
+    restore_lock: RestoreLock,
     app_handle: AppHandle,
 ) -> Result<(), String> {
+    // Held until the check is done, so a restore doesn't replace the database under it
+    let pass = restore_lock.read_owned().await;
+
     // Get subscription details
     let subscription = get_subscription(&db_pool, subscription_id)
         .await
@@ -27,20 +57,8 @@ pub async fn check_single_subscription_now(
 
     // Spawn task to check subscription
     tokio::spawn(async move {
-        check_subscription(
-            subscription.id,
-            subscription.name,
-            subscription.rss_url,
-            subscription.output_directory,
-            subscription.max_items_to_check,
-            subscription.max_episodes,
-            subscription.preferred_quality,
-            subscription.filename_format,
-            db_pool,
-            download_tx,
-            app_handle,
-        )
-        .await;
+        check_subscription(subscription, db_pool, http_client, download_tx, app_handle).await;
+        drop(pass);
     });
 
     Ok(())
@@ -48,7 +66,9 @@ pub async fn check_single_subscription_now(
 
 pub async fn start_feed_checker(
     db_pool: SqlitePool,
+    http_client: HttpClient,
     download_tx: mpsc::Sender<DownloadRequest>,
+    restore_lock: RestoreLock,
     app_handle: AppHandle,
 ) {
     let mut ticker = interval(Duration::from_secs(60)); // Check every minute
@@ -58,6 +78,10 @@ pub async fn start_feed_checker(
     loop {
         ticker.tick().await;
 
+        // Shared by the checks of this pass, a restore waits for all of them and the
+        // subscriptions listed here stay those of the database being checked
+        let pass = Arc::new(restore_lock.clone().read_owned().await);
+
         // Get subscriptions that need checking
         let subscriptions = match get_subscriptions_to_check(&db_pool).await {
             Ok(subs) => subs,
@@ -73,48 +97,102 @@ pub async fn start_feed_checker(
 
         for subscription in subscriptions {
             let db_pool_clone = db_pool.clone();
+            let http_client_clone = http_client.clone();
             let download_tx_clone = download_tx.clone();
             let app_handle_clone = app_handle.clone();
+            let pass = pass.clone();
 
             // Spawn task for each subscription check
             tokio::spawn(async move {
                 check_subscription(
-                    subscription.id,
-                    subscription.name.clone(),
-                    subscription.rss_url.clone(),
-                    subscription.output_directory.clone(),
-                    subscription.max_items_to_check,
-                    subscription.max_episodes,
-                    subscription.preferred_quality.clone(),
-                    subscription.filename_format.clone(),
+                    subscription,
                     db_pool_clone,
+                    http_client_clone,
                     download_tx_clone,
                     app_handle_clone,
                 )
                 .await;
+                drop(pass);
             });
         }
     }
 }
 
 async fn check_subscription(
-    subscription_id: i64,
-    subscription_name: String,
-    rss_url: String,
-    output_directory: String,
-    max_items_to_check: i32,
-    max_episodes: Option<i32>,
-    preferred_quality: String,
-    filename_format: String,
+    subscription: Subscription,
     db_pool: SqlitePool,
+    http_client: HttpClient,
     download_tx: mpsc::Sender<DownloadRequest>,
     app_handle: AppHandle,
 ) {
+    let subscription_id = subscription.id;
+    let subscription_name = subscription.name.clone();
+    let rss_url = subscription.rss_url.clone();
+    let max_items_to_check = subscription.max_items_to_check;
+    let max_episodes = subscription.max_episodes;
+    let preferred_quality = subscription.preferred_quality.clone();
+    let identity_strategy = subscription.identity_strategy.clone();
+
     tracing::info!("Checking subscription: {} ({})", subscription_name, rss_url);
 
+    let identity = IdentityStrategy::parse(&identity_strategy).unwrap_or_else(|e| {
+        tracing::warn!("{}, using GUIDs for {}", e, subscription_name);
+        IdentityStrategy::Guid
+    });
+
+    // A broken filter lets everything through rather than stopping downloads
+    let filter = get_episode_filter(&db_pool, subscription_id)
+        .await
+        .and_then(|filter| ItemFilter::new(&filter))
+        .unwrap_or_else(|e| {
+            tracing::error!("Ignoring episode filter of {}: {}", subscription_name, e);
+            ItemFilter::default()
+        });
+
+    // Files age out whether the feed changed or not, apply the retention rules on every
+    // check, before an unchanged or unreachable feed ends it
+    if let Err(e) = enforce_retention(&db_pool, subscription_id, 0).await {
+        tracing::error!(
+            "Failed to apply retention rules of {}: {}",
+            subscription_name,
+            e
+        );
+    }
+
+    // Send validators from the last fetch, if any
+    let cache = match get_feed_cache(&db_pool, subscription_id, &rss_url).await {
+        Ok(cache) => cache,
+        Err(e) => {
+            tracing::error!("Failed to load feed cache for {}: {}", subscription_name, e);
+            None
+        }
+    };
+
     // Fetch RSS feed
-    let xml = match fetch_rss(&rss_url).await {
-        Ok(xml) => xml,
+    let fetched = match fetch_rss_conditional(
+        &http_client,
+        &rss_url,
+        cache.as_ref().and_then(|c| c.etag.as_deref()),
+        cache.as_ref().and_then(|c| c.last_modified.as_deref()),
+    )
+    .await
+    {
+        Ok(FeedResponse::Modified(fetched)) => fetched,
+        Ok(FeedResponse::NotModified) => {
+            tracing::info!("Feed unchanged for subscription {}", subscription_name);
+            let _ = update_subscription_checked(&db_pool, subscription_id, 0, None).await;
+
+            let _ = app_handle.emit_all(
+                "subscription-checked",
+                SubscriptionCheckedPayload {
+                    subscription_id,
+                    new_episodes_count: 0,
+                    error: None,
+                },
+            );
+
+            return;
+        }
         Err(e) => {
             tracing::error!("Failed to fetch RSS for {}: {}", subscription_name, e);
             let _ = update_subscription_checked(&db_pool, subscription_id, 0, Some(e.to_string()))
@@ -135,7 +213,7 @@ async fn check_subscription(
     };
 
     // Parse RSS with quality preference
-    let feed = match parse_rss_with_quality(&xml, &preferred_quality) {
+    let feed = match parse_rss_with_quality(&fetched.body, &preferred_quality) {
         Ok(feed) => feed,
         Err(e) => {
             tracing::error!("Failed to parse RSS for {}: {}", subscription_name, e);
@@ -155,6 +233,20 @@ async fn check_subscription(
         }
     };
 
+    // Only cache feeds that parsed, so a broken feed isn't skipped as unchanged
+    if let Err(e) = save_feed_cache(
+        &db_pool,
+        subscription_id,
+        &rss_url,
+        fetched.etag.as_deref(),
+        fetched.last_modified.as_deref(),
+        &fetched.body,
+    )
+    .await
+    {
+        tracing::error!("Failed to save feed cache for {}: {}", subscription_name, e);
+    }
+
     let mut new_episodes_count = 0;
 
     // Calculate available download slots based on max_episodes limit
@@ -190,29 +282,43 @@ async fn check_subscription(
         usize::MAX // No limit
     };
 
-    // First pass: collect all new episodes
+    // First pass: collect all new episodes, and known ones whose audio changed
+    // New items the filter leaves out are recorded as skipped and take no slot
     let mut new_items = Vec::new();
-    for item in feed
-        .items
+    let mut updated_items = Vec::new();
+    let keys = identity.item_keys(&feed.items);
+    for (key, item) in keys
         .into_iter()
+        .zip(feed.items)
         .take(max_items_to_check as usize)
     {
-        // Check if episode already exists
-        let exists = match episode_exists(&db_pool, subscription_id, &item.guid).await {
-            Ok(exists) => exists,
+        // Check if episode already exists, by the key of the subscription's strategy
+        let existing = match get_episode_by_guid(&db_pool, subscription_id, &key).await {
+            Ok(existing) => existing,
             Err(e) => {
                 tracing::error!("Failed to check episode existence: {}", e);
                 continue;
             }
         };
 
-        if !exists {
-            new_items.push(item);
+        match existing {
+            None => match filter.rejection(&item) {
+                Some(reason) => {
+                    record_skipped_item(&db_pool, &app_handle, subscription_id, key, item, reason)
+                        .await
+                }
+                None => new_items.push((key, item)),
+            },
+            Some(episode) => match enclosure_change(&episode, &item) {
+                EnclosureChange::Unchanged => {}
+                EnclosureChange::Moved => rebase_episode(&db_pool, &episode, &item).await,
+                EnclosureChange::Replaced => updated_items.push((episode, item)),
+            },
         }
     }
 
     // Sort by pub_date DESC (most recent first), items without date go last
-    new_items.sort_by(|a, b| {
+    new_items.sort_by(|(_, a), (_, b)| {
         match (&b.pub_date, &a.pub_date) {
             (Some(date_b), Some(date_a)) => date_b.cmp(date_a),
             (Some(_), None) => std::cmp::Ordering::Less,
@@ -221,6 +327,27 @@ async fn check_subscription(
         }
     });
 
+    // The first check of a new subscription skips the archive its initial mode leaves out
+    let initial_download = InitialDownload::of_subscription(&subscription).unwrap_or_else(|e| {
+        tracing::warn!("{}, downloading everything for {}", e, subscription_name);
+        Some(InitialDownload::All)
+    });
+    if let Some(initial_download) = initial_download {
+        let (kept, skipped) = initial_download.split(new_items);
+        for (key, item, reason) in skipped {
+            record_skipped_item(&db_pool, &app_handle, subscription_id, key, item, reason).await;
+        }
+        new_items = kept;
+
+        if let Err(e) = clear_initial_download(&db_pool, subscription_id).await {
+            tracing::error!(
+                "Failed to clear initial download of {}: {}",
+                subscription_name,
+                e
+            );
+        }
+    }
+
     // Take only available_slots episodes
     let items_to_process: Vec<_> = new_items.into_iter().take(available_slots).collect();
 
@@ -237,7 +364,7 @@ async fn check_subscription(
     }
 
     // Second pass: process selected episodes
-    for item in items_to_process {
+    for (key, item) in items_to_process {
 
         // Extract enclosure (audio URL)
         let Some(enclosure) = item.enclosure else {
@@ -249,7 +376,7 @@ async fn check_subscription(
         let episode = match insert_episode(
             &db_pool,
             subscription_id,
-            item.guid.clone(),
+            key,
             item.title.clone(),
             item.description.clone(),
             item.pub_date,
@@ -259,6 +386,10 @@ async fn check_subscription(
             item.duration,
             item.image_url.clone(),
             item.author.clone(),
+            item.season,
+            item.episode_number,
+            item.standard_enclosure.as_ref().map(|e| e.url.clone()),
+            item.standard_enclosure.as_ref().and_then(|e| e.length),
         )
         .await
         {
@@ -286,45 +417,51 @@ async fn check_subscription(
             },
         );
 
-        // Build output path with custom filename format
-        let extension = enclosure
-            .mime_type
-            .as_ref()
-            .map(|mime| extension_from_mime(mime))
-            .or_else(|| {
-                crate::utils::extract_extension(&enclosure.url)
-            })
-            .unwrap_or_else(|| "mp3".to_string());
-
-        let output_path = build_output_path_with_format(
-            &output_directory,
-            &subscription_name,
-            &item.title,
-            item.pub_date,
-            &format!("{}.{}", item.title, extension),
-            &filename_format,
-        );
-
-        // Add to download queue
-        if let Err(e) = add_to_queue(&db_pool, episode.id, 0).await {
-            tracing::error!("Failed to add episode to queue: {}", e);
-            continue;
-        }
+        // Add to download queue, named after the subscription's filename format
+        let request = match queue_download(&db_pool, &subscription, &episode).await {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::error!("Failed to add episode to queue: {}", e);
+                continue;
+            }
+        };
 
         // Send download request
-        if let Err(e) = download_tx
-            .send(DownloadRequest {
-                episode_id: episode.id,
-                subscription_id,
-                url: enclosure.url.clone(),
-                output_path,
-            })
-            .await
-        {
+        if let Err(e) = download_tx.send(request).await {
             tracing::error!("Failed to send download request: {}", e);
         }
     }
 
+    if !updated_items.is_empty() {
+        let action = match get_setting(&db_pool, "updated_episode_action").await {
+            Ok(value) => UpdatedEpisodeAction::from_setting(value.as_deref()),
+            Err(e) => {
+                tracing::error!("Failed to load updated episode action: {}", e);
+                UpdatedEpisodeAction::Flag
+            }
+        };
+
+        // A publisher re-encoding the whole archive shouldn't queue all of it at once, the
+        // episodes left out are still updated in the feed on the next checks
+        let limit = match action {
+            UpdatedEpisodeAction::Flag => updated_items.len(),
+            _ => MAX_REDOWNLOADS_PER_CHECK,
+        };
+        if updated_items.len() > limit {
+            tracing::warn!(
+                "{} episodes of {} were updated, handling {} now and the rest on the next checks",
+                updated_items.len(),
+                subscription_name,
+                limit
+            );
+        }
+
+        for (episode, item) in updated_items.into_iter().take(limit) {
+            handle_updated_episode(&db_pool, &download_tx, &app_handle, action, episode, item)
+                .await;
+        }
+    }
+
     // Update subscription
     let _ = update_subscription_checked(&db_pool, subscription_id, new_episodes_count, None).await;
 
@@ -344,3 +481,240 @@ async fn check_subscription(
         new_episodes_count
     );
 }
+
+/// Record a new item left out by the subscription's filter or initial download,
+/// so it can be shown and downloaded by hand
+async fn record_skipped_item(
+    db_pool: &SqlitePool,
+    app_handle: &AppHandle,
+    subscription_id: i64,
+    key: String,
+    item: ParsedItem,
+    reason: String,
+) {
+    let Some(enclosure) = item.enclosure else {
+        return;
+    };
+
+    let episode = insert_episode(
+        db_pool,
+        subscription_id,
+        key,
+        item.title,
+        item.description,
+        item.pub_date,
+        enclosure.url,
+        enclosure.mime_type,
+        enclosure.length,
+        item.duration,
+        item.image_url,
+        item.author,
+        item.season,
+        item.episode_number,
+        item.standard_enclosure.as_ref().map(|e| e.url.clone()),
+        item.standard_enclosure.as_ref().and_then(|e| e.length),
+    )
+    .await;
+    let mut episode = match episode {
+        Ok(episode) => episode,
+        Err(e) => {
+            tracing::error!("Failed to insert skipped episode: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = mark_episode_skipped(db_pool, episode.id, &reason).await {
+        tracing::error!("Failed to mark episode {} as skipped: {}", episode.id, e);
+        return;
+    }
+    tracing::info!("Skipped episode {}: {}", episode.title, reason);
+
+    episode.download_status = "skipped".to_string();
+    episode.skip_reason = Some(reason);
+    let _ = app_handle.emit_all(
+        "episode-discovered",
+        EpisodeDiscoveredPayload {
+            subscription_id,
+            episode,
+        },
+    );
+}
+
+/// Follow a known episode to the URL its audio moved to, without marking it updated
+async fn rebase_episode(db_pool: &SqlitePool, episode: &Episode, item: &ParsedItem) {
+    let (Some(enclosure), Some(standard)) = (&item.enclosure, &item.standard_enclosure) else {
+        return;
+    };
+
+    // A running download keeps its URL, the next check picks the move up
+    if episode.download_status == "downloading" {
+        return;
+    }
+
+    let rebased: AppResult<()> = async {
+        rebase_episode_audio(
+            db_pool,
+            episode.id,
+            &enclosure.url,
+            enclosure.mime_type.as_deref(),
+            enclosure.length,
+        )
+        .await?;
+        set_episode_enclosure(db_pool, episode.id, &standard.url, standard.length).await
+    }
+    .await;
+    if let Err(e) = rebased {
+        tracing::error!("Failed to update URL of episode {}: {}", episode.id, e);
+    }
+}
+
+/// What to do with a downloaded episode whose audio was replaced in the feed,
+/// from the `updated_episode_action` setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpdatedEpisodeAction {
+    /// Only mark the episode as updated
+    Flag,
+    /// Download the new file over the previous one
+    Replace,
+    /// Rename the previous file to a versioned name, then download the new one
+    KeepPrevious,
+}
+
+impl UpdatedEpisodeAction {
+    fn from_setting(value: Option<&str>) -> Self {
+        match value {
+            Some("replace") => Self::Replace,
+            Some("keep_previous") => Self::KeepPrevious,
+            _ => Self::Flag,
+        }
+    }
+}
+
+/// Record the new audio of an updated episode, re-download it if configured
+async fn handle_updated_episode(
+    db_pool: &SqlitePool,
+    download_tx: &mpsc::Sender<DownloadRequest>,
+    app_handle: &AppHandle,
+    action: UpdatedEpisodeAction,
+    episode: Episode,
+    item: ParsedItem,
+) {
+    let Some(enclosure) = item.enclosure else {
+        return;
+    };
+
+    // A running download keeps its URL, the next check picks the change up
+    if episode.download_status == "downloading" {
+        return;
+    }
+
+    tracing::info!(
+        "Episode updated in feed: {} ({} -> {})",
+        episode.title,
+        episode.audio_url,
+        enclosure.url
+    );
+
+    let mut updated = match mark_episode_content_updated(
+        db_pool,
+        episode.id,
+        &enclosure.url,
+        enclosure.mime_type.as_deref(),
+        enclosure.length,
+        item.duration,
+    )
+    .await
+    {
+        Ok(updated) => updated,
+        Err(e) => {
+            tracing::error!("Failed to update episode {}: {}", episode.id, e);
+            return;
+        }
+    };
+
+    // The next checks compare against the new file
+    if let Some(standard) = item.standard_enclosure {
+        if let Err(e) =
+            set_episode_enclosure(db_pool, episode.id, &standard.url, standard.length).await
+        {
+            tracing::error!(
+                "Failed to record enclosure of episode {}: {}",
+                episode.id,
+                e
+            );
+        }
+        updated.enclosure_url = Some(standard.url);
+        updated.enclosure_length = standard.length;
+    }
+
+    // Episodes not downloaded yet simply fetch the new URL when their turn comes
+    let previous_path = episode
+        .download_path
+        .as_ref()
+        .filter(|_| episode.download_status == "completed")
+        .map(PathBuf::from);
+
+    let redownload = match (action, previous_path) {
+        (UpdatedEpisodeAction::Flag, _) | (_, None) => false,
+        (action, Some(previous_path)) => {
+            match queue_redownload(db_pool, download_tx, action, &updated, &previous_path).await {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::error!("Failed to re-download episode {}: {}", episode.id, e);
+                    false
+                }
+            }
+        }
+    };
+
+    let _ = app_handle.emit_all(
+        "episode-updated",
+        EpisodeUpdatedPayload {
+            subscription_id: episode.subscription_id,
+            episode: updated,
+            redownload,
+        },
+    );
+}
+
+/// Queue the new audio of an updated episode under the name of the previous file
+async fn queue_redownload(
+    db_pool: &SqlitePool,
+    download_tx: &mpsc::Sender<DownloadRequest>,
+    action: UpdatedEpisodeAction,
+    episode: &Episode,
+    previous_path: &Path,
+) -> AppResult<()> {
+    let output_path = previous_path.with_extension(episode_extension(episode));
+
+    if action == UpdatedEpisodeAction::KeepPrevious && previous_path.exists() {
+        let version_path = previous_version_path(previous_path);
+        tokio::fs::rename(previous_path, &version_path).await?;
+        tracing::info!("Kept previous version as {}", version_path.display());
+    } else if output_path != previous_path {
+        // The new file has another extension and won't overwrite the previous one, which
+        // stays until the new one is downloaded
+        let previous_path = previous_path.display().to_string();
+        set_episode_replaced_path(db_pool, episode.id, Some(&previous_path)).await?;
+    }
+
+    reset_episode_for_retry(db_pool, episode.id).await?;
+    add_to_queue(
+        db_pool,
+        episode.id,
+        Some(&output_path.display().to_string()),
+    )
+    .await?;
+
+    download_tx
+        .send(DownloadRequest {
+            episode_id: episode.id,
+            subscription_id: episode.subscription_id,
+            url: episode.audio_url.clone(),
+            output_path,
+        })
+        .await
+        .map_err(|e| AppError::Other(format!("Failed to send download request: {}", e)))?;
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
+pub mod backup_scheduler;
 pub mod feed_checker;
 pub mod update_checker;
 
+pub use backup_scheduler::start_backup_scheduler;
 pub use feed_checker::start_feed_checker;
 pub use update_checker::start_update_checker;
@@ -4,15 +4,16 @@ use tokio::time::sleep;
 use tracing::{error, info};
 
 use crate::updater::check_for_updates;
+use crate::utils::HttpClient;
 
 /// Start the automatic update checker
 /// Checks for updates on startup and every 6 hours thereafter
-pub async fn start_update_checker(app_handle: AppHandle) {
+pub async fn start_update_checker(app_handle: AppHandle, http_client: HttpClient) {
     info!("Starting automatic update checker (every 6 hours)");
 
     loop {
         // Check for updates
-        match check_for_updates().await {
+        match check_for_updates(&http_client).await {
             Ok(update_info) => {
                 if update_info.update_available {
                     info!(
@@ -1,19 +1,37 @@
 use sqlx::SqlitePool;
-use tokio::sync::mpsc;
+use std::sync::Arc;
+use tokio::sync::{mpsc, RwLock};
 
-use crate::download::DownloadRequest;
+use crate::download::{DownloadControl, DownloadRequest};
+use crate::utils::HttpClient;
+
+/// Held for writing while a backup is restored, background tasks hold it for reading
+/// during each pass so none of them writes to the database being replaced
+pub type RestoreLock = Arc<RwLock<()>>;
 
 /// Global application state shared across all Tauri commands
 pub struct AppState {
     pub db_pool: SqlitePool,
     pub download_tx: mpsc::Sender<DownloadRequest>,
+    pub download_control: DownloadControl,
+    pub http_client: HttpClient,
+    pub restore_lock: RestoreLock,
 }
 
 impl AppState {
-    pub fn new(db_pool: SqlitePool, download_tx: mpsc::Sender<DownloadRequest>) -> Self {
+    pub fn new(
+        db_pool: SqlitePool,
+        download_tx: mpsc::Sender<DownloadRequest>,
+        download_control: DownloadControl,
+        http_client: HttpClient,
+        restore_lock: RestoreLock,
+    ) -> Self {
         Self {
             db_pool,
             download_tx,
+            download_control,
+            http_client,
+            restore_lock,
         }
     }
 }
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::utils::HttpClient;
+
 const GITHUB_REPO: &str = "Synapsr/PodcastSync";
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -20,13 +22,11 @@ struct GitHubRelease {
 }
 
 /// Check for updates on GitHub
-pub async fn check_for_updates() -> Result<UpdateInfo, String> {
+pub async fn check_for_updates(http: &HttpClient) -> Result<UpdateInfo, String> {
     let url = format!("https://api.github.com/repos/{}/releases/latest", GITHUB_REPO);
 
-    let client = reqwest::Client::new();
-    let response = client
+    let response = http
         .get(&url)
-        .header("User-Agent", "PodcastSync")
         .send()
         .await
         .map_err(|e| format!("Failed to fetch update info: {}", e))?;
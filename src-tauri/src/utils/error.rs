@@ -11,9 +11,21 @@ pub enum AppError {
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
 
+    #[error("HTTP error {0}: Failed to download file")]
+    HttpStatus(reqwest::StatusCode),
+
     #[error("RSS parsing error: {0}")]
     RssParsing(#[from] rss::Error),
 
+    #[error("Atom parsing error: {0}")]
+    AtomParsing(#[from] atom_syndication::Error),
+
+    #[error("OPML parsing error: {0}")]
+    OpmlParsing(#[from] quick_xml::Error),
+
+    #[error("Tagging error: {0}")]
+    Tagging(#[from] lofty::error::LoftyError),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -26,6 +38,15 @@ pub enum AppError {
     #[error("Download cancelled")]
     DownloadCancelled,
 
+    #[error("Downloaded file size mismatch: expected {expected} bytes, got {actual} bytes")]
+    SizeMismatch { expected: u64, actual: u64 },
+
+    #[error("Not enough disk space: {required} bytes needed, {available} bytes free")]
+    InsufficientSpace { required: u64, available: u64 },
+
+    #[error("Episode of {size} bytes is larger than the storage quota of {quota} bytes")]
+    StorageQuota { size: u64, quota: u64 },
+
     #[error("{0}")]
     Other(String),
 }
@@ -1,5 +1,6 @@
-use chrono::{DateTime, Utc};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::utils::template::{Template, TemplateValues};
 
 /// Sanitize a filename by removing/replacing invalid characters
 pub fn sanitize_filename(name: &str) -> String {
@@ -20,78 +21,52 @@ pub fn sanitize_filename(name: &str) -> String {
     sanitized.chars().take(200).collect()
 }
 
-/// Apply filename format with variables: {show}, {episode}, {date}
-pub fn apply_filename_format(
-    format: &str,
-    subscription_name: &str,
-    episode_title: &str,
-    pub_date: Option<DateTime<Utc>>,
-) -> String {
-    let sanitized_show = sanitize_filename(subscription_name);
-    let sanitized_episode = sanitize_filename(episode_title);
-    let date_str = pub_date
-        .map(|d| d.format("%Y-%m-%d").to_string())
-        .unwrap_or_else(|| "unknown-date".to_string());
-
-    format
-        .replace("{show}", &sanitized_show)
-        .replace("{episode}", &sanitized_episode)
-        .replace("{date}", &date_str)
-}
+/// Format used when a subscription's format can't be parsed
+const FALLBACK_FILENAME_FORMAT: &str = "{show}/{show} - {episode}";
 
 /// Build output path for an episode with custom filename format
-/// Format can use: {show}, {episode}, {date}
-/// Examples: "{show}-{episode}", "{episode}", "{date}_{episode}", etc.
+/// See `Template` for the format syntax, `/` creates subfolders of `base_directory`
+/// Examples: "{show}/{episode}", "{radio_slug}/{date:%Y/%m}/{episode|max:80}"
 pub fn build_output_path_with_format(
     base_directory: &str,
-    subscription_name: &str,
-    episode_title: &str,
-    pub_date: Option<DateTime<Utc>>,
-    audio_url: &str,
     filename_format: &str,
+    values: &TemplateValues,
+    extension: &str,
 ) -> PathBuf {
-    let sanitized_sub_name = sanitize_filename(subscription_name);
-
-    // Apply custom format
-    let filename_base = apply_filename_format(
-        filename_format,
-        subscription_name,
-        episode_title,
-        pub_date,
-    );
-
-    let extension = extract_extension(audio_url).unwrap_or("mp3".to_string());
-    let filename = format!("{}.{}", filename_base, extension);
-
     let mut path = PathBuf::from(base_directory);
-    path.push(&sanitized_sub_name);
-    path.push(&filename);
+    path.push(relative_output_path(filename_format, values, extension));
 
     // Handle duplicates
-    if path.exists() {
-        path = handle_duplicate(path);
-    }
-
-    path
+    available_path(path)
 }
 
-/// Build output path for an episode (legacy, uses default format)
-pub fn build_output_path(
-    base_directory: &str,
-    subscription_name: &str,
-    episode_title: &str,
-    pub_date: Option<DateTime<Utc>>,
-    audio_url: &str,
+/// Path of an episode inside the output directory
+pub fn relative_output_path(
+    filename_format: &str,
+    values: &TemplateValues,
+    extension: &str,
 ) -> PathBuf {
-    // Use default format: {show}-{episode}
-    build_output_path_with_format(
-        base_directory,
-        subscription_name,
-        episode_title,
-        pub_date,
-        audio_url,
-        "{show}-{episode}",
-    )
+    let template = Template::parse_path(filename_format).unwrap_or_else(|e| {
+        tracing::warn!("{}, using \"{}\"", e, FALLBACK_FILENAME_FORMAT);
+        Template::parse_path(FALLBACK_FILENAME_FORMAT).expect("valid fallback format")
+    });
+
+    // Everything rendered empty
+    let mut relative_path = template.render_path(values);
+    if relative_path.as_os_str().is_empty() {
+        let title = sanitize_filename(values.episode);
+        relative_path.push(if title.is_empty() {
+            format!("episode-{}", values.id)
+        } else {
+            title
+        });
+    }
+
+    let mut relative_path = relative_path.into_os_string();
+    relative_path.push(".");
+    relative_path.push(extension);
+
+    PathBuf::from(relative_path)
 }
 
 /// Extract file extension from URL or MIME type
@@ -107,18 +82,45 @@ pub fn extract_extension(url: &str) -> Option<String> {
     None
 }
 
-/// Get extension from MIME type
-pub fn extension_from_mime(mime_type: &str) -> String {
-    match mime_type {
-        "audio/mpeg" => "mp3",
-        "audio/mp4" => "m4a",
-        "audio/ogg" => "ogg",
-        "audio/wav" => "wav",
-        "audio/flac" => "flac",
-        "audio/aac" => "aac",
-        _ => "mp3", // default
+/// Get extension from MIME type, None for unknown and generic types
+/// Parameters are ignored except `codecs`, Ogg files holding Opus get `opus`
+pub fn extension_from_mime(mime_type: &str) -> Option<String> {
+    let mut parameters = mime_type.split(';');
+    let essence = parameters.next().unwrap_or_default().trim().to_lowercase();
+    let opus_codec = parameters.any(|parameter| {
+        let parameter = parameter.trim().to_lowercase();
+        parameter.starts_with("codecs=") && parameter.contains("opus")
+    });
+
+    let extension = match essence.as_str() {
+        "audio/mpeg" | "audio/mp3" | "audio/mpeg3" | "audio/x-mpeg" | "audio/x-mp3" => "mp3",
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" | "audio/x-m4b" => "m4a",
+        "video/mp4" => "mp4",
+        "video/x-m4v" => "m4v",
+        "video/quicktime" => "mov",
+        "audio/ogg" | "application/ogg" | "audio/vorbis" if opus_codec => "opus",
+        "audio/ogg" | "application/ogg" | "audio/vorbis" => "ogg",
+        "audio/opus" => "opus",
+        "audio/webm" | "video/webm" => "webm",
+        "audio/x-matroska" => "mka",
+        "video/x-matroska" => "mkv",
+        "audio/wav" | "audio/x-wav" | "audio/wave" | "audio/vnd.wave" => "wav",
+        "audio/flac" | "audio/x-flac" => "flac",
+        "audio/aiff" | "audio/x-aiff" => "aiff",
+        "audio/aac" | "audio/aacp" | "audio/x-aac" => "aac",
+        _ => return None,
+    };
+
+    Some(extension.to_string())
+}
+
+/// `path`, or a numbered variant of it when the file already exists
+pub fn available_path(path: PathBuf) -> PathBuf {
+    if path.exists() {
+        handle_duplicate(path)
+    } else {
+        path
     }
-    .to_string()
 }
 
 /// Handle duplicate filenames by appending a number
@@ -143,6 +145,29 @@ fn handle_duplicate(path: PathBuf) -> PathBuf {
     }
 }
 
+/// Free path to keep the previous version of a file: "name.v1.ext", "name.v2.ext"...
+pub fn previous_version_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned());
+
+    let mut version = 1;
+    loop {
+        let file_name = match &extension {
+            Some(extension) => format!("{}.v{}.{}", stem, version, extension),
+            None => format!("{}.v{}", stem, version),
+        };
+
+        let candidate = path.with_file_name(file_name);
+        if !candidate.exists() {
+            return candidate;
+        }
+        version += 1;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,10 +196,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_previous_version_path() {
+        assert_eq!(
+            previous_version_path(Path::new("/nonexistent/show/episode.mp3")),
+            PathBuf::from("/nonexistent/show/episode.v1.mp3")
+        );
+        assert_eq!(
+            previous_version_path(Path::new("/nonexistent/show/episode")),
+            PathBuf::from("/nonexistent/show/episode.v1")
+        );
+    }
+
+    #[test]
+    fn test_build_output_path_with_format() {
+        let values = TemplateValues {
+            show: "Morning Show",
+            episode: "News",
+            id: 3,
+            ..TemplateValues::default()
+        };
+
+        assert_eq!(
+            build_output_path_with_format("/nonexistent", "{show}/{episode}", &values, "mp3"),
+            PathBuf::from("/nonexistent/Morning Show/News.mp3")
+        );
+        assert_eq!(
+            build_output_path_with_format("/nonexistent", "{episode}", &values, "m4a"),
+            PathBuf::from("/nonexistent/News.m4a")
+        );
+        assert_eq!(
+            build_output_path_with_format("/nonexistent", "{automation}", &values, "mp3"),
+            PathBuf::from("/nonexistent/News.mp3")
+        );
+        assert_eq!(
+            build_output_path_with_format("/nonexistent", "{show", &values, "mp3"),
+            PathBuf::from("/nonexistent/Morning Show/Morning Show - News.mp3")
+        );
+    }
+
     #[test]
     fn test_extension_from_mime() {
-        assert_eq!(extension_from_mime("audio/mpeg"), "mp3");
-        assert_eq!(extension_from_mime("audio/mp4"), "m4a");
-        assert_eq!(extension_from_mime("audio/unknown"), "mp3");
+        let extension = |mime| extension_from_mime(mime);
+        assert_eq!(extension("audio/mpeg").as_deref(), Some("mp3"));
+        assert_eq!(extension("audio/mp4").as_deref(), Some("m4a"));
+        assert_eq!(extension("Audio/X-M4A").as_deref(), Some("m4a"));
+        assert_eq!(extension("video/mp4").as_deref(), Some("mp4"));
+        assert_eq!(extension("audio/ogg").as_deref(), Some("ogg"));
+        assert_eq!(extension("audio/ogg; codecs=opus").as_deref(), Some("opus"));
+        assert_eq!(extension("audio/webm").as_deref(), Some("webm"));
+        assert_eq!(extension("audio/unknown"), None);
+        assert_eq!(extension("application/octet-stream"), None);
     }
 }
@@ -0,0 +1,237 @@
+use reqwest::redirect::Policy;
+use reqwest::{Certificate, Client, Proxy, RequestBuilder};
+use sqlx::SqlitePool;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::db::settings::{get_setting, get_setting_int};
+use crate::utils::{AppError, AppResult};
+
+/// Settings used to build the HTTP client, rebuilt live when changed
+pub const HTTP_SETTING_KEYS: [&str; 6] = [
+    "http_connect_timeout_seconds",
+    "http_read_timeout_seconds",
+    "http_user_agent",
+    "http_proxy_url",
+    "http_ca_certificates",
+    "http_max_redirects",
+];
+
+const DEFAULT_USER_AGENT: &str = concat!("PodcastSync/", env!("CARGO_PKG_VERSION"));
+
+/// Options shared by every network request
+#[derive(Debug, Clone)]
+pub struct HttpSettings {
+    pub connect_timeout: Duration,
+    /// Longest wait for data: bounds whole feed and API requests,
+    /// and the silence between two chunks of a download
+    pub read_timeout: Duration,
+    pub user_agent: String,
+    /// http://, https://, socks5:// or socks5h:// proxy for all requests
+    pub proxy_url: Option<String>,
+    /// PEM files with extra trusted CA certificates
+    pub ca_certificates: Vec<PathBuf>,
+    pub max_redirects: usize,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(60),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            proxy_url: None,
+            ca_certificates: Vec::new(),
+            max_redirects: 10,
+        }
+    }
+}
+
+impl HttpSettings {
+    /// Load HTTP options from settings, falling back to defaults for missing keys
+    pub async fn load(pool: &SqlitePool) -> AppResult<Self> {
+        let defaults = Self::default();
+
+        let connect_timeout = get_setting_int(
+            pool,
+            "http_connect_timeout_seconds",
+            defaults.connect_timeout.as_secs() as i32,
+        )
+        .await?;
+        let read_timeout = get_setting_int(
+            pool,
+            "http_read_timeout_seconds",
+            defaults.read_timeout.as_secs() as i32,
+        )
+        .await?;
+        let max_redirects =
+            get_setting_int(pool, "http_max_redirects", defaults.max_redirects as i32).await?;
+        let user_agent =
+            non_empty(get_setting(pool, "http_user_agent").await?).unwrap_or(defaults.user_agent);
+        let proxy_url = non_empty(get_setting(pool, "http_proxy_url").await?);
+        let ca_certificates = get_setting(pool, "http_ca_certificates")
+            .await?
+            .map(|value| parse_path_list(&value))
+            .unwrap_or_default();
+
+        Ok(Self {
+            connect_timeout: Duration::from_secs(connect_timeout.max(1) as u64),
+            read_timeout: Duration::from_secs(read_timeout.max(1) as u64),
+            user_agent,
+            proxy_url,
+            ca_certificates,
+            max_redirects: max_redirects.max(0) as usize,
+        })
+    }
+
+    /// Build a reqwest client with these options
+    pub fn build_client(&self) -> AppResult<Client> {
+        let redirect = match self.max_redirects {
+            0 => Policy::none(),
+            limit => Policy::limited(limit),
+        };
+
+        let mut builder = Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .user_agent(&self.user_agent)
+            .redirect(redirect);
+
+        if let Some(proxy_url) = &self.proxy_url {
+            let proxy = Proxy::all(proxy_url).map_err(|e| {
+                AppError::InvalidInput(format!("Invalid proxy URL '{}': {}", proxy_url, e))
+            })?;
+            builder = builder.proxy(proxy);
+        }
+
+        for path in &self.ca_certificates {
+            let pem = std::fs::read(path).map_err(|e| {
+                AppError::InvalidInput(format!(
+                    "Failed to read CA certificate {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            let certificates = Certificate::from_pem_bundle(&pem).map_err(|e| {
+                AppError::InvalidInput(format!("Invalid CA certificate {}: {}", path.display(), e))
+            })?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// Shared HTTP client used by every network path, rebuilt when settings change
+#[derive(Clone)]
+pub struct HttpClient {
+    inner: Arc<RwLock<(Client, HttpSettings)>>,
+}
+
+impl HttpClient {
+    pub fn new(settings: HttpSettings) -> AppResult<Self> {
+        let client = settings.build_client()?;
+        Ok(Self {
+            inner: Arc::new(RwLock::new((client, settings))),
+        })
+    }
+
+    /// Build the client from settings, using defaults if they are invalid
+    pub async fn load(pool: &SqlitePool) -> Self {
+        let client = match HttpSettings::load(pool).await {
+            Ok(settings) => Self::new(settings),
+            Err(e) => Err(e),
+        };
+
+        client.unwrap_or_else(|e| {
+            tracing::error!("Invalid HTTP settings, using defaults: {}", e);
+            Self::new(HttpSettings::default()).expect("Failed to build default HTTP client")
+        })
+    }
+
+    /// Rebuild the client after settings changed
+    /// The previous client is kept if the new settings are invalid
+    pub async fn reload(&self, pool: &SqlitePool) -> AppResult<()> {
+        let settings = HttpSettings::load(pool).await?;
+        let client = settings.build_client()?;
+
+        if let Ok(mut inner) = self.inner.write() {
+            *inner = (client, settings);
+        }
+
+        Ok(())
+    }
+
+    /// The underlying client, cheap to clone
+    pub fn client(&self) -> Client {
+        match self.inner.read() {
+            Ok(inner) => inner.0.clone(),
+            Err(poisoned) => poisoned.into_inner().0.clone(),
+        }
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        match self.inner.read() {
+            Ok(inner) => inner.1.read_timeout,
+            Err(poisoned) => poisoned.into_inner().1.read_timeout,
+        }
+    }
+
+    /// GET request for a small response (feed, API call), bounded by the read timeout
+    /// Large downloads use `client()` and time out on stalled chunks instead
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client().get(url).timeout(self.read_timeout())
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Paths separated by new lines or semicolons
+fn parse_path_list(value: &str) -> Vec<PathBuf> {
+    value
+        .split(['\n', ';'])
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path_list() {
+        assert_eq!(
+            parse_path_list("/etc/ca.pem; /opt/proxy.pem\n\n"),
+            vec![
+                PathBuf::from("/etc/ca.pem"),
+                PathBuf::from("/opt/proxy.pem")
+            ]
+        );
+        assert!(parse_path_list("  ").is_empty());
+    }
+
+    #[test]
+    fn test_build_client() {
+        assert!(HttpSettings::default().build_client().is_ok());
+
+        let with_proxy = HttpSettings {
+            proxy_url: Some("http://proxy.local:3128".to_string()),
+            ..HttpSettings::default()
+        };
+        assert!(with_proxy.build_client().is_ok());
+
+        let missing_certificate = HttpSettings {
+            ca_certificates: vec![PathBuf::from("/nonexistent/ca.pem")],
+            ..HttpSettings::default()
+        };
+        assert!(missing_certificate.build_client().is_err());
+    }
+}
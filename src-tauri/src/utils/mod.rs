@@ -1,5 +1,11 @@
 pub mod error;
 pub mod file_naming;
+pub mod http;
+pub mod template;
 
 pub use error::{AppError, AppResult};
-pub use file_naming::{build_output_path, build_output_path_with_format, extract_extension, extension_from_mime};
+pub use file_naming::{
+    available_path, build_output_path_with_format, extension_from_mime, extract_extension,
+    previous_version_path, relative_output_path,
+};
+pub use http::HttpClient;
@@ -0,0 +1,437 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+
+use crate::db::models::{Episode, Subscription};
+use crate::utils::file_naming::sanitize_filename;
+use crate::utils::{AppError, AppResult};
+
+/// Placeholders available in filename and tag formats
+pub const VARIABLES: &[&str] = &[
+    "show",
+    "episode",
+    "date",
+    "year",
+    "guid",
+    "id",
+    "season",
+    "episode_number",
+    "radio_slug",
+    "automation",
+    "duration",
+    "program",
+    "description",
+];
+
+/// Extra placeholders of hook command arguments
+pub const HOOK_VARIABLES: &[&str] = &["path", "status", "error"];
+
+/// Values a template is rendered with
+#[derive(Debug, Clone, Default)]
+pub struct TemplateValues<'a> {
+    pub show: &'a str,
+    pub episode: &'a str,
+    pub guid: &'a str,
+    pub id: i64,
+    pub pub_date: Option<DateTime<Utc>>,
+    pub season: Option<i32>,
+    pub episode_number: Option<i32>,
+    pub radio_slug: Option<&'a str>,
+    pub automation: Option<&'a str>,
+    pub duration: Option<i32>,
+    pub program: Option<&'a str>,
+    pub description: Option<&'a str>,
+    /// Hook commands only
+    pub path: Option<&'a str>,
+    pub status: Option<&'a str>,
+    pub error: Option<&'a str>,
+}
+
+impl<'a> TemplateValues<'a> {
+    pub fn new(subscription: &'a Subscription, episode: &'a Episode) -> Self {
+        Self {
+            show: &subscription.name,
+            episode: &episode.title,
+            guid: &episode.guid,
+            id: episode.id,
+            pub_date: episode.pub_date,
+            season: episode.season,
+            episode_number: episode.episode_number,
+            radio_slug: subscription.radio_slug.as_deref(),
+            automation: subscription.automation_name.as_deref(),
+            duration: episode.duration_seconds,
+            program: episode.program_name.as_deref(),
+            description: episode.description.as_deref(),
+            ..Self::default()
+        }
+    }
+}
+
+/// A parsed format such as `{show}/{date:%Y/%m}/{episode|max:60}`
+///
+/// A placeholder is `{name}`, optionally with a format after a colon (strftime for
+/// `date`, zero padding width for numbers, e.g. `{season:02}`) and filters after
+/// pipes: `max:N`, `upper`, `lower`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Placeholder {
+    variable: String,
+    format: Option<String>,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Max(usize),
+    Upper,
+    Lower,
+}
+
+impl Template {
+    pub fn parse(format: &str) -> AppResult<Self> {
+        Self::parse_with(format, &[])
+    }
+
+    /// Parse a format that may also use the given extra variables
+    pub fn parse_with(format: &str, extra_variables: &[&str]) -> AppResult<Self> {
+        let mut parts = Vec::new();
+        let mut rest = format;
+
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return Err(invalid(format, "unexpected '}'"));
+            }
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+
+            let Some(end) = rest[start..].find('}') else {
+                return Err(invalid(format, "missing '}'"));
+            };
+            let placeholder = &rest[start + 1..start + end];
+            if placeholder.contains('{') {
+                return Err(invalid(format, "missing '}'"));
+            }
+
+            parts.push(Part::Placeholder(
+                parse_placeholder(placeholder, extra_variables).map_err(|reason| {
+                    invalid(format, &format!("{{{}}}: {}", placeholder, reason))
+                })?,
+            ));
+            rest = &rest[start + end + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Parse a filename format, which must end with a file name
+    pub fn parse_path(format: &str) -> AppResult<Self> {
+        let trimmed = format.trim();
+        if trimmed.is_empty() || trimmed.ends_with('/') {
+            return Err(invalid(format, "must end with a file name"));
+        }
+
+        Self::parse(trimmed)
+    }
+
+    /// Render as plain text, for tags
+    pub fn render_text(&self, values: &TemplateValues) -> String {
+        self.render(values, false).trim().to_string()
+    }
+
+    /// Render as a relative path, `/` separates folders
+    /// Values can't add folders, except the `/` of a date format
+    pub fn render_path(&self, values: &TemplateValues) -> PathBuf {
+        self.render(values, true)
+            .split('/')
+            .map(sanitize_filename)
+            .filter(|segment| !segment.is_empty() && segment != "." && segment != "..")
+            .collect()
+    }
+
+    fn render(&self, values: &TemplateValues, path: bool) -> String {
+        let mut rendered = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => rendered.push_str(text),
+                Part::Placeholder(placeholder) => {
+                    let mut value = placeholder.value(values);
+                    for filter in &placeholder.filters {
+                        value = filter.apply(value);
+                    }
+
+                    if !path {
+                        rendered.push_str(&value);
+                    } else if placeholder.variable == "date" {
+                        // A date format may contain `/` on purpose
+                        let segments: Vec<String> =
+                            value.split('/').map(sanitize_filename).collect();
+                        rendered.push_str(&segments.join("/"));
+                    } else {
+                        rendered.push_str(&sanitize_filename(&value));
+                    }
+                }
+            }
+        }
+
+        rendered
+    }
+}
+
+impl Placeholder {
+    fn value(&self, values: &TemplateValues) -> String {
+        let number = |number: Option<i64>| {
+            let width = self
+                .format
+                .as_deref()
+                .and_then(|width| width.parse().ok())
+                .unwrap_or(0);
+            number
+                .map(|number| format!("{:0width$}", number, width = width))
+                .unwrap_or_default()
+        };
+
+        match self.variable.as_str() {
+            "show" => values.show.to_string(),
+            "episode" => values.episode.to_string(),
+            "date" => values
+                .pub_date
+                .map(|date| {
+                    date.format(self.format.as_deref().unwrap_or("%Y-%m-%d"))
+                        .to_string()
+                })
+                .unwrap_or_else(|| "unknown-date".to_string()),
+            "year" => values
+                .pub_date
+                .map(|date| date.format("%Y").to_string())
+                .unwrap_or_default(),
+            "guid" => values.guid.to_string(),
+            "id" => number(Some(values.id)),
+            "season" => number(values.season.map(i64::from)),
+            "episode_number" => number(values.episode_number.map(i64::from)),
+            "radio_slug" => values.radio_slug.unwrap_or_default().to_string(),
+            "automation" => values.automation.unwrap_or_default().to_string(),
+            "duration" => values.duration.map(format_duration).unwrap_or_default(),
+            "program" => values
+                .program
+                .filter(|program| !program.trim().is_empty())
+                .unwrap_or(values.show)
+                .to_string(),
+            "description" => values.description.map(plain_text).unwrap_or_default(),
+            "path" => values.path.unwrap_or_default().to_string(),
+            "status" => values.status.unwrap_or_default().to_string(),
+            "error" => values.error.unwrap_or_default().to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl Filter {
+    fn apply(&self, value: String) -> String {
+        match self {
+            Filter::Max(max) => value
+                .chars()
+                .take(*max)
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+        }
+    }
+}
+
+fn parse_placeholder(placeholder: &str, extra_variables: &[&str]) -> Result<Placeholder, String> {
+    let mut pieces = placeholder.split('|');
+    let head = pieces.next().unwrap_or_default();
+    let (variable, format) = match head.split_once(':') {
+        Some((variable, format)) => (variable.trim(), Some(format.to_string())),
+        None => (head.trim(), None),
+    };
+
+    if !VARIABLES.contains(&variable) && !extra_variables.contains(&variable) {
+        return Err(format!("unknown variable '{}'", variable));
+    }
+
+    if let Some(format) = &format {
+        match variable {
+            "date" => {
+                if format.is_empty()
+                    || StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+                {
+                    return Err(format!("invalid date format '{}'", format));
+                }
+            }
+            "id" | "season" | "episode_number" => {
+                if format.parse::<u8>().is_err() {
+                    return Err(format!("invalid width '{}'", format));
+                }
+            }
+            _ => return Err(format!("'{}' doesn't take a format", variable)),
+        }
+    }
+
+    let filters = pieces
+        .map(|filter| match filter.trim().split_once(':') {
+            Some(("max", max)) => max
+                .trim()
+                .parse()
+                .ok()
+                .filter(|max| *max > 0)
+                .map(Filter::Max)
+                .ok_or_else(|| format!("invalid length '{}'", max)),
+            None if filter.trim() == "upper" => Ok(Filter::Upper),
+            None if filter.trim() == "lower" => Ok(Filter::Lower),
+            _ => Err(format!("unknown filter '{}'", filter.trim())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Placeholder {
+        variable: variable.to_string(),
+        format,
+        filters,
+    })
+}
+
+fn invalid(format: &str, reason: &str) -> AppError {
+    AppError::InvalidInput(format!("Invalid format \"{}\": {}", format, reason))
+}
+
+/// Duration like 1h02m03s, or 42m10s under an hour
+fn format_duration(seconds: i32) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else {
+        format!("{}m{:02}s", minutes, seconds)
+    }
+}
+
+/// Text without HTML markup
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.replace("&amp;", "&")
+        .replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn values() -> TemplateValues<'static> {
+        TemplateValues {
+            show: "Morning: Show",
+            episode: "News / Weather",
+            guid: "https://example.com/ep/42",
+            id: 7,
+            pub_date: Some(Utc.with_ymd_and_hms(2024, 3, 5, 8, 0, 0).unwrap()),
+            season: Some(2),
+            episode_number: Some(5),
+            radio_slug: Some("lumy"),
+            duration: Some(3723),
+            description: Some("<p>Guests &amp; <b>news</b></p>"),
+            ..TemplateValues::default()
+        }
+    }
+
+    #[test]
+    fn test_render_path() {
+        let render = |format: &str| Template::parse_path(format).unwrap().render_path(&values());
+
+        assert_eq!(
+            render("{show}/{show} - {episode}"),
+            PathBuf::from("Morning_ Show/Morning_ Show - News _ Weather")
+        );
+        assert_eq!(
+            render("{radio_slug}/{date:%Y/%m}/S{season:02}E{episode_number:02} {duration}"),
+            PathBuf::from("lumy/2024/03/S02E05 1h02m03s")
+        );
+        assert_eq!(
+            render("{show|upper|max:7}_{id:4}_{automation}_{guid}"),
+            PathBuf::from("MORNING_0007__https___example.com_ep_42")
+        );
+        assert_eq!(
+            render("../{episode|lower}"),
+            PathBuf::from("news _ weather")
+        );
+    }
+
+    #[test]
+    fn test_render_text() {
+        let template = Template::parse("{program}: {description|max:8} ({year})").unwrap();
+        assert_eq!(
+            template.render_text(&values()),
+            "Morning: Show: Guests & (2024)"
+        );
+
+        let template = Template::parse("{episode} ").unwrap();
+        assert_eq!(template.render_text(&values()), "News / Weather");
+
+        let template = Template::parse("{date}").unwrap();
+        let values = TemplateValues {
+            pub_date: None,
+            ..values()
+        };
+        assert_eq!(template.render_text(&values), "unknown-date");
+    }
+
+    #[test]
+    fn test_invalid_formats() {
+        for format in [
+            "{show",
+            "show}",
+            "{{show}}",
+            "{title}",
+            "{date:%Q}",
+            "{show:02}",
+            "{season:x}",
+            "{episode|max:0}",
+            "{episode|capitalize}",
+        ] {
+            assert!(Template::parse(format).is_err(), "{}", format);
+        }
+
+        assert!(Template::parse_path("{show}/").is_err());
+        assert!(Template::parse_path("  ").is_err());
+        assert!(Template::parse("").is_ok());
+
+        assert!(Template::parse("{path}").is_err());
+        assert!(Template::parse_with("{path}", HOOK_VARIABLES).is_ok());
+    }
+}
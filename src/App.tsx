@@ -8,7 +8,7 @@ import { useAudioPlayerStore } from './stores/useAudioPlayerStore'
 import { formatBytes } from './lib/utils'
 import { listen } from '@tauri-apps/api/event'
 import type { DownloadStartedPayload, DownloadProgressPayload, DownloadCompletedPayload, DownloadFailedPayload } from './types/download'
-import type { EpisodeDiscoveredPayload, SubscriptionCheckedPayload } from './types/events'
+import type { EpisodeDiscoveredPayload, EpisodeUpdatedPayload, SubscriptionCheckedPayload } from './types/events'
 import type { Subscription } from './types/subscription'
 import { Plus, RefreshCw, Trash2, FolderOpen, Pencil, ArrowLeft, Download, CheckCircle, Clock, XCircle, Play, MoreVertical, FolderIcon, Pause, Info } from 'lucide-react'
 import { fsApi, subscriptionApi, episodeApi } from './lib/api'
@@ -20,6 +20,7 @@ import { Tooltip } from './components/ui/Tooltip'
 import { Footer } from './components/Footer'
 import { UpdateNotification } from './components/UpdateNotification'
 import { EpisodeDetailsModal } from './components/EpisodeDetailsModal'
+import { FilenameFormatPreview } from './components/FilenameFormatPreview'
 import { useTranslation, useFormatRelativeTime } from './i18n/LanguageContext'
 import { LanguageSelector } from './components/LanguageSelector'
 import logoImage from './assets/lumyradio-logo.png'
@@ -39,7 +40,7 @@ function App() {
     updateLastChecked,
   } = useSubscriptionsStore()
 
-  const { episodes, fetchEpisodes, addEpisode, updateEpisodeProgress, markEpisodeCompleted, markEpisodeFailed } = useEpisodesStore()
+  const { episodes, fetchEpisodes, addEpisode, replaceEpisode, updateEpisodeProgress, markEpisodeCompleted, markEpisodeFailed } = useEpisodesStore()
 
   const [showAddForm, setShowAddForm] = useState(false)
   const [editingSubscription, setEditingSubscription] = useState<Subscription | null>(null)
@@ -83,6 +84,17 @@ function App() {
       incrementEpisodeCount(event.payload.subscription_id)
     })
 
+    // Listen for episodes whose audio was replaced in the feed
+    const unsubscribeUpdated = listen<EpisodeUpdatedPayload>('episode-updated', (event) => {
+      replaceEpisode(event.payload.episode)
+    })
+
+    // Everything changed after a backup was restored
+    const unsubscribeRestored = listen('database-restored', () => {
+      fetchSubscriptions()
+      fetchEpisodes()
+    })
+
     // Listen for subscription checked events
     const unsubscribeChecked = listen<SubscriptionCheckedPayload>('subscription-checked', (event) => {
       updateLastChecked(event.payload.subscription_id)
@@ -94,6 +106,8 @@ function App() {
       unsubscribeCompleted.then((fn) => fn())
       unsubscribeFailed.then((fn) => fn())
       unsubscribeDiscovered.then((fn) => fn())
+      unsubscribeUpdated.then((fn) => fn())
+      unsubscribeRestored.then((fn) => fn())
       unsubscribeChecked.then((fn) => fn())
     }
   }, [])
@@ -374,9 +388,23 @@ function AddSubscriptionForm({ onClose }: { onClose: () => void }) {
   const [checkFrequency, setCheckFrequency] = useState(15)
   const [quality, setQuality] = useState<'enclosure' | 'original' | 'flac' | 'mp3'>('enclosure')
   const [maxEpisodes, setMaxEpisodes] = useState<number | null>(15)
-  const [filenameFormat, setFilenameFormat] = useState('{show} - {episode}')
+  const [filenameFormat, setFilenameFormat] = useState('{show}/{show} - {episode}')
+  const [maxItemsToCheck, setMaxItemsToCheck] = useState(100)
   const [isFetchingTitle, setIsFetchingTitle] = useState(false)
 
+  // Start from the same defaults as OPML imports
+  useEffect(() => {
+    subscriptionApi
+      .getDefaults()
+      .then((defaults) => {
+        setCheckFrequency(defaults.check_frequency_minutes)
+        setMaxItemsToCheck(defaults.max_items_to_check)
+        setQuality(defaults.preferred_quality)
+        setFilenameFormat(defaults.filename_format)
+      })
+      .catch((error) => console.error('Failed to load subscription defaults:', error))
+  }, [])
+
   // Pre-fill output directory from last subscription
   useEffect(() => {
     if (subscriptions.length > 0 && !outputDir) {
@@ -425,7 +453,7 @@ function AddSubscriptionForm({ onClose }: { onClose: () => void }) {
         rss_url: rssUrl,
         output_directory: outputDir,
         check_frequency_minutes: checkFrequency,
-        max_items_to_check: 100,
+        max_items_to_check: maxItemsToCheck,
         preferred_quality: quality,
         max_episodes: maxEpisodes,
         filename_format: filenameFormat,
@@ -541,10 +569,10 @@ function AddSubscriptionForm({ onClose }: { onClose: () => void }) {
                 className="flex h-10 w-full rounded-md border border-input bg-background px-3 py-2 text-sm ring-offset-background file:border-0 file:bg-transparent file:text-sm file:font-medium placeholder:text-muted-foreground focus-visible:outline-none focus-visible:ring-2 focus-visible:ring-ring focus-visible:ring-offset-2 disabled:cursor-not-allowed disabled:opacity-50"
                 required
               >
-                <option value="{show} - {episode}">{t('filenameFormatShowEpisode')}</option>
-                <option value="{episode}">{t('filenameFormatEpisodeOnly')}</option>
-                <option value="{episode} - {show}">{t('filenameFormatEpisodeShow')}</option>
-                <option value="{date}_{episode}">{t('filenameFormatDateEpisode')}</option>
+                <option value="{show}/{show} - {episode}">{t('filenameFormatShowEpisode')}</option>
+                <option value="{show}/{episode}">{t('filenameFormatEpisodeOnly')}</option>
+                <option value="{show}/{episode} - {show}">{t('filenameFormatEpisodeShow')}</option>
+                <option value="{show}/{date}_{episode}">{t('filenameFormatDateEpisode')}</option>
               </select>
               <Input
                 value={filenameFormat}
@@ -555,6 +583,7 @@ function AddSubscriptionForm({ onClose }: { onClose: () => void }) {
               <p className="text-xs text-muted-foreground mt-1">
                 {t('filenameFormatDescription')}
               </p>
+              <FilenameFormatPreview format={filenameFormat} />
             </div>
             <div className="flex gap-2">
               <Button type="submit" className="flex-1">
@@ -718,10 +747,10 @@ function EditSubscriptionForm({
                 className="flex h-10 w-full rounded-md border border-input bg-background px-3 py-2 text-sm ring-offset-background file:border-0 file:bg-transparent file:text-sm file:font-medium placeholder:text-muted-foreground focus-visible:outline-none focus-visible:ring-2 focus-visible:ring-ring focus-visible:ring-offset-2 disabled:cursor-not-allowed disabled:opacity-50"
                 required
               >
-                <option value="{show} - {episode}">{t('filenameFormatShowEpisode')}</option>
-                <option value="{episode}">{t('filenameFormatEpisodeOnly')}</option>
-                <option value="{episode} - {show}">{t('filenameFormatEpisodeShow')}</option>
-                <option value="{date}_{episode}">{t('filenameFormatDateEpisode')}</option>
+                <option value="{show}/{show} - {episode}">{t('filenameFormatShowEpisode')}</option>
+                <option value="{show}/{episode}">{t('filenameFormatEpisodeOnly')}</option>
+                <option value="{show}/{episode} - {show}">{t('filenameFormatEpisodeShow')}</option>
+                <option value="{show}/{date}_{episode}">{t('filenameFormatDateEpisode')}</option>
               </select>
               <Input
                 value={filenameFormat}
@@ -732,6 +761,7 @@ function EditSubscriptionForm({
               <p className="text-xs text-muted-foreground mt-1">
                 {t('filenameFormatDescription')}
               </p>
+              <FilenameFormatPreview format={filenameFormat} subscriptionId={subscription.id} />
             </div>
             <div className="flex gap-2">
               <Button type="submit" className="flex-1">
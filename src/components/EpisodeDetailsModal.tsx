@@ -53,6 +53,7 @@ export function EpisodeDetailsModal({ episode, onClose }: EpisodeDetailsModalPro
       case 'paused':
         return 'text-orange-500'
       case 'skipped':
+      case 'removed':
         return 'text-gray-500'
       default:
         return 'text-gray-500'
@@ -72,7 +73,9 @@ export function EpisodeDetailsModal({ episode, onClose }: EpisodeDetailsModalPro
       case 'paused':
         return t('paused')
       case 'skipped':
-        return t('pending')
+        return t('skipped')
+      case 'removed':
+        return t('removed')
       default:
         return status
     }
@@ -157,6 +160,16 @@ export function EpisodeDetailsModal({ episode, onClose }: EpisodeDetailsModalPro
             </div>
           )}
 
+          {/* Filter Information */}
+          {episode.skip_reason && (
+            <div>
+              <h3 className="text-sm font-medium text-foreground mb-2">{t('skipReason')}</h3>
+              <p className="text-sm text-muted-foreground bg-muted p-3 rounded-md">
+                {episode.skip_reason}
+              </p>
+            </div>
+          )}
+
           {/* Error Information */}
           {episode.download_error && (
             <div>
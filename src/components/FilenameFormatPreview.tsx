@@ -0,0 +1,52 @@
+import { useEffect, useState } from 'react'
+import { subscriptionApi } from '../lib/api'
+import type { FilenamePreview } from '../types/subscription'
+import { useTranslation } from '../i18n/LanguageContext'
+
+export function FilenameFormatPreview({
+  format,
+  subscriptionId,
+}: {
+  format: string
+  subscriptionId?: number
+}) {
+  const { t } = useTranslation()
+  const [previews, setPreviews] = useState<FilenamePreview[]>([])
+  const [error, setError] = useState<string | null>(null)
+
+  // Render the format against real episodes while typing (with debouncing)
+  useEffect(() => {
+    const timer = setTimeout(async () => {
+      try {
+        setPreviews(await subscriptionApi.previewFilenameFormat(format, subscriptionId))
+        setError(null)
+      } catch (error) {
+        setPreviews([])
+        setError(String(error))
+      }
+    }, 500)
+
+    return () => clearTimeout(timer)
+  }, [format, subscriptionId])
+
+  if (error) {
+    return <p className="text-xs text-destructive mt-1">{error}</p>
+  }
+
+  if (previews.length === 0) {
+    return null
+  }
+
+  return (
+    <div className="mt-2">
+      <p className="text-xs font-medium">{t('filenameFormatPreview')}</p>
+      <ul className="text-xs text-muted-foreground font-mono">
+        {previews.map((preview) => (
+          <li key={preview.episode_id} className="truncate" title={preview.path}>
+            {preview.path}
+          </li>
+        ))}
+      </ul>
+    </div>
+  )
+}
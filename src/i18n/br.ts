@@ -55,8 +55,9 @@ export const br = {
   filenameFormatEpisodeOnly: 'Rann hepken',
   filenameFormatEpisodeShow: 'Rann - Abadenn',
   filenameFormatDateEpisode: 'Deiziad_Rann',
-  filenameFormatCustomPlaceholder: 'Personelaet: {show}, {episode}, {date}',
-  filenameFormatDescription: 'Argemmennoù hegerz: {show}, {episode}, {date}',
+  filenameFormatCustomPlaceholder: 'Personelaet: {show}/{date:%Y/%m}/{episode}',
+  filenameFormatDescription: 'Argemmennoù: {show}, {episode}, {date}, {date:%Y/%m}, {year}, {guid}, {id}, {season:02}, {episode_number:02}, {radio_slug}, {automation}, {duration}, {program}. Siloù: {episode|max:80|lower|upper}. Implijit / evit an isteuliadoù.',
+  filenameFormatPreview: 'Rakwel',
 
   // Episodes
   episodes: 'Rannoù',
@@ -72,6 +73,8 @@ export const br = {
   failed: "C'hwitet",
   pending: 'War-c\'hortoz',
   paused: 'Ehanet',
+  skipped: 'Lezet a-gostez',
+  removed: 'Dilamet',
 
   // Episode details
   details: 'Munudoù',
@@ -89,6 +92,7 @@ export const br = {
   completed: 'Echu',
   path: 'Hent',
   error: 'Fazi',
+  skipReason: 'Lezet a-gostez gant ar sil',
   technicalInfo: 'Titouroù teknikel',
   discoveredOn: 'Dizoloet d\'an',
 
@@ -55,8 +55,9 @@ export const en = {
   filenameFormatEpisodeOnly: 'Episode only',
   filenameFormatEpisodeShow: 'Episode - Show',
   filenameFormatDateEpisode: 'Date_Episode',
-  filenameFormatCustomPlaceholder: 'Custom: {show}, {episode}, {date}',
-  filenameFormatDescription: 'Available variables: {show}, {episode}, {date}',
+  filenameFormatCustomPlaceholder: 'Custom: {show}/{date:%Y/%m}/{episode}',
+  filenameFormatDescription: 'Variables: {show}, {episode}, {date}, {date:%Y/%m}, {year}, {guid}, {id}, {season:02}, {episode_number:02}, {radio_slug}, {automation}, {duration}, {program}. Filters: {episode|max:80|lower|upper}. Use / for subfolders.',
+  filenameFormatPreview: 'Preview',
 
   // Episodes
   episodes: 'Episodes',
@@ -72,6 +73,8 @@ export const en = {
   failed: 'Failed',
   pending: 'Pending',
   paused: 'Paused',
+  skipped: 'Skipped',
+  removed: 'Removed',
 
   // Episode details
   details: 'Details',
@@ -89,6 +92,7 @@ export const en = {
   completed: 'Completed',
   path: 'Path',
   error: 'Error',
+  skipReason: 'Skipped by filter',
   technicalInfo: 'Technical Information',
   discoveredOn: 'Discovered on',
 
@@ -55,8 +55,9 @@ export const fr = {
   filenameFormatEpisodeOnly: 'Épisode uniquement',
   filenameFormatEpisodeShow: 'Épisode - Émission',
   filenameFormatDateEpisode: 'Date_Épisode',
-  filenameFormatCustomPlaceholder: 'Personnalisé : {show}, {episode}, {date}',
-  filenameFormatDescription: 'Variables disponibles : {show}, {episode}, {date}',
+  filenameFormatCustomPlaceholder: 'Personnalisé : {show}/{date:%Y/%m}/{episode}',
+  filenameFormatDescription: 'Variables : {show}, {episode}, {date}, {date:%Y/%m}, {year}, {guid}, {id}, {season:02}, {episode_number:02}, {radio_slug}, {automation}, {duration}, {program}. Filtres : {episode|max:80|lower|upper}. Utilisez / pour les sous-dossiers.',
+  filenameFormatPreview: 'Aperçu',
 
   // Episodes
   episodes: 'Épisodes',
@@ -72,6 +73,8 @@ export const fr = {
   failed: 'Échec',
   pending: 'En attente',
   paused: 'En pause',
+  skipped: 'Ignoré',
+  removed: 'Supprimé',
 
   // Episode details
   details: 'Détails',
@@ -89,6 +92,7 @@ export const fr = {
   completed: 'Terminé',
   path: 'Chemin',
   error: 'Erreur',
+  skipReason: 'Ignoré par le filtre',
   technicalInfo: 'Informations techniques',
   discoveredOn: 'Découvert le',
 
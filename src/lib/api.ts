@@ -1,7 +1,20 @@
 import { invoke } from '@tauri-apps/api/tauri'
-import type { Subscription, CreateSubscriptionData } from '../types/subscription'
+import type {
+  Subscription,
+  CreateSubscriptionData,
+  EpisodeFilter,
+  FilenamePreview,
+  IdentityStrategy,
+  OpmlImportReport,
+  RekeySummary,
+  RetentionAction,
+  RetentionHistoryEntry,
+  SubscriptionDefaults,
+} from '../types/subscription'
 import type { Episode, EpisodeStats } from '../types/episode'
 import type { UpdateInfo } from '../types/update'
+import type { DownloadQueueItem } from '../types/download'
+import type { BackupInfo } from '../types/backup'
 
 export interface AvailableMedia {
   standard_url: string | null
@@ -14,6 +27,7 @@ export interface AvailableMedia {
 export const subscriptionApi = {
   list: () => invoke<Subscription[]>('list_subscriptions'),
   get: (id: number) => invoke<Subscription>('get_subscription', { id }),
+  getDefaults: () => invoke<SubscriptionDefaults>('get_subscription_defaults'),
   create: (data: CreateSubscriptionData) =>
     invoke<Subscription>('create_subscription', { data }),
   update: (id: number, data: CreateSubscriptionData) =>
@@ -21,8 +35,29 @@ export const subscriptionApi = {
   delete: (id: number) => invoke<void>('delete_subscription', { id }),
   toggle: (id: number, enabled: boolean) =>
     invoke<void>('toggle_subscription', { id, enabled }),
+  getFilter: (subscriptionId: number) =>
+    invoke<EpisodeFilter>('get_episode_filter', { subscriptionId }),
+  setFilter: (subscriptionId: number, filter: EpisodeFilter) =>
+    invoke<EpisodeFilter>('set_episode_filter', { subscriptionId, filter }),
+  previewRetention: (subscriptionId: number) =>
+    invoke<RetentionAction[]>('preview_retention_policy', { subscriptionId }),
+  applyRetention: (subscriptionId: number) =>
+    invoke<RetentionAction[]>('apply_retention_policy', { subscriptionId }),
+  retentionHistory: (subscriptionId?: number | null) =>
+    invoke<RetentionHistoryEntry[]>('list_retention_history', { subscriptionId }),
   checkNow: (id: number) => invoke<void>('check_subscription_now', { id }),
   fetchRssTitle: (url: string) => invoke<string>('fetch_rss_title', { url }),
+  previewFilenameFormat: (filenameFormat: string, subscriptionId?: number | null) =>
+    invoke<FilenamePreview[]>('preview_filename_format', { filenameFormat, subscriptionId }),
+  rekey: (id: number, identityStrategy: IdentityStrategy) =>
+    invoke<RekeySummary>('rekey_subscription', { id, identityStrategy }),
+}
+
+// OPML API
+export const opmlApi = {
+  import: (content: string, outputDirectory?: string | null) =>
+    invoke<OpmlImportReport>('import_opml', { content, outputDirectory }),
+  export: () => invoke<string>('export_opml'),
 }
 
 // Episode API
@@ -34,7 +69,11 @@ export const episodeApi = {
     invoke<Episode[]>('list_episodes_by_status', { status }),
   get: (id: number) => invoke<Episode>('get_episode', { id }),
   retry: (id: number) => invoke<void>('retry_episode', { id }),
+  backfill: (subscriptionId: number, from?: string | null, to?: string | null) =>
+    invoke<number>('backfill_subscription', { subscriptionId, from, to }),
   delete: (id: number) => invoke<void>('delete_episode', { id }),
+  setProtected: (id: number, isProtected: boolean) =>
+    invoke<void>('set_episode_protected', { id, protected: isProtected }),
   getStats: () => invoke<EpisodeStats>('get_episode_stats'),
   verifyFile: (id: number) => invoke<boolean>('verify_episode_file', { id }),
   verifySubscriptionFiles: (subscriptionId: number) =>
@@ -51,10 +90,25 @@ export const settingsApi = {
   set: (key: string, value: string) => invoke<void>('set_setting', { key, value }),
 }
 
+// Backup API
+export const backupApi = {
+  create: (path?: string | null) => invoke<string>('create_backup', { path }),
+  list: () => invoke<BackupInfo[]>('list_backups'),
+  restore: (path: string) => invoke<void>('restore_backup', { path }),
+}
+
 // Download API
 export const downloadApi = {
   getQueueSize: () => invoke<number>('get_queue_size'),
+  listQueue: () => invoke<DownloadQueueItem[]>('list_download_queue'),
   clearQueue: () => invoke<void>('clear_queue'),
+  cancel: (episodeId: number) => invoke<void>('cancel_download', { episodeId }),
+  pause: (episodeId: number) => invoke<void>('pause_download', { episodeId }),
+  resume: (episodeId: number) => invoke<void>('resume_download', { episodeId }),
+  bump: (episodeId: number) => invoke<void>('bump_download', { episodeId }),
+  pauseAll: () => invoke<void>('pause_all_downloads'),
+  resumeAll: () => invoke<void>('resume_all_downloads'),
+  isPaused: () => invoke<boolean>('get_downloads_paused'),
 }
 
 // File system API
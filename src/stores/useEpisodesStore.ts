@@ -15,6 +15,7 @@ interface EpisodesStore {
   retryEpisode: (id: number) => Promise<void>
   deleteEpisode: (id: number) => Promise<void>
   addEpisode: (episode: Episode) => void
+  replaceEpisode: (episode: Episode) => void
   updateEpisodeProgress: (id: number, progress: number) => void
   markEpisodeCompleted: (id: number, filePath: string) => void
   markEpisodeFailed: (id: number, error: string) => void
@@ -116,6 +117,12 @@ export const useEpisodesStore = create<EpisodesStore>((set) => ({
     })
   },
 
+  replaceEpisode: (episode) => {
+    set((state) => ({
+      episodes: state.episodes.map((e) => (e.id === episode.id ? episode : e)),
+    }))
+  },
+
   updateEpisodeProgress: (id, progress) => {
     set((state) => ({
       episodes: state.episodes.map((e) =>
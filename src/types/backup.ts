@@ -0,0 +1,5 @@
+export interface BackupInfo {
+  path: string
+  created_at: string
+  size_bytes: number
+}
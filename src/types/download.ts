@@ -21,3 +21,43 @@ export interface DownloadFailedPayload {
   episode_id: number
   error: string
 }
+
+export interface DownloadRetryScheduledPayload {
+  episode_id: number
+  attempt: number
+  max_attempts: number
+  next_attempt_at: string
+  error: string
+}
+
+export interface HookFinishedPayload {
+  episode_id: number
+  subscription_id: number
+  event: 'completed' | 'failed'
+  succeeded: boolean
+}
+
+export interface DiskSpaceLowPayload {
+  episode_id: number
+  directory: string
+  required_bytes: number
+  available_bytes: number
+}
+
+export interface DownloadStateChangedPayload {
+  episode_id: number
+  subscription_id: number
+}
+
+export interface DownloadQueueStatePayload {
+  paused: boolean
+}
+
+export interface DownloadQueueItem {
+  id: number
+  episode_id: number
+  priority: number
+  added_at: string
+  next_attempt_at: string | null
+  paused: boolean
+}
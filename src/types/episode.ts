@@ -19,9 +19,33 @@ export interface Episode {
   download_error: string | null
   download_attempts: number
   discovered_at: string
+  resume_offset: number
+  resume_etag: string | null
+  resume_last_modified: string | null
+  content_updated_at: string | null
+  season: number | null
+  episode_number: number | null
+  hook_status: 'succeeded' | 'failed' | null
+  hook_exit_code: number | null
+  hook_output: string | null
+  hook_ran_at: string | null
+  skip_reason: string | null
+  download_size_bytes: number | null
+  protected: boolean
+  enclosure_url: string | null
+  enclosure_length: number | null
+  replaced_path: string | null
 }
 
-export type DownloadStatus = 'pending' | 'downloading' | 'completed' | 'failed' | 'skipped'
+export type DownloadStatus =
+  | 'pending'
+  | 'downloading'
+  | 'completed'
+  | 'failed'
+  | 'paused'
+  | 'cancelled'
+  | 'skipped'
+  | 'removed'
 
 export interface EpisodeStats {
   total: number
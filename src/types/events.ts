@@ -5,6 +5,12 @@ export interface EpisodeDiscoveredPayload {
   episode: Episode
 }
 
+export interface EpisodeUpdatedPayload {
+  subscription_id: number
+  episode: Episode
+  redownload: boolean
+}
+
 export interface SubscriptionCheckedPayload {
   subscription_id: number
   new_episodes_count: number
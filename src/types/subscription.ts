@@ -1,5 +1,10 @@
 export type QualityPreference = 'enclosure' | 'original' | 'flac' | 'mp3'
 
+export type IdentityStrategy = 'guid' | 'enclosure_url' | 'normalized_url' | 'content_hash'
+
+/** What the first check downloads from a new subscription's archive */
+export type InitialDownload = 'all' | 'none' | 'latest' | 'since'
+
 export interface Subscription {
   id: number
   name: string
@@ -12,7 +17,26 @@ export interface Subscription {
   enabled: boolean
   preferred_quality: QualityPreference
   max_episodes: number | null
+  max_storage_bytes: number | null
+  retention_days: number | null
+  retention_keep_completed: number | null
+  archive_directory: string | null
   filename_format: string
+  retry_max_attempts: number | null
+  retry_base_delay_seconds: number | null
+  ignore_bandwidth_schedule: boolean
+  download_priority: number
+  identity_strategy: IdentityStrategy
+  write_tags: boolean
+  tag_title_format: string
+  tag_artist_format: string
+  tag_album_format: string
+  tag_comment_format: string
+  on_completed_command: string
+  on_failed_command: string
+  initial_download: InitialDownload | null
+  initial_download_count: number | null
+  initial_download_since: string | null
   last_checked_at: string | null
   last_success_at: string | null
   last_error: string | null
@@ -32,5 +56,92 @@ export interface CreateSubscriptionData {
   max_items_to_check: number
   preferred_quality: QualityPreference
   max_episodes: number | null
+  /** 0 removes the quota, the current one is kept when missing */
+  max_storage_bytes?: number | null
+  /** 0 or an empty directory removes the rule, the current one is kept when missing */
+  retention_days?: number | null
+  retention_keep_completed?: number | null
+  archive_directory?: string | null
   filename_format: string
+  retry_max_attempts?: number | null
+  retry_base_delay_seconds?: number | null
+  ignore_bandwidth_schedule?: boolean
+  /** Higher priorities are downloaded first */
+  download_priority?: number
+  identity_strategy?: IdentityStrategy
+  write_tags?: boolean
+  tag_title_format?: string
+  tag_artist_format?: string
+  tag_album_format?: string
+  tag_comment_format?: string
+  on_completed_command?: string
+  on_failed_command?: string
+  /** Only used on creation, applied by the first check */
+  initial_download?: InitialDownload
+  initial_download_count?: number | null
+  initial_download_since?: string | null
+}
+
+/** Values of a new subscription, from the default settings */
+export interface SubscriptionDefaults {
+  check_frequency_minutes: number
+  max_items_to_check: number
+  preferred_quality: QualityPreference
+  filename_format: string
+}
+
+/** Rules new items must pass to be downloaded, null disables a rule */
+export interface EpisodeFilter {
+  subscription_id?: number
+  title_include: string | null
+  title_exclude: string | null
+  description_include: string | null
+  description_exclude: string | null
+  author_include: string | null
+  min_duration_seconds: number | null
+  max_duration_seconds: number | null
+  min_size_bytes: number | null
+  max_size_bytes: number | null
+  published_after: string | null
+}
+
+export interface OpmlImportReport {
+  imported: Subscription[]
+  duplicates: string[]
+  failed: string[]
+}
+
+export interface RekeySummary {
+  updated: number
+  unchanged: number
+  unmatched: number
+  conflicts: number
+}
+
+export interface FilenamePreview {
+  episode_id: number
+  title: string
+  path: string
+}
+
+/** A file or episode a retention rule removes */
+export interface RetentionAction {
+  episode_id: number
+  title: string
+  file_path: string | null
+  archive: boolean
+  remove_episode: boolean
+  reason: string
+}
+
+export interface RetentionHistoryEntry {
+  id: number
+  subscription_id: number
+  episode_id: number
+  episode_title: string
+  file_path: string | null
+  archive_path: string | null
+  episode_removed: boolean
+  reason: string
+  created_at: string
 }